@@ -0,0 +1,44 @@
+use std::env;
+use std::error::Error;
+use serde_json::Value;
+
+// URL del servidor en ejecución que administran los subcomandos
+fn server_url() -> String {
+    env::var("F1_SERVER_URL").unwrap_or_else(|_| "http://127.0.0.1:7000".to_string())
+}
+
+// Subcomando "snapshots" para administrar las copias del servidor en ejecución:
+//   http_server_proyecto1_so snapshots list
+//   http_server_proyecto1_so snapshots create
+//   http_server_proyecto1_so snapshots restore <id>
+pub fn run_snapshots_command(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let client = reqwest::blocking::Client::new();
+    let base = format!("{}/api/admin/snapshots", server_url());
+
+    let response = match args.first().map(String::as_str) {
+        Some("list") => client.get(&base).send()?,
+        Some("create") => client.post(&base).send()?,
+        Some("restore") => {
+            let id = args.get(1).ok_or("Uso: snapshots restore <id>")?;
+            client.post(format!("{}/{}/restore", base, id)).send()?
+        }
+        _ => return Err("Uso: snapshots <list|create|restore <id>>".into()),
+    };
+
+    let status = response.status();
+    let body: Value = response.json()?;
+    if !status.is_success() {
+        return Err(format!("El servidor respondió {}: {}", status, body["error"]).into());
+    }
+
+    if let Some(snapshots) = body["snapshots"].as_array() {
+        println!("{:<24} {:>10}", "ID", "BYTES");
+        for snapshot in snapshots {
+            println!("{:<24} {:>10}", snapshot["id"].as_str().unwrap_or(""), snapshot["size"]);
+        }
+    } else {
+        println!("{}", serde_json::to_string_pretty(&body)?);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,136 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde::Deserialize;
+
+use crate::json_hashmaps::datasets::{is_valid_dataset_name, DEFAULT_DATASET};
+use crate::utils::get_current_dir;
+
+// Configuración del servidor. Cada valor se toma, en orden de prioridad, de la línea de comandos,
+// de las variables de entorno o del archivo de configuración (JSON):
+//
+//   --config <archivo>          F1_CONFIG
+//   --data-path <archivo>       F1_DATA_PATH   "data_path": "<archivo>"
+//   --dataset <nombre>=<ruta>   F1_DATASETS    "datasets": { "<nombre>": "<ruta>" }
+//   --unique-drivers            F1_UNIQUE_DRIVERS=true   "unique_drivers": true
+//
+// F1_DATASETS separa los conjuntos con comas: 2023=data/f1_2023.json,2024=data/f1_2024.json
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    // Archivo del conjunto de datos por defecto (/api/escuderias)
+    pub data_path: PathBuf,
+    // Conjuntos de datos adicionales (/api/{dataset}/escuderias)
+    pub datasets: BTreeMap<String, PathBuf>,
+    // Un piloto solo puede pertenecer a una escudería
+    pub unique_drivers: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ConfigFile {
+    data_path: Option<PathBuf>,
+    datasets: BTreeMap<String, PathBuf>,
+    unique_drivers: Option<bool>,
+}
+
+impl Config {
+    // Leer la configuración de los argumentos y de las variables de entorno del proceso
+    pub fn load(args: &[String]) -> Result<Config, Box<dyn Error>> {
+        Self::from_sources(args, |name| env::var(name).ok())
+    }
+
+    pub fn from_sources(
+        args: &[String],
+        env_var: impl Fn(&str) -> Option<String>,
+    ) -> Result<Config, Box<dyn Error>> {
+        let mut cli_config = None;
+        let mut cli_data_path = None;
+        let mut cli_datasets = Vec::new();
+        let mut cli_unique_drivers = false;
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().cloned().ok_or_else(|| format!("Falta el valor de {}", arg));
+            match arg.as_str() {
+                "--config" => cli_config = Some(PathBuf::from(value()?)),
+                "--data-path" => cli_data_path = Some(PathBuf::from(value()?)),
+                "--dataset" => cli_datasets.push(parse_dataset(&value()?)?),
+                "--unique-drivers" => cli_unique_drivers = true,
+                other => return Err(format!("Argumento desconocido: {}", other).into()),
+            }
+        }
+
+        let cwd = get_current_dir()?;
+        let mut config = Config {
+            data_path: cwd.join("data/f1_data.json"),
+            datasets: BTreeMap::new(),
+            unique_drivers: false,
+        };
+
+        // Archivo de configuración; sus rutas relativas parten de la carpeta del archivo
+        if let Some(config_path) = cli_config.or_else(|| env_var("F1_CONFIG").map(PathBuf::from)) {
+            let content = fs::read_to_string(&config_path)
+                .map_err(|e| format!("Error reading {}: {}", config_path.display(), e))?;
+            let file: ConfigFile = serde_json::from_str(&content)
+                .map_err(|e| format!("Error parsing {}: {}", config_path.display(), e))?;
+            let base = cwd.join(config_path.parent().unwrap_or(Path::new("")));
+
+            if let Some(data_path) = file.data_path {
+                config.data_path = base.join(data_path);
+            }
+            for (name, path) in file.datasets {
+                config.datasets.insert(name, base.join(path));
+            }
+            if let Some(unique_drivers) = file.unique_drivers {
+                config.unique_drivers = unique_drivers;
+            }
+        }
+
+        // Variables de entorno
+        if let Some(data_path) = env_var("F1_DATA_PATH") {
+            config.data_path = cwd.join(data_path);
+        }
+        if let Some(datasets) = env_var("F1_DATASETS") {
+            for dataset in datasets.split(',').filter(|d| !d.trim().is_empty()) {
+                let (name, path) = parse_dataset(dataset.trim())?;
+                config.datasets.insert(name, cwd.join(path));
+            }
+        }
+        if let Some(unique_drivers) = env_var("F1_UNIQUE_DRIVERS") {
+            config.unique_drivers = match unique_drivers.trim().to_lowercase().as_str() {
+                "1" | "true" | "yes" => true,
+                "0" | "false" | "no" | "" => false,
+                other => return Err(format!("Valor inválido para F1_UNIQUE_DRIVERS: '{}'", other).into()),
+            };
+        }
+
+        // Línea de comandos
+        if let Some(data_path) = cli_data_path {
+            config.data_path = cwd.join(data_path);
+        }
+        for (name, path) in cli_datasets {
+            config.datasets.insert(name, cwd.join(path));
+        }
+        if cli_unique_drivers {
+            config.unique_drivers = true;
+        }
+
+        for name in config.datasets.keys() {
+            if !is_valid_dataset_name(name) || name == DEFAULT_DATASET {
+                return Err(format!("Nombre de conjunto de datos inválido: '{}'", name).into());
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+// Separar "<nombre>=<ruta>"
+fn parse_dataset(value: &str) -> Result<(String, PathBuf), Box<dyn Error>> {
+    let Some((name, path)) = value.split_once('=') else {
+        return Err(format!("Se esperaba <nombre>=<ruta> en '{}'", value).into());
+    };
+    Ok((name.trim().to_string(), PathBuf::from(path.trim())))
+}
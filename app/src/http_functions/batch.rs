@@ -0,0 +1,211 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::error::Error;
+use crate::json_hashmaps::audit::{driver_state, team_state, EntityChange, EntityState};
+use crate::json_hashmaps::models::{Driver, FieldError, Team, ValidationError};
+use crate::json_hashmaps::patch::{TeamPatch, JSON_PATCH_CONTENT_TYPE, MERGE_PATCH_CONTENT_TYPE};
+use crate::json_hashmaps::team_store::{find_team, BatchError, Mutation, TeamStore};
+use crate::http_functions::functions::entity_tags;
+use crate::utils::{now_utc, percent_decode};
+
+pub const MAX_BATCH_OPERATIONS: usize = 100;
+
+// Operación de un lote: la misma solicitud que se haría a la API, con la ruta relativa
+// a /api (o /api/{dataset}), p. ej. {"method": "DELETE", "path": "/escuderias/Ferrari/pilotos/Carlos Sainz"}
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BatchOperation {
+    pub method: String,
+    pub path: String,
+    #[serde(default)]
+    pub body: Value,
+    // Encabezados If-Match y Content-Type de la operación
+    pub if_match: Option<String>,
+    pub content_type: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BatchRequest {
+    operations: Vec<BatchOperation>,
+}
+
+// Resultado de una operación del lote
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OperationResult {
+    pub status: u16,
+    pub message: String,
+}
+
+impl BatchOperation {
+    // Segmentos de la ruta, ya decodificados
+    fn segments(&self) -> Vec<String> {
+        self.path
+            .trim_matches('/')
+            .split('/')
+            .map(|segment| percent_decode(segment, false))
+            .collect()
+    }
+
+    // Escudería y piloto que cambia la operación, para la auditoría; los que se crean se buscan
+    // por el nombre del cuerpo
+    fn entity_keys(&self) -> (String, Option<String>) {
+        let segments = self.segments();
+        let segments: Vec<&str> = segments.iter().map(String::as_str).collect();
+        let body_name = || self.body.get("name").and_then(Value::as_str).unwrap_or_default().to_string();
+        match segments.as_slice() {
+            [_, team_name, "pilotos"] => (team_name.to_string(), Some(body_name())),
+            [_, team_name, "pilotos", driver_name] => (team_name.to_string(), Some(driver_name.to_string())),
+            [_, team_name, ..] => (team_name.to_string(), None),
+            _ => (body_name(), None),
+        }
+    }
+
+    // Traducir la operación al cambio que aplica, al resultado que se informa si se aplica
+    // y al nombre con el que se registra en la auditoría
+    fn to_mutation(&self) -> Result<(Mutation, OperationResult, &'static str), Box<dyn Error>> {
+        let segments = self.segments();
+        let segments: Vec<&str> = segments.iter().map(String::as_str).collect();
+        let method = self.method.to_uppercase();
+
+        let (mutation, status, message, operation) = match (method.as_str(), segments.as_slice()) {
+            ("POST", ["escuderias"]) => {
+                let team = Team::from_value(self.body.clone())?;
+                (Mutation::Insert { team }, 201, "Team added", "post_team")
+            }
+            ("PUT", ["escuderias", team_name]) => {
+                let team = Team::from_value(self.body.clone())?;
+                (Mutation::Replace { team_name: team_name.to_string(), team }, 200, "Team updated", "put_team")
+            }
+            ("PATCH", ["escuderias", team_name]) => {
+                // Sin Content-Type, un arreglo es un JSON Patch y un objeto un Merge Patch
+                let content_type = self.content_type.as_deref().unwrap_or(if self.body.is_array() {
+                    JSON_PATCH_CONTENT_TYPE
+                } else {
+                    MERGE_PATCH_CONTENT_TYPE
+                });
+                let patch = TeamPatch::from_body(content_type, self.body.clone())?;
+                (Mutation::PatchTeam { team_name: team_name.to_string(), patch }, 200, "Team updated", "patch_team")
+            }
+            ("DELETE", ["escuderias", team_name]) => {
+                (Mutation::SoftDelete { team_name: team_name.to_string(), date: now_utc() }, 200, "Team deleted", "delete_team")
+            }
+            ("POST", ["escuderias", team_name, "restore"]) => {
+                (Mutation::Restore { team_name: team_name.to_string() }, 200, "Team restored", "restore_team")
+            }
+            ("POST", ["escuderias", team_name, "pilotos"]) => {
+                let driver = Driver::from_value(self.body.clone())?;
+                (Mutation::InsertDriver { team_name: team_name.to_string(), driver }, 201, "Driver added", "post_driver")
+            }
+            ("PUT", ["escuderias", team_name, "pilotos", driver_name]) => {
+                let driver = Driver::from_value(self.body.clone())?;
+                let mutation = Mutation::ReplaceDriver {
+                    team_name: team_name.to_string(),
+                    driver_name: driver_name.to_string(),
+                    driver,
+                };
+                (mutation, 200, "Driver updated", "put_driver")
+            }
+            ("PATCH", ["escuderias", team_name, "pilotos", driver_name]) => {
+                let Some(changes) = self.body.as_object() else {
+                    return Err(invalid("body", "Se esperaba un objeto JSON"));
+                };
+                let mutation = Mutation::PatchDriver {
+                    team_name: team_name.to_string(),
+                    driver_name: driver_name.to_string(),
+                    changes: changes.clone(),
+                };
+                (mutation, 200, "Driver updated", "patch_driver")
+            }
+            ("DELETE", ["escuderias", team_name, "pilotos", driver_name]) => {
+                let mutation = Mutation::DeleteDriver {
+                    team_name: team_name.to_string(),
+                    driver_name: driver_name.to_string(),
+                };
+                (mutation, 200, "Driver deleted", "delete_driver")
+            }
+            _ => {
+                return Err(invalid(
+                    "path",
+                    &format!("Operación no soportada en un lote: {} {}", self.method, self.path),
+                ))
+            }
+        };
+
+        // If-Match se verifica contra la escudería de la ruta antes de aplicar la operación
+        let mutation = match (&self.if_match, segments.get(1)) {
+            (Some(if_match), Some(team_name)) => Mutation::Batch {
+                mutations: vec![
+                    Mutation::CheckVersion { team_name: team_name.to_string(), versions: entity_tags(if_match, false) },
+                    mutation,
+                ],
+            },
+            _ => mutation,
+        };
+
+        Ok((mutation, OperationResult { status, message: message.to_string() }, operation))
+    }
+}
+
+// Aplicar todas las operaciones del cuerpo {"operations": [...]} como un único cambio:
+// se aplican todas o ninguna. Si una falla, el error es un BatchError con su posición.
+// Devuelve el resultado y, para la auditoría, el cambio de cada operación.
+pub fn apply_batch(body: Value, store: &dyn TeamStore) -> Result<(Vec<OperationResult>, Vec<EntityChange>), Box<dyn Error>> {
+    let request: BatchRequest = serde_path_to_error::deserialize(body).map_err(ValidationError::from)?;
+    if request.operations.is_empty() || request.operations.len() > MAX_BATCH_OPERATIONS {
+        return Err(invalid(
+            "operations",
+            &format!("El lote debe tener entre 1 y {} operaciones", MAX_BATCH_OPERATIONS),
+        ));
+    }
+
+    let mut mutations = Vec::with_capacity(request.operations.len());
+    let mut results = Vec::with_capacity(request.operations.len());
+    let mut names = Vec::with_capacity(request.operations.len());
+    for (index, operation) in request.operations.iter().enumerate() {
+        let (mutation, result, name) = operation.to_mutation().map_err(|error| BatchError::boxed(index, error))?;
+        mutations.push(mutation);
+        results.push(result);
+        names.push(name);
+    }
+
+    // El cambio de cada operación se toma mientras se aplica el lote, con los datos bloqueados:
+    // lo registrado es exactamente lo que se aplicó, y después de guardarlo ya no puede fallar nada
+    let keys: Vec<(String, Option<String>)> = request.operations.iter().map(BatchOperation::entity_keys).collect();
+    let mut changes = Vec::with_capacity(mutations.len());
+    let mut observe = |index: usize, before: &[Team], after: &[Team]| {
+        let (team_name, driver_name) = &keys[index];
+        let before = operation_entity(before, team_name, driver_name.as_deref());
+        // Después se busca por identificador, por si la operación cambió el nombre
+        let (team_name, driver_name) = match &before {
+            Some((path, _)) => {
+                let ids: Vec<&str> = path.split('/').collect();
+                (ids[1].to_string(), ids.get(3).map(|id| id.to_string()))
+            }
+            None => (team_name.clone(), driver_name.clone()),
+        };
+        let after = operation_entity(after, &team_name, driver_name.as_deref());
+        changes.push(EntityChange { operation: names[index].to_string(), before, after });
+    };
+    store.apply_observed(Mutation::Batch { mutations }, &mut observe).map_err(|error| match error.downcast::<BatchError>() {
+        // Quitar el lote interno de If-Match, conservando la posición de la operación
+        Ok(batch_error) => BatchError::boxed(batch_error.index, BatchError::unwrap_nested(batch_error.error)),
+        Err(error) => error,
+    })?;
+    Ok((results, changes))
+}
+
+fn operation_entity(teams: &[Team], team_name: &str, driver_name: Option<&str>) -> EntityState {
+    let team = find_team(teams, team_name)?;
+    match driver_name {
+        Some(driver_name) => driver_state(team, driver_name),
+        None => Some(team_state(team)),
+    }
+}
+
+fn invalid(field: &str, message: &str) -> Box<dyn Error> {
+    ValidationError {
+        errors: vec![FieldError::new(field, message)],
+    }
+    .into()
+}
@@ -1,82 +1,296 @@
+use serde::Deserialize;
 use serde_json::Value;
-use std::{collections::HashMap, error::Error};
-use crate::json_hashmaps::f1_data_hashmap::write_json_to_file;
+use std::error::Error;
+use crate::json_hashmaps::models::{Driver, FieldError, Team, Transfer, ValidationError};
+use crate::json_hashmaps::patch::TeamPatch;
+use crate::json_hashmaps::search_index::{IndexedStore, SearchResult};
+use crate::json_hashmaps::audit::{team_entity_changes, EntityChange};
+use crate::json_hashmaps::team_store::{driver_position, find_team, BatchError, Mutation, TeamStore};
+use crate::http_functions::query::{ListQuery, Page};
+use crate::utils::now_utc;
 
-pub fn post_team(
-    new_team: Value,
-    f1_data: &mut HashMap<String, Value>, // Cambiar para tomar una referencia mutable
-) -> Result<(), Box<dyn Error>> {
-    let teams = f1_data.get_mut("teams").unwrap().as_array_mut().unwrap();
+pub fn get_teams(store: &dyn TeamStore) -> Result<Vec<Team>, Box<dyn Error>> {
+    store.list()
+}
 
-    // Verificar si el equipo ya existe
-    if teams.iter().any(|team| team["name"] == new_team["name"]) {
-        return Err("El equipo ya existe".into());
-    }
+// Escuderías filtradas, ordenadas y paginadas según los parámetros de consulta
+pub fn list_teams(
+    store: &dyn TeamStore,
+    query: &ListQuery,
+) -> Result<Page, Box<dyn Error>> {
+    let teams = if query.include_deleted() { store.list_all()? } else { store.list()? };
+    let teams = teams
+        .into_iter()
+        .map(serde_json::to_value)
+        .collect::<Result<Vec<Value>, _>>()?;
+    query.apply(teams)
+}
 
-    teams.push(new_team);
-    write_json_to_file(f1_data);
-    Ok(())
+pub fn get_team(
+    team_name: &str,
+    store: &dyn TeamStore,
+) -> Result<Team, Box<dyn Error>> {
+    store
+        .get(team_name)?
+        .ok_or_else(|| format!("El equipo '{}' no existe", team_name).into())
 }
 
+// Agregar una escudería y devolverla con el identificador que le asignó el servidor
+pub fn post_team(
+    new_team: Value,
+    store: &dyn TeamStore,
+) -> Result<Team, Box<dyn Error>> {
+    let new_team = Team::from_value(new_team)?;
+    let name = new_team.name.clone();
+    store.insert(new_team)?;
+    store
+        .list()?
+        .into_iter()
+        .find(|team| team.name == name)
+        .ok_or_else(|| format!("El equipo '{}' no existe", name).into())
+}
+
+// Los cambios sobre una escudería reciben el encabezado If-Match, si llegó.
+// Con If-Match el cambio solo se aplica si la escudería sigue en una de esas versiones.
 pub fn put_team(
     team_name: &str,
     new_team: Value,
-    f1_data: &mut HashMap<String, Value>, // Cambiar para tomar una referencia mutable
+    if_match: Option<&str>,
+    store: &dyn TeamStore,
 ) -> Result<(), Box<dyn Error>> {
-    let teams: &mut Vec<Value> = f1_data.get_mut("teams").unwrap().as_array_mut().unwrap();
-
-    // Verificar si el equipo existe
-    if let Some(pos) = teams.iter().position(|team| team["name"] == team_name) {
-        teams[pos] = new_team; // Actualizar el equipo
-        write_json_to_file(f1_data);
-        Ok(())
-    } else {
-        return Err(format!("El equipo '{}' no existe", team_name).into());
+    let new_team = Team::from_value(new_team)?;
+    match if_match {
+        Some(if_match) => apply_if_match(
+            team_name,
+            if_match,
+            Mutation::Replace { team_name: team_name.to_string(), team: new_team },
+            store,
+        ),
+        None => store.replace(team_name, new_team),
     }
 }
 
+// Las escuderías no se borran: se marcan como eliminadas hasta que se purgan
 pub fn delete_team(
     team_name: &str,
-    f1_data: &mut HashMap<String, Value>, // Cambiar para tomar una referencia mutable
+    if_match: Option<&str>,
+    store: &dyn TeamStore,
+) -> Result<(), Box<dyn Error>> {
+    let mutation = Mutation::SoftDelete { team_name: team_name.to_string(), date: now_utc() };
+    match if_match {
+        Some(if_match) => apply_if_match(team_name, if_match, mutation, store),
+        None => store.apply_mutation(mutation),
+    }
+}
+
+// Deshacer la eliminación de una escudería que todavía no se purgó y devolverla
+pub fn restore_team(
+    team_name: &str,
+    store: &dyn TeamStore,
+) -> Result<Team, Box<dyn Error>> {
+    store.apply_mutation(Mutation::Restore { team_name: team_name.to_string() })?;
+    get_team(team_name, store)
+}
+
+// PATCH de una escudería con application/merge-patch+json o application/json-patch+json
+pub fn patch_team(
+    team_name: &str,
+    content_type: &str,
+    body: Value,
+    if_match: Option<&str>,
+    store: &dyn TeamStore,
 ) -> Result<(), Box<dyn Error>> {
-    let teams = f1_data.get_mut("teams").unwrap().as_array_mut().unwrap();
-
-    // Verificar si el equipo existe
-    if let Some(pos) = teams.iter().position(|team| team["name"] == team_name) {
-        teams.remove(pos); // Eliminar el equipo si se encuentra
-        write_json_to_file(f1_data);
-        Ok(())
-    } else {
-        Err(format!("El equipo '{}' no existe", team_name).into()) // Retornar un error si no se encuentra
+    let patch = TeamPatch::from_body(content_type, body)?;
+    match if_match {
+        Some(if_match) => apply_if_match(
+            team_name,
+            if_match,
+            Mutation::PatchTeam { team_name: team_name.to_string(), patch },
+            store,
+        ),
+        None => store.patch_team(team_name, &patch),
     }
 }
 
+// Verificar la versión y aplicar el cambio de forma atómica
+fn apply_if_match(
+    team_name: &str,
+    if_match: &str,
+    mutation: Mutation,
+    store: &dyn TeamStore,
+) -> Result<(), Box<dyn Error>> {
+    let check = Mutation::CheckVersion {
+        team_name: team_name.to_string(),
+        versions: entity_tags(if_match, false),
+    };
+    store
+        .apply_mutation(Mutation::Batch { mutations: vec![check, mutation] })
+        .map_err(BatchError::unwrap_nested)
+}
+
+// Etiquetas de un encabezado If-Match o If-None-Match: "a", W/"b" o *.
+// Las débiles (W/) solo se incluyen si `weak`, porque If-Match usa la comparación fuerte.
+pub fn entity_tags(header: &str, weak: bool) -> Vec<String> {
+    header
+        .split(',')
+        .map(str::trim)
+        .filter_map(|tag| match tag.strip_prefix("W/") {
+            Some(tag) => weak.then_some(tag),
+            None => Some(tag),
+        })
+        .map(|tag| tag.trim_matches('"').to_string())
+        .filter(|tag| !tag.is_empty())
+        .collect()
+}
+
 pub fn patch_driver(
     team_name: &str,
     driver_name: &str,
     updated_data: Value,
-    f1_data: &mut HashMap<String, Value>, // Cambiar para tomar una referencia mutable
+    store: &dyn TeamStore,
 ) -> Result<(), Box<dyn Error>> {
-    let teams = f1_data.get_mut("teams").unwrap().as_array_mut().unwrap();
+    let Some(changes) = updated_data.as_object() else {
+        return Err(ValidationError {
+            errors: vec![FieldError::new("body", "Se esperaba un objeto JSON")],
+        }
+        .into());
+    };
 
-    // Verificar si el equipo existe
-    if let Some(team) = teams.iter_mut().find(|team| team["name"] == team_name) {
-        let drivers = team.get_mut("drivers").unwrap().as_array_mut().unwrap();
+    store.patch_driver(team_name, driver_name, changes)
+}
 
-        // Verificar si el conductor existe
-        if let Some(driver) = drivers.iter_mut().find(|driver| driver["name"] == driver_name) {
-            for (key, value) in updated_data.as_object().unwrap() {
-                driver[key] = value.clone();
+pub fn get_drivers(
+    team_name: &str,
+    store: &dyn TeamStore,
+) -> Result<Vec<Driver>, Box<dyn Error>> {
+    match store.get(team_name)? {
+        Some(team) => Ok(team.drivers),
+        None => Err(format!("El equipo '{}' no existe", team_name).into()),
+    }
+}
+
+pub fn get_driver(
+    team_name: &str,
+    driver_name: &str,
+    store: &dyn TeamStore,
+) -> Result<Driver, Box<dyn Error>> {
+    let team = get_team(team_name, store)?;
+    let position = driver_position(&team, driver_name)?;
+    Ok(team.drivers[position].clone())
+}
+
+// Agregar un piloto y devolverlo con el identificador que le asignó el servidor
+pub fn post_driver(
+    team_name: &str,
+    new_driver: Value,
+    store: &dyn TeamStore,
+) -> Result<Driver, Box<dyn Error>> {
+    let new_driver = Driver::from_value(new_driver)?;
+    let name = new_driver.name.clone();
+    store.insert_driver(team_name, new_driver)?;
+    get_drivers(team_name, store)?
+        .into_iter()
+        .find(|driver| driver.name == name)
+        .ok_or_else(|| format!("El conductor '{}' no existe en el equipo '{}'", name, team_name).into())
+}
+
+pub fn put_driver(
+    team_name: &str,
+    driver_name: &str,
+    new_driver: Value,
+    store: &dyn TeamStore,
+) -> Result<(), Box<dyn Error>> {
+    let new_driver = Driver::from_value(new_driver)?;
+    store.replace_driver(team_name, driver_name, new_driver)
+}
+
+pub fn delete_driver(
+    team_name: &str,
+    driver_name: &str,
+    store: &dyn TeamStore,
+) -> Result<(), Box<dyn Error>> {
+    store.delete_driver(team_name, driver_name)
+}
+
+// Cuerpo de POST /api/pilotos/:driver/transfer
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TransferRequest {
+    to: String,
+    // Escudería de origen; solo es necesaria si el piloto está en varias
+    from: Option<String>,
+}
+
+// Traspasar un piloto a otra escudería en un solo cambio, registrando la fecha del traspaso.
+// Devuelve el traspaso y, para la auditoría, el cambio de las dos escuderías.
+pub fn transfer_driver(
+    driver_name: &str,
+    body: Value,
+    store: &dyn TeamStore,
+) -> Result<(Transfer, Vec<EntityChange>), Box<dyn Error>> {
+    let request: TransferRequest = serde_path_to_error::deserialize(body).map_err(ValidationError::from)?;
+
+    let from_team = match request.from {
+        Some(from) => get_team(&from, store)?,
+        None => {
+            // El piloto se busca por su identificador y, si ninguno coincide, por su nombre
+            let teams = store.list()?;
+            let with_driver = |matches: &dyn Fn(&Driver) -> bool| -> Vec<Team> {
+                teams.iter().filter(|team| team.drivers.iter().any(matches)).cloned().collect()
+            };
+            let mut from_teams = with_driver(&|driver| driver.id == driver_name);
+            if from_teams.is_empty() {
+                from_teams = with_driver(&|driver| driver.name == driver_name);
+            }
+            match from_teams.len() {
+                0 => return Err(format!("El conductor '{}' no existe", driver_name).into()),
+                1 => from_teams.remove(0),
+                _ => {
+                    let names: Vec<&str> = from_teams.iter().map(|team| team.name.as_str()).collect();
+                    return Err(ValidationError {
+                        errors: vec![FieldError::new(
+                            "from",
+                            &format!("El conductor está en varias escuderías ({}); indique desde cuál", names.join(", ")),
+                        )],
+                    }
+                    .into());
+                }
             }
-            write_json_to_file(f1_data);
-            Ok(())
-        } else {
-            Err(format!(
-                "El conductor '{}' no existe en el equipo '{}'",
-                driver_name, team_name
-            ).into())
         }
-    } else {
-        Err(format!("El equipo '{}' no existe", team_name).into())
+    };
+    let driver_id = from_team.drivers[driver_position(&from_team, driver_name)?].id.clone();
+
+    // El traspaso registrado (con los nombres de las escuderías) y el cambio de cada escudería se toman
+    // mientras se aplica, con los datos bloqueados: otro cambio no se puede colar entre medio
+    let mut applied = None;
+    store.apply_observed(
+        Mutation::TransferDriver {
+            driver_name: driver_id.clone(),
+            from: from_team.id,
+            to: request.to.clone(),
+            date: now_utc(),
+        },
+        &mut |_, before, after| {
+            let transfer = find_team(after, &request.to)
+                .and_then(|team| team.drivers.iter().find(|driver| driver.id == driver_id))
+                .and_then(|driver| driver.transfers.last().cloned());
+            applied = transfer.map(|transfer| (transfer, team_entity_changes("transfer_driver", before, after)));
+        },
+    )?;
+    applied.ok_or_else(|| format!("El conductor '{}' no tiene traspasos", driver_id).into())
+}
+
+pub fn search(
+    query: &str,
+    limit: usize,
+    store: &IndexedStore,
+) -> Result<Vec<SearchResult>, Box<dyn Error>> {
+    if query.trim().is_empty() {
+        return Err(ValidationError {
+            errors: vec![FieldError::new("q", "La búsqueda no puede estar vacía")],
+        }
+        .into());
     }
-}
\ No newline at end of file
+
+    store.search(query, limit)
+}
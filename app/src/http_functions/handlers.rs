@@ -0,0 +1,525 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::io::Write;
+use std::net::TcpStream;
+use std::sync::Arc;
+use serde_json::{json, Map, Value};
+
+use crate::http_functions::functions::entity_tags;
+use crate::http_functions::query::{Page, Projection};
+use crate::json_hashmaps::audit::{Actor, AuditLog, EntityChange, EntityState};
+use crate::json_hashmaps::datasets::{DatasetNotFound, Datasets, DEFAULT_DATASET};
+use crate::json_hashmaps::formats::UnsupportedFormat;
+use crate::json_hashmaps::models::ValidationError;
+use crate::json_hashmaps::patch::UnsupportedMediaType;
+use crate::json_hashmaps::seasons::SeasonNotFound;
+use crate::json_hashmaps::snapshots::SnapshotNotFound;
+use crate::json_hashmaps::team_store::{BatchError, ConflictError, PreconditionFailed, StorageError, TeamStore};
+use crate::json_hashmaps::webhooks::WebhookNotFound;
+use crate::server_http::negotiation::{render, Representation};
+use crate::server_http::server::Server;
+use crate::utils::{percent_decode, percent_encode};
+
+// Solicitud tal como la entrega el router (método, ruta, encabezados, parámetros, cuerpo...)
+pub type Request = HashMap<String, Value>;
+
+// Sesión del cliente: el valor de la cookie de la solicitud o el de una nueva
+pub type Session = dyn Fn(&Request) -> String + Send + Sync;
+
+// Error de una solicitud mal formada (sin cuerpo, parámetros inválidos); se responde con 400
+#[derive(Debug)]
+pub struct BadRequest(pub String);
+
+impl fmt::Display for BadRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Error for BadRequest {}
+
+// Solicitud de una ruta de la API con su sesión, su cliente, los conjuntos de datos y la auditoría
+pub struct ApiRequest {
+    pub request: Request,
+    pub cookie_value: String,
+    client_ip: String,
+    datasets: Arc<Datasets>,
+    audit: Arc<AuditLog>,
+}
+
+impl ApiRequest {
+    // Parámetro de la ruta (p. ej. :name), ya decodificado (%20 -> espacio); vacío si no está
+    pub fn param(&self, name: &str) -> String {
+        path_param(&self.request, name).unwrap_or_default()
+    }
+
+    // Año o ronda de la ruta de una temporada (:year, :round)
+    pub fn number(&self, name: &str) -> Result<u32, Box<dyn Error>> {
+        path_param(&self.request, name)
+            .and_then(|value| value.parse().ok())
+            .ok_or_else(|| format!("Invalid {}", name).into())
+    }
+
+    // Parámetro de consulta (?name=valor)
+    pub fn query(&self, name: &str) -> Option<&str> {
+        self.request.get("query")?.get(name)?.as_str()
+    }
+
+    // Todos los parámetros de consulta
+    pub fn query_map(&self) -> Map<String, Value> {
+        self.request.get("query").and_then(Value::as_object).cloned().unwrap_or_default()
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        header(&self.request, name)
+    }
+
+    pub fn body(&self) -> Result<&Value, Box<dyn Error>> {
+        self.request.get("body").ok_or_else(|| Box::new(BadRequest("Invalid request body".to_string())) as Box<dyn Error>)
+    }
+
+    // Cuerpo de la solicitud como texto; los cuerpos que no son JSON ya llegan como cadena
+    pub fn text(&self) -> Result<String, Box<dyn Error>> {
+        match self.body()? {
+            Value::String(text) => Ok(text.clone()),
+            body => Ok(body.to_string()),
+        }
+    }
+
+    pub fn path(&self) -> &str {
+        request_path(&self.request)
+    }
+
+    // Nombre del conjunto de datos de la ruta (:dataset) o del conjunto por defecto
+    pub fn dataset(&self) -> String {
+        path_param(&self.request, "dataset").unwrap_or_else(|| DEFAULT_DATASET.to_string())
+    }
+
+    // Almacenamiento del conjunto de datos de la ruta; DatasetNotFound (404) si no existe
+    pub fn store(&self) -> Result<Arc<dyn TeamStore>, Box<dyn Error>> {
+        self.datasets.get(&self.dataset())
+    }
+
+    pub fn datasets(&self) -> &Datasets {
+        &self.datasets
+    }
+
+    pub fn audit(&self) -> &AuditLog {
+        &self.audit
+    }
+
+    // Quién hace la solicitud, para la auditoría
+    pub fn actor(&self) -> Actor {
+        Actor {
+            session: self.cookie_value.clone(),
+            client_ip: self.client_ip.clone(),
+        }
+    }
+
+    // Aplicar un cambio registrándolo en la auditoría del conjunto de datos de la solicitud
+    pub fn record<T>(
+        &self,
+        operation: &str,
+        entity: impl Fn(Option<&T>) -> Result<EntityState, Box<dyn Error>>,
+        change: impl FnOnce() -> Result<T, Box<dyn Error>>,
+    ) -> Result<T, Box<dyn Error>> {
+        self.audit.record(&self.actor(), &self.dataset(), operation, entity, change)
+    }
+
+    // Igual que `record`, para los cambios que afectan a varias entidades
+    pub fn record_all<T>(
+        &self,
+        change: impl FnOnce() -> Result<(T, Vec<EntityChange>), Box<dyn Error>>,
+    ) -> Result<T, Box<dyn Error>> {
+        self.audit.record_all(&self.actor(), &self.dataset(), change)
+    }
+
+    // Dejar solo los campos pedidos con ?fields=name,drivers.name
+    pub fn project(&self, value: Value) -> Result<Value, Box<dyn Error>> {
+        match self.query("fields") {
+            Some(fields) => Ok(Projection::parse(fields)?.apply(value)),
+            None => Ok(value),
+        }
+    }
+
+    // Encabezado Link con las páginas siguiente y anterior, conservando los demás parámetros de consulta
+    pub fn links(&self, page: &Page) -> Option<String> {
+        let links: Vec<String> = [("next", &page.next_cursor), ("prev", &page.prev_cursor)]
+            .into_iter()
+            .filter_map(|(rel, cursor)| {
+                let mut query = self.query_map();
+                query.insert("cursor".to_string(), json!(cursor.as_ref()?));
+                Some(format!("<{}?{}>; rel=\"{}\"", self.path(), query_string(&query), rel))
+            })
+            .collect();
+        Some(links.join(", ")).filter(|links| !links.is_empty())
+    }
+}
+
+// Respuesta de una ruta de la API; la cookie de sesión se agrega al enviarla
+pub struct ApiResponse {
+    status: String,
+    content_type: Option<String>,
+    headers: String,
+    body: String,
+}
+
+impl ApiResponse {
+    // Respuesta JSON; se envía en el formato que pida Accept
+    pub fn json(status: &str, body: Value) -> Self {
+        Self::content(status, "application/json", body.to_string())
+    }
+
+    pub fn ok(body: Value) -> Self {
+        Self::json("200 OK", body)
+    }
+
+    // 201 con la ruta del recurso creado
+    pub fn created(location: &str, body: Value) -> Self {
+        Self::json("201 Created", body).header("Location", location)
+    }
+
+    // 200 con un mensaje ({"message": "Team updated"})
+    pub fn message(message: &str) -> Self {
+        Self::ok(json!({ "message": message }))
+    }
+
+    // Respuesta con un cuerpo ya serializado (p. ej. una exportación en CSV)
+    pub fn content(status: &str, content_type: &str, body: String) -> Self {
+        ApiResponse {
+            status: status.to_string(),
+            content_type: Some(content_type.to_string()),
+            headers: String::new(),
+            body,
+        }
+    }
+
+    // Los errores de validación se responden con 422 y la lista de campos inválidos, los conflictos
+    // con cambios hechos al archivo en disco con 409 y los errores al guardar los datos con 500.
+    // `status` es el estado de los errores que no son de un tipo conocido.
+    pub fn error(error: &(dyn Error + 'static), status: &str) -> Self {
+        Self::json(error_status(error, status), error_body(error))
+    }
+
+    // Respuesta de un lote que no se aplicó: el estado es el de la operación que falló y
+    // cada operación informa su resultado (424 para las que no se aplicaron por esa falla)
+    pub fn batch_error(batch_error: &BatchError, operation_count: usize) -> Self {
+        let status = error_status(batch_error.error.as_ref(), "400 Bad Request");
+        let results: Vec<Value> = (0..operation_count.max(batch_error.index + 1))
+            .map(|index| {
+                if index == batch_error.index {
+                    let mut result = error_body(batch_error.error.as_ref());
+                    result["status"] = json!(status.split(' ').next().unwrap_or_default().parse::<u16>().unwrap_or(400));
+                    result
+                } else {
+                    json!({ "status": 424, "error": format!("No se aplicó porque falló la operación {}", batch_error.index) })
+                }
+            })
+            .collect();
+
+        Self::json(status, json!({ "error": batch_error.to_string(), "index": batch_error.index, "results": results }))
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push_str(&format!("{}: {}\r\n", name, value));
+        self
+    }
+
+    // Agregar el ETag, o responder 304 sin cuerpo si If-None-Match ya incluye esa versión.
+    // El 304 no tiene cuerpo, así que se indica Connection: close para que el cliente no reutilice
+    // la conexión (el servidor atiende una solicitud por conexión).
+    // Cada formato de respuesta (Accept) es otra representación y tiene su propio ETag.
+    pub fn cached(self, request: &Request, etag: &str) -> Self {
+        let etag = match Representation::negotiate(header(request, "Accept")) {
+            Ok(Representation::Json) | Err(_) => etag.to_string(),
+            Ok(representation) => format!("{}-{}", etag, representation.name()),
+        };
+        let not_modified = header(request, "If-None-Match")
+            .is_some_and(|tags| entity_tags(tags, true).iter().any(|tag| tag == "*" || *tag == etag));
+        if not_modified {
+            return ApiResponse {
+                status: "304 Not Modified".to_string(),
+                content_type: None,
+                headers: format!("ETag: \"{}\"\r\nConnection: close\r\n", etag),
+                body: String::new(),
+            };
+        }
+
+        ApiResponse {
+            headers: format!("ETag: \"{}\"\r\n{}", etag, self.headers),
+            ..self
+        }
+    }
+
+    // Respuesta HTTP completa con la cookie de sesión
+    pub fn to_http(&self, cookie_value: &str) -> String {
+        let content_type = self.content_type.as_ref().map(|content_type| format!("Content-Type: {}\r\n", content_type));
+        format!(
+            "HTTP/1.1 {}\r\n{}{}Set-Cookie: session={}; Max-Age=60; HttpOnly\r\n\r\n{}",
+            self.status,
+            content_type.unwrap_or_default(),
+            self.headers,
+            cookie_value,
+            self.body
+        )
+    }
+}
+
+// Cuerpo JSON de un error; los de validación incluyen la lista de campos inválidos
+pub fn error_body(error: &(dyn Error + 'static)) -> Value {
+    match error.downcast_ref::<ValidationError>() {
+        Some(validation) => json!({ "error": "Datos inválidos", "errors": validation.errors }),
+        None => json!({ "error": error.to_string() }),
+    }
+}
+
+// Código de estado según el tipo de error; `status` si no es de un tipo conocido
+pub fn error_status<'a>(error: &(dyn Error + 'static), status: &'a str) -> &'a str {
+    if error.is::<ValidationError>() {
+        "422 Unprocessable Entity"
+    } else if error.is::<BadRequest>() {
+        "400 Bad Request"
+    } else if error.is::<StorageError>() {
+        "500 Internal Server Error"
+    } else if error.is::<SnapshotNotFound>()
+        || error.is::<DatasetNotFound>()
+        || error.is::<WebhookNotFound>()
+        || error.is::<SeasonNotFound>()
+    {
+        "404 Not Found"
+    } else if error.is::<ConflictError>() {
+        "409 Conflict"
+    } else if error.is::<PreconditionFailed>() {
+        "412 Precondition Failed"
+    } else if error.is::<UnsupportedMediaType>() || error.is::<UnsupportedFormat>() {
+        "415 Unsupported Media Type"
+    } else {
+        status
+    }
+}
+
+// Parámetro de la ruta (p. ej. :name), ya decodificado (%20 -> espacio)
+pub fn path_param(request: &Request, name: &str) -> Option<String> {
+    request.get("params")?.get(name)?.as_str().map(|value| percent_decode(value, false))
+}
+
+pub fn request_path(request: &Request) -> &str {
+    request.get("path").and_then(Value::as_str).unwrap_or("")
+}
+
+// Encabezado de la solicitud, sin distinguir mayúsculas
+pub fn header<'a>(request: &'a Request, name: &str) -> Option<&'a str> {
+    request
+        .get("headers")?
+        .as_object()?
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))?
+        .1
+        .as_str()
+}
+
+fn query_string(query: &Map<String, Value>) -> String {
+    let query: Vec<String> = query
+        .iter()
+        .map(|(key, value)| format!("{}={}", percent_encode(key), percent_encode(value.as_str().unwrap_or(""))))
+        .collect();
+    query.join("&")
+}
+
+// Enviar una respuesta de la API en el formato pedido con Accept (JSON si no se indica)
+pub fn send_response(stream: &mut TcpStream, request: &Request, response: &str) {
+    let representation = Representation::negotiate(header(request, "Accept")).unwrap_or(Representation::Json);
+    stream.write_all(&render(response, representation)).unwrap();
+    stream.flush().unwrap();
+}
+
+// Handler de una ruta que responde JSON: si Accept no admite ninguno de los formatos disponibles
+// se responde 406 sin ejecutarlo, así una solicitud rechazada no llega a modificar los datos
+pub fn negotiated<F>(handler: F) -> impl Fn(&mut TcpStream, Request) + Send + Sync + 'static
+where
+    F: Fn(&mut TcpStream, Request) + Send + Sync + 'static,
+{
+    move |stream: &mut TcpStream, request: Request| match Representation::negotiate(header(&request, "Accept")) {
+        Ok(_) => handler(stream, request),
+        Err(e) => {
+            let available: Vec<&str> = Representation::ALL.iter().flat_map(|representation| representation.media_types()).copied().collect();
+            let response = format!(
+                "HTTP/1.1 406 Not Acceptable\r\nContent-Type: application/json\r\nVary: Accept\r\n\r\n{}",
+                json!({ "error": e.to_string(), "available": available })
+            );
+            stream.write_all(response.as_bytes()).unwrap();
+            stream.flush().unwrap();
+        }
+    }
+}
+
+// Registro de las rutas de la API. Cada handler recibe la solicitud con su sesión y devuelve la
+// respuesta o un error, que se responde con el estado de su tipo (ver `ApiResponse::error`) o con
+// el `status` de la ruta si no es de un tipo conocido.
+pub struct ApiRoutes<'a> {
+    server: &'a mut Server,
+    datasets: Arc<Datasets>,
+    audit: Arc<AuditLog>,
+    session: Arc<Session>,
+}
+
+impl<'a> ApiRoutes<'a> {
+    pub fn new(
+        server: &'a mut Server,
+        datasets: Arc<Datasets>,
+        audit: Arc<AuditLog>,
+        session: impl Fn(&Request) -> String + Send + Sync + 'static,
+    ) -> Self {
+        ApiRoutes { server, datasets, audit, session: Arc::new(session) }
+    }
+
+    // Ruta fuera de los conjuntos de datos (/api/admin/...); usa el conjunto por defecto
+    pub fn route<F>(&mut self, method: &str, path: &str, status: &'static str, handler: F)
+    where
+        F: Fn(&ApiRequest) -> Result<ApiResponse, Box<dyn Error>> + Send + Sync + 'static,
+    {
+        let handler = self.handler(status, move |_: &mut TcpStream, request: &ApiRequest| handler(request).map(Some));
+        self.server.add_route(method, path, negotiated(handler));
+    }
+
+    // Ruta de escuderías para el conjunto por defecto (/api/escuderias...) y para los conjuntos
+    // con nombre (/api/:dataset/escuderias...)
+    pub fn dataset_route<F>(&mut self, method: &str, path: &str, status: &'static str, handler: F)
+    where
+        F: Fn(&ApiRequest) -> Result<ApiResponse, Box<dyn Error>> + Send + Sync + 'static,
+    {
+        let handler = self.handler(status, move |_: &mut TcpStream, request: &ApiRequest| handler(request).map(Some));
+        self.register_dataset_route(method, path, negotiated(handler));
+    }
+
+    // Igual que dataset_route, para las rutas que responden en su propio formato (exportaciones)
+    // y no pasan por la negociación de Accept
+    pub fn raw_dataset_route<F>(&mut self, method: &str, path: &str, status: &'static str, handler: F)
+    where
+        F: Fn(&ApiRequest) -> Result<ApiResponse, Box<dyn Error>> + Send + Sync + 'static,
+    {
+        let handler = self.handler(status, move |_: &mut TcpStream, request: &ApiRequest| handler(request).map(Some));
+        self.register_dataset_route(method, path, handler);
+    }
+
+    // Ruta que escribe su respuesta directamente en la conexión y la deja abierta (eventos).
+    // Si el handler falla, se responde el error como en las demás rutas.
+    pub fn stream_dataset_route<F>(&mut self, method: &str, path: &str, status: &'static str, handler: F)
+    where
+        F: Fn(&mut TcpStream, &ApiRequest) -> Result<(), Box<dyn Error>> + Send + Sync + 'static,
+    {
+        let handler = self.handler(status, move |stream: &mut TcpStream, request: &ApiRequest| {
+            handler(stream, request).map(|_| None)
+        });
+        self.register_dataset_route(method, path, handler);
+    }
+
+    // Ruta de una escudería o de sus pilotos. Las solicitudes que los indican por nombre
+    // se redirigen (307, conservando el método y el cuerpo) a la misma ruta con sus identificadores.
+    pub fn team_route<F>(&mut self, method: &str, path: &str, status: &'static str, handler: F)
+    where
+        F: Fn(&ApiRequest) -> Result<ApiResponse, Box<dyn Error>> + Send + Sync + 'static,
+    {
+        let datasets = Arc::clone(&self.datasets);
+        let handler = self.handler(status, move |_: &mut TcpStream, request: &ApiRequest| handler(request).map(Some));
+        let handler = move |stream: &mut TcpStream, request: Request| {
+            let dataset = path_param(&request, "dataset").unwrap_or_else(|| DEFAULT_DATASET.to_string());
+            let location = datasets.get(&dataset).and_then(|store| canonical_location(&request, store.as_ref()));
+            match location {
+                Ok(Some(location)) => {
+                    let response = format!(
+                        "HTTP/1.1 307 Temporary Redirect\r\nLocation: {}\r\nContent-Type: application/json\r\nConnection: close\r\n\r\n{}",
+                        location,
+                        json!({ "message": "Use the id in the URL", "location": location })
+                    );
+                    send_response(stream, &request, &response);
+                }
+                // Si no existen, el handler responde con el error correspondiente
+                _ => handler(stream, request),
+            }
+        };
+        self.register_dataset_route(method, path, negotiated(handler));
+    }
+
+    fn register_dataset_route<F>(&mut self, method: &str, path: &str, handler: F)
+    where
+        F: Fn(&mut TcpStream, Request) + Send + Sync + 'static,
+    {
+        let handler = Arc::new(handler);
+        let default_handler = Arc::clone(&handler);
+        self.server.add_route(method, &format!("/api{}", path), move |stream: &mut TcpStream, request: Request| {
+            default_handler(stream, request)
+        });
+        self.server.add_route(method, &format!("/api/:dataset{}", path), move |stream: &mut TcpStream, request: Request| {
+            handler(stream, request)
+        });
+    }
+
+    // Handler del router para una ruta de la API: arma la solicitud con su sesión, ejecuta el handler
+    // y envía su respuesta o su error. Si el handler devuelve None ya respondió por su cuenta.
+    fn handler<F>(&self, status: &'static str, handler: F) -> impl Fn(&mut TcpStream, Request) + Send + Sync + 'static
+    where
+        F: Fn(&mut TcpStream, &ApiRequest) -> Result<Option<ApiResponse>, Box<dyn Error>> + Send + Sync + 'static,
+    {
+        let datasets = Arc::clone(&self.datasets);
+        let audit = Arc::clone(&self.audit);
+        let session = Arc::clone(&self.session);
+        move |stream: &mut TcpStream, request: Request| {
+            let request = ApiRequest {
+                cookie_value: session(&request),
+                client_ip: stream.peer_addr().map(|addr| addr.ip().to_string()).unwrap_or_default(),
+                datasets: Arc::clone(&datasets),
+                audit: Arc::clone(&audit),
+                request,
+            };
+            let response = match handler(stream, &request) {
+                Ok(Some(response)) => response,
+                Ok(None) => return,
+                Err(e) => ApiResponse::error(&*e, status),
+            };
+            send_response(stream, &request.request, &response.to_http(&request.cookie_value));
+        }
+    }
+}
+
+// Ruta con los identificadores de la escudería y el piloto si la solicitud usa sus nombres;
+// None si ya usa los identificadores o si no existen
+fn canonical_location(request: &Request, store: &dyn TeamStore) -> Result<Option<String>, Box<dyn Error>> {
+    let Some(team_key) = path_param(request, "name").or_else(|| path_param(request, "team_name")) else {
+        return Ok(None);
+    };
+    let Some(team) = store.get(&team_key)? else {
+        return Ok(None);
+    };
+    let driver = path_param(request, "driver_name").and_then(|driver_key| {
+        let by_id = team.drivers.iter().find(|driver| driver.id == driver_key);
+        by_id.or_else(|| team.drivers.iter().find(|driver| driver.name == driver_key)).cloned()
+    });
+
+    let mut changed = false;
+    let mut previous = "";
+    let mut segments = Vec::new();
+    for segment in request_path(request).split('/') {
+        let id = match (previous, &driver) {
+            ("escuderias", _) => Some(&team.id),
+            ("pilotos", Some(driver)) => Some(&driver.id),
+            _ => None,
+        };
+        match id {
+            Some(id) if percent_decode(segment, false) != *id => {
+                changed = true;
+                segments.push(percent_encode(id));
+            }
+            _ => segments.push(segment.to_string()),
+        }
+        previous = segment;
+    }
+    if !changed {
+        return Ok(None);
+    }
+
+    let query = request.get("query").and_then(Value::as_object).map(query_string).unwrap_or_default();
+    let location = segments.join("/");
+    Ok(Some(if query.is_empty() { location } else { format!("{}?{}", location, query) }))
+}
@@ -0,0 +1,170 @@
+use serde::Serialize;
+use serde_json::json;
+use std::error::Error;
+use crate::json_hashmaps::audit::{team_entity_changes, EntityChange};
+use crate::json_hashmaps::formats::DataFormat;
+use crate::json_hashmaps::models::{F1Data, FieldError, Team, ValidationError};
+use crate::json_hashmaps::patch::{diff, PatchOperation};
+use crate::json_hashmaps::team_store::{find_team, team_changes, BatchError, ChangeKind, Mutation, TeamStore};
+use crate::utils::now_utc;
+
+// Qué pasa con las escuderías que no están en el archivo importado
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportMode {
+    // Se conservan
+    Merge,
+    // Se eliminan (como DELETE: quedan eliminadas hasta que se purgan)
+    Replace,
+}
+
+impl ImportMode {
+    pub fn parse(name: &str) -> Result<ImportMode, Box<dyn Error>> {
+        match name {
+            "merge" => Ok(ImportMode::Merge),
+            "replace" => Ok(ImportMode::Replace),
+            _ => Err(format!("Modo '{}' no soportado; use merge o replace", name).into()),
+        }
+    }
+}
+
+// Cambio de una escudería en una importación; `diff` son las operaciones de JSON Patch
+// que convierten la escudería actual en la importada
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ImportChange {
+    pub change: ChangeKind,
+    pub id: String,
+    pub name: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub diff: Vec<PatchOperation>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ImportReport {
+    pub dry_run: bool,
+    pub format: &'static str,
+    // Escuderías del archivo
+    pub teams: usize,
+    pub changes: Vec<ImportChange>,
+    // Escuderías del archivo iguales a las actuales
+    pub unchanged: usize,
+}
+
+// Escuderías (no eliminadas) en el formato indicado
+pub fn export_teams(format: DataFormat, store: &dyn TeamStore) -> Result<String, Box<dyn Error>> {
+    format.write_teams(&store.list()?)
+}
+
+// Importar escuderías: las del archivo reemplazan a las existentes con el mismo identificador o nombre
+// y las demás se agregan, todo como un único cambio. Con `dry_run` solo se informa qué cambiaría.
+// Devuelve el informe y, para la auditoría, el cambio de cada escudería (ninguno si no se aplicó).
+pub fn import_teams(
+    text: &str,
+    format: DataFormat,
+    mode: ImportMode,
+    dry_run: bool,
+    store: &dyn TeamStore,
+) -> Result<(ImportReport, Vec<EntityChange>), Box<dyn Error>> {
+    let teams = read_import(text, format)?;
+    let current = store.list()?;
+
+    let mut mutations: Vec<Mutation> = teams
+        .iter()
+        .map(|team| {
+            let existing = (!team.id.is_empty()).then(|| find_team(&current, &team.id)).flatten();
+            match existing.or_else(|| find_team(&current, &team.name)) {
+                Some(existing) => Mutation::Replace { team_name: existing.id.clone(), team: team.clone() },
+                None => Mutation::Insert { team: team.clone() },
+            }
+        })
+        .collect();
+    if mode == ImportMode::Replace {
+        let date = now_utc();
+        for team in &current {
+            let imported = mutations.iter().any(|mutation| matches!(mutation, Mutation::Replace { team_name, .. } if *team_name == team.id));
+            if !imported {
+                mutations.push(Mutation::SoftDelete { team_name: team.id.clone(), date: date.clone() });
+            }
+        }
+    }
+    let mutation = Mutation::Batch { mutations };
+
+    // Aplicar el cambio sobre una copia para saber qué cambiaría
+    let mut preview = store.list_all()?;
+    mutation.apply(&mut preview).map_err(batch_cause)?;
+    preview.retain(|team| !team.is_deleted());
+    let changes: Vec<ImportChange> = team_changes(&current, &preview)
+        .into_iter()
+        .map(|change| {
+            let before = find_team(&current, &change.id);
+            let after = change.team.as_ref();
+            ImportChange {
+                change: change.kind,
+                name: after.or(before).map(|team| team.name.clone()).unwrap_or_default(),
+                diff: match (change.kind, before, after) {
+                    (ChangeKind::Updated, Some(before), Some(after)) => diff(&json!(before), &json!(after)),
+                    _ => Vec::new(),
+                },
+                id: change.id,
+            }
+        })
+        .collect();
+
+    let mut audit_changes = Vec::new();
+    if !dry_run && !changes.is_empty() {
+        store.apply_mutation(mutation).map_err(batch_cause)?;
+        audit_changes = team_entity_changes("import_team", &current, &preview);
+    }
+    let changed_imports = changes.iter().filter(|change| change.change != ChangeKind::Deleted).count();
+    let report = ImportReport {
+        dry_run,
+        format: format.name(),
+        teams: teams.len(),
+        unchanged: teams.len() - changed_imports,
+        changes,
+    };
+    Ok((report, audit_changes))
+}
+
+// Leer y validar las escuderías del archivo; los errores de cada una se reportan como "teams[i].campo"
+fn read_import(text: &str, format: DataFormat) -> Result<Vec<Team>, ValidationError> {
+    let mut teams = Vec::new();
+    let mut errors = Vec::new();
+    for (i, value) in format.read_teams(text)?.into_iter().enumerate() {
+        match serde_path_to_error::deserialize::<_, Team>(value) {
+            Ok(team) => teams.push(team),
+            Err(e) => {
+                // Un campo faltante se reporta en la escudería ("body" es la raíz)
+                for error in ValidationError::from(e).errors {
+                    let field = match error.field.as_str() {
+                        "body" => format!("teams[{}]", i),
+                        field => format!("teams[{}].{}", i, field),
+                    };
+                    errors.push(FieldError::new(&field, &error.message));
+                }
+            }
+        }
+    }
+    if !errors.is_empty() {
+        return Err(ValidationError { errors });
+    }
+
+    // Mismas reglas que el archivo de datos: escuderías válidas y sin nombres repetidos
+    let data = F1Data {
+        teams: teams.into_iter().map(|team| Team { deleted_at: None, ..team }).collect(),
+        ..F1Data::default()
+    };
+    let errors = data.validate();
+    if errors.is_empty() {
+        Ok(data.teams)
+    } else {
+        Err(ValidationError { errors })
+    }
+}
+
+// La importación es un lote interno: se informa el error de la operación que falló, sin su posición
+fn batch_cause(error: Box<dyn Error>) -> Box<dyn Error> {
+    match error.downcast::<BatchError>() {
+        Ok(batch_error) => batch_error.error,
+        Err(error) => error,
+    }
+}
@@ -1 +1,6 @@
-pub mod functions;
\ No newline at end of file
+pub mod functions;
+pub mod query;
+pub mod batch;
+pub mod seasons;
+pub mod import_export;
+pub mod handlers;
@@ -0,0 +1,352 @@
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::error::Error;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD as BASE64;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::json_hashmaps::models::{FieldError, ValidationError};
+
+// Campos pedidos con ?fields=name,drivers.name, organizados como árbol:
+// una hoja incluye el valor completo y un nodo con hijos solo esos subcampos
+#[derive(Debug, Default, PartialEq)]
+pub struct Projection {
+    children: BTreeMap<String, Projection>,
+}
+
+impl Projection {
+    pub fn parse(fields: &str) -> Result<Projection, Box<dyn Error>> {
+        let mut root = Projection::default();
+
+        for field in fields.split(',').map(str::trim) {
+            if field.is_empty() || field.split('.').any(str::is_empty) {
+                return Err(ValidationError {
+                    errors: vec![FieldError::new("fields", &format!("Campo inválido: '{}'", field))],
+                }
+                .into());
+            }
+
+            let mut node = &mut root;
+            for part in field.split('.') {
+                node = node.children.entry(part.to_string()).or_default();
+            }
+        }
+
+        Ok(root)
+    }
+
+    // Dejar solo los campos pedidos; en los arreglos se aplica a cada elemento.
+    // Los campos que no existen simplemente no aparecen en el resultado.
+    pub fn apply(&self, value: Value) -> Value {
+        if self.children.is_empty() {
+            return value;
+        }
+
+        match value {
+            Value::Object(mut object) => {
+                let mut projected = Map::new();
+                for (key, child) in &self.children {
+                    if let Some(value) = object.remove(key) {
+                        projected.insert(key.clone(), child.apply(value));
+                    }
+                }
+                Value::Object(projected)
+            }
+            Value::Array(items) => Value::Array(items.into_iter().map(|item| self.apply(item)).collect()),
+            other => other,
+        }
+    }
+}
+
+// Cantidad máxima de elementos por página (?limit=)
+pub const MAX_PAGE_SIZE: usize = 100;
+
+// Parámetros de consulta que no son filtros
+const RESERVED_PARAMS: &[&str] = &["fields", "sort", "limit", "cursor", "include_deleted"];
+
+// Campos del modelo por los que se puede filtrar aunque ninguna escudería los tenga;
+// los campos adicionales solo se aceptan si alguna escudería o piloto los tiene
+const MODEL_FIELDS: &[&str] = &[
+    "id",
+    "name",
+    "drivers",
+    "deleted_at",
+    "drivers.id",
+    "drivers.name",
+    "drivers.age",
+    "drivers.nationality",
+    "drivers.transfers",
+    "drivers.transfers.from",
+    "drivers.transfers.to",
+    "drivers.transfers.date",
+];
+
+// Filtros, orden y paginación de una lista, p. ej.
+// ?driver.nationality=British&sort=-name&limit=10&cursor=...
+#[derive(Debug, Default, PartialEq)]
+pub struct ListQuery {
+    // (parámetro, ruta del campo, valor esperado)
+    filters: Vec<(String, Vec<String>, String)>,
+    // (ruta del campo, descendente)
+    sort: Vec<(Vec<String>, bool)>,
+    limit: Option<usize>,
+    cursor: Option<Cursor>,
+    // Incluir las escuderías eliminadas (?include_deleted=true)
+    include_deleted: bool,
+}
+
+// Posición de una página: el elemento del borde (sus campos de orden y su identificador) y si la
+// página empieza después de él o termina antes. Como no depende de la posición en la lista, las
+// escuderías que se agregan o eliminan entre una página y otra no hacen saltar ni repetir elementos.
+// Se envía como base64 y para el cliente es opaco.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Cursor {
+    before: bool,
+    keys: Vec<Value>,
+    id: String,
+}
+
+impl Cursor {
+    fn encode(&self) -> String {
+        BASE64.encode(serde_json::to_vec(self).unwrap_or_default())
+    }
+
+    fn decode(text: &str) -> Option<Cursor> {
+        serde_json::from_slice(&BASE64.decode(text).ok()?).ok()
+    }
+}
+
+// Página de resultados
+#[derive(Debug, PartialEq)]
+pub struct Page {
+    pub items: Vec<Value>,
+    pub total: usize,
+    pub limit: Option<usize>,
+    pub next_cursor: Option<String>,
+    pub prev_cursor: Option<String>,
+}
+
+impl ListQuery {
+    pub fn parse(query: &Map<String, Value>) -> Result<ListQuery, Box<dyn Error>> {
+        let mut list_query = ListQuery::default();
+        let mut errors = Vec::new();
+
+        for (key, value) in query {
+            let value = value.as_str().unwrap_or_default();
+            match key.as_str() {
+                "sort" => {
+                    for field in value.split(',').map(str::trim) {
+                        let (field, descending) = match field.strip_prefix('-') {
+                            Some(field) => (field, true),
+                            None => (field, false),
+                        };
+                        match field_path(field) {
+                            Some(path) => list_query.sort.push((path, descending)),
+                            None => errors.push(FieldError::new("sort", &format!("Campo inválido: '{}'", field))),
+                        }
+                    }
+                }
+                "limit" => match value.parse::<usize>() {
+                    Ok(limit) if (1..=MAX_PAGE_SIZE).contains(&limit) => list_query.limit = Some(limit),
+                    _ => errors.push(FieldError::new(
+                        "limit",
+                        &format!("Debe ser un número entre 1 y {}", MAX_PAGE_SIZE),
+                    )),
+                },
+                "cursor" => match Cursor::decode(value) {
+                    Some(cursor) => list_query.cursor = Some(cursor),
+                    None => errors.push(FieldError::new("cursor", "Cursor inválido")),
+                },
+                "include_deleted" => match value.parse::<bool>() {
+                    Ok(include_deleted) => list_query.include_deleted = include_deleted,
+                    Err(_) => errors.push(FieldError::new("include_deleted", "Debe ser true o false")),
+                },
+                key if RESERVED_PARAMS.contains(&key) => {}
+                key => match field_path(key) {
+                    Some(path) => list_query.filters.push((key.to_string(), path, value.to_string())),
+                    None => errors.push(FieldError::new(key, "Filtro inválido")),
+                },
+            }
+        }
+
+        // Un cursor de una consulta con otro orden no indica ninguna posición en esta
+        if list_query.cursor.as_ref().is_some_and(|cursor| cursor.keys.len() != list_query.sort.len()) {
+            errors.push(FieldError::new("cursor", "El cursor no corresponde a este orden"));
+        }
+
+        if !errors.is_empty() {
+            return Err(ValidationError { errors }.into());
+        }
+        Ok(list_query)
+    }
+
+    pub fn include_deleted(&self) -> bool {
+        self.include_deleted
+    }
+
+    // Filtrar, ordenar y devolver la página pedida. Un filtro por un campo que no es del modelo
+    // ni aparece en ningún elemento es un error, no una lista vacía.
+    pub fn apply(&self, items: Vec<Value>) -> Result<Page, Box<dyn Error>> {
+        let errors: Vec<FieldError> = self
+            .filters
+            .iter()
+            .filter(|(_, path, _)| !MODEL_FIELDS.contains(&path.join(".").as_str()))
+            .filter(|(_, path, _)| !items.iter().any(|item| has_field(item, path)))
+            .map(|(key, _, _)| FieldError::new(key, "Campo desconocido"))
+            .collect();
+        if !errors.is_empty() {
+            return Err(ValidationError { errors }.into());
+        }
+
+        let mut items: Vec<Value> = items
+            .into_iter()
+            .filter(|item| self.filters.iter().all(|(_, path, expected)| matches_filter(item, path, expected)))
+            .collect();
+
+        // Al paginar, el identificador desempata para que cada elemento tenga una posición fija;
+        // sin orden ni paginación se conserva el orden del archivo (sort_by es estable)
+        let paginated = self.limit.is_some() || self.cursor.is_some();
+        if paginated || !self.sort.is_empty() {
+            items.sort_by(|a, b| {
+                let ordering = self.compare(&self.sort_keys(a), &self.sort_keys(b));
+                match paginated {
+                    true => ordering.then_with(|| item_id(a).cmp(item_id(b))),
+                    false => ordering,
+                }
+            });
+        }
+
+        // La página empieza después del elemento del cursor o termina antes de él
+        let total = items.len();
+        let position = |cursor: &Cursor| {
+            items.partition_point(|item| {
+                let ordering = self.compare(&self.sort_keys(item), &cursor.keys).then_with(|| item_id(item).cmp(&cursor.id));
+                ordering.is_lt() || (!cursor.before && ordering.is_eq())
+            })
+        };
+        let (start, end) = match (&self.cursor, self.limit) {
+            (Some(cursor), Some(limit)) if cursor.before => {
+                let end = position(cursor);
+                (end.saturating_sub(limit), end)
+            }
+            (Some(cursor), _) if cursor.before => (0, position(cursor)),
+            (Some(cursor), limit) => {
+                let start = position(cursor);
+                (start, limit.map_or(total, |limit| (start + limit).min(total)))
+            }
+            (None, limit) => (0, limit.map_or(total, |limit| limit.min(total))),
+        };
+        let items: Vec<Value> = items.drain(start..end).collect();
+
+        let cursor = |item: Option<&Value>, before: bool| {
+            let item = item?;
+            let keys = self.sort_keys(item).into_iter().map(|key| key.cloned().unwrap_or(Value::Null)).collect();
+            Some(Cursor { before, keys, id: item_id(item).to_string() }.encode())
+        };
+        let next_cursor = self.limit.filter(|_| end < total).and_then(|_| cursor(items.last(), false));
+        let prev_cursor = self.limit.filter(|_| start > 0).and_then(|_| cursor(items.first(), true));
+
+        Ok(Page {
+            items,
+            total,
+            limit: self.limit,
+            next_cursor,
+            prev_cursor,
+        })
+    }
+
+    // Valores de los campos de ?sort de un elemento
+    fn sort_keys<'a>(&self, item: &'a Value) -> Vec<Option<&'a Value>> {
+        self.sort.iter().map(|(path, _)| lookup(item, path)).collect()
+    }
+
+    // Comparar dos elementos por sus campos de orden. Los valores de un cursor son null
+    // cuando el elemento no tenía el campo.
+    fn compare<K: AsSortKey>(&self, a: &[Option<&Value>], b: &[K]) -> Ordering {
+        self.sort
+            .iter()
+            .zip(a.iter().zip(b))
+            .map(|((_, descending), (a, b))| compare_fields(*a, b.as_sort_key(), *descending))
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(Ordering::Equal)
+    }
+}
+
+// Valor de un campo de orden, tanto de un elemento como de un cursor
+trait AsSortKey {
+    fn as_sort_key(&self) -> Option<&Value>;
+}
+
+impl AsSortKey for Option<&Value> {
+    fn as_sort_key(&self) -> Option<&Value> {
+        *self
+    }
+}
+
+impl AsSortKey for Value {
+    fn as_sort_key(&self) -> Option<&Value> {
+        Some(self).filter(|value| !value.is_null())
+    }
+}
+
+fn item_id(item: &Value) -> &str {
+    item.get("id").and_then(Value::as_str).unwrap_or_default()
+}
+
+// Separar "drivers.nationality" en partes. "driver." es un alias de "drivers."
+fn field_path(field: &str) -> Option<Vec<String>> {
+    let mut path: Vec<String> = field.split('.').map(str::to_string).collect();
+    if path.iter().any(String::is_empty) {
+        return None;
+    }
+    if path.len() > 1 && path[0] == "driver" {
+        path[0] = "drivers".to_string();
+    }
+    Some(path)
+}
+
+// Un filtro se cumple si algún valor en la ruta es igual al esperado; en los arreglos
+// basta con que un elemento coincida (p. ej. algún piloto británico)
+fn matches_filter(value: &Value, path: &[String], expected: &str) -> bool {
+    match (value, path.split_first()) {
+        (Value::Array(items), _) => items.iter().any(|item| matches_filter(item, path, expected)),
+        (Value::Object(object), Some((key, rest))) => {
+            object.get(key).is_some_and(|value| matches_filter(value, rest, expected))
+        }
+        (Value::String(s), None) => s.eq_ignore_ascii_case(expected),
+        (Value::Number(_) | Value::Bool(_), None) => expected.parse::<Value>().is_ok_and(|expected| expected == *value),
+        _ => false,
+    }
+}
+
+// Si el campo existe en el valor; en los arreglos basta con que exista en un elemento
+fn has_field(value: &Value, path: &[String]) -> bool {
+    match (value, path.split_first()) {
+        (Value::Array(items), _) => items.iter().any(|item| has_field(item, path)),
+        (Value::Object(object), Some((key, rest))) => object.get(key).is_some_and(|value| has_field(value, rest)),
+        (_, None) => true,
+        _ => false,
+    }
+}
+
+// Valor de un campo para ordenar; solo se recorren objetos
+fn lookup<'a>(value: &'a Value, path: &[String]) -> Option<&'a Value> {
+    path.iter().try_fold(value, |value, key| value.get(key))
+}
+
+// Comparar dos valores; los que no tienen el campo quedan siempre al final
+fn compare_fields(a: Option<&Value>, b: Option<&Value>, descending: bool) -> Ordering {
+    let ordering = match (a, b) {
+        (None, None) => return Ordering::Equal,
+        (None, Some(_)) => return Ordering::Greater,
+        (Some(_), None) => return Ordering::Less,
+        (Some(Value::Number(a)), Some(Value::Number(b))) => {
+            a.as_f64().partial_cmp(&b.as_f64()).unwrap_or(Ordering::Equal)
+        }
+        (Some(Value::String(a)), Some(Value::String(b))) => a.to_lowercase().cmp(&b.to_lowercase()),
+        (Some(a), Some(b)) => a.to_string().cmp(&b.to_string()),
+    };
+
+    if descending { ordering.reverse() } else { ordering }
+}
@@ -0,0 +1,132 @@
+use serde_json::Value;
+use std::error::Error;
+use crate::json_hashmaps::models::{FieldError, ValidationError};
+use crate::json_hashmaps::seasons::{
+    constructor_standings, driver_standings, ConstructorStanding, DriverStanding, Race, Season, SeasonNotFound,
+};
+use crate::json_hashmaps::team_store::TeamStore;
+
+pub fn get_seasons(store: &dyn TeamStore) -> Result<Vec<Season>, Box<dyn Error>> {
+    store.seasons()
+}
+
+pub fn get_season(year: u32, store: &dyn TeamStore) -> Result<Season, Box<dyn Error>> {
+    store
+        .seasons()?
+        .into_iter()
+        .find(|season| season.year == year)
+        .ok_or_else(|| SeasonNotFound::Season(year).into())
+}
+
+pub fn get_race(year: u32, round: u32, store: &dyn TeamStore) -> Result<Race, Box<dyn Error>> {
+    get_season(year, store)?
+        .race(round)
+        .cloned()
+        .ok_or_else(|| SeasonNotFound::Race(year, round).into())
+}
+
+// Crear o reemplazar una temporada completa. Devuelve la temporada guardada (con los identificadores
+// y los puntos de cada resultado) y si se creó.
+pub fn put_season(year: u32, body: Value, store: &dyn TeamStore) -> Result<(Season, bool), Box<dyn Error>> {
+    let mut season: Season = serde_path_to_error::deserialize(body).map_err(ValidationError::from)?;
+    season.year = key_from_path("year", season.year, year)?;
+    // Las carreras sin ronda la toman de su lugar en la lista
+    for (i, race) in season.races.iter_mut().enumerate() {
+        if race.round == 0 {
+            race.round = i as u32 + 1;
+        }
+    }
+
+    let teams = store.list_all()?;
+    let errors = season.validate(&teams);
+    if !errors.is_empty() {
+        return Err(Box::new(ValidationError { errors }));
+    }
+    season.normalize(&teams);
+
+    let mut created = false;
+    store.update_seasons(&mut |seasons| {
+        match seasons.iter_mut().find(|existing| existing.year == year) {
+            Some(existing) => *existing = season.clone(),
+            None => {
+                seasons.push(season.clone());
+                seasons.sort_by_key(|season| season.year);
+                created = true;
+            }
+        }
+        Ok(())
+    })?;
+    Ok((season, created))
+}
+
+pub fn delete_season(year: u32, store: &dyn TeamStore) -> Result<(), Box<dyn Error>> {
+    store.update_seasons(&mut |seasons| {
+        let position = seasons.iter().position(|season| season.year == year).ok_or(SeasonNotFound::Season(year))?;
+        seasons.remove(position);
+        Ok(())
+    })
+}
+
+// Crear o reemplazar una carrera de una temporada existente. Devuelve la carrera guardada y si se creó.
+pub fn put_race(year: u32, round: u32, body: Value, store: &dyn TeamStore) -> Result<(Race, bool), Box<dyn Error>> {
+    let mut race: Race = serde_path_to_error::deserialize(body).map_err(ValidationError::from)?;
+    race.round = key_from_path("round", race.round, round)?;
+
+    let teams = store.list_all()?;
+    let errors = race.validate(year, &teams, "");
+    if !errors.is_empty() {
+        return Err(Box::new(ValidationError { errors }));
+    }
+    race.normalize(&teams);
+
+    let mut created = false;
+    store.update_seasons(&mut |seasons| {
+        let season = seasons.iter_mut().find(|season| season.year == year).ok_or(SeasonNotFound::Season(year))?;
+        match season.races.iter_mut().find(|existing| existing.round == round) {
+            Some(existing) => *existing = race.clone(),
+            None => {
+                season.races.push(race.clone());
+                season.races.sort_by_key(|race| race.round);
+                created = true;
+            }
+        }
+        Ok(())
+    })?;
+    Ok((race, created))
+}
+
+pub fn delete_race(year: u32, round: u32, store: &dyn TeamStore) -> Result<(), Box<dyn Error>> {
+    store.update_seasons(&mut |seasons| {
+        let season = seasons.iter_mut().find(|season| season.year == year).ok_or(SeasonNotFound::Season(year))?;
+        let position = season
+            .races
+            .iter()
+            .position(|race| race.round == round)
+            .ok_or(SeasonNotFound::Race(year, round))?;
+        season.races.remove(position);
+        Ok(())
+    })
+}
+
+// Campeonato de pilotos calculado con los resultados guardados
+pub fn get_driver_standings(year: u32, store: &dyn TeamStore) -> Result<Vec<DriverStanding>, Box<dyn Error>> {
+    let season = get_season(year, store)?;
+    Ok(driver_standings(&season, &store.list_all()?))
+}
+
+// Campeonato de constructores calculado con los resultados guardados
+pub fn get_constructor_standings(year: u32, store: &dyn TeamStore) -> Result<Vec<ConstructorStanding>, Box<dyn Error>> {
+    let season = get_season(year, store)?;
+    Ok(constructor_standings(&season, &store.list_all()?))
+}
+
+// El año o la ronda del cuerpo es opcional, pero si viene debe coincidir con el de la ruta
+fn key_from_path(field: &str, body_value: u32, path_value: u32) -> Result<u32, ValidationError> {
+    if body_value == 0 || body_value == path_value {
+        Ok(path_value)
+    } else {
+        Err(ValidationError {
+            errors: vec![FieldError::new(field, "Debe coincidir con el de la ruta")],
+        })
+    }
+}
@@ -0,0 +1,278 @@
+use std::error::Error;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Seek, SeekFrom, Write};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+use crate::json_hashmaps::models::{FieldError, Team, ValidationError};
+use crate::json_hashmaps::patch::{diff, PatchOperation};
+use crate::json_hashmaps::team_store::{driver_position, team_changes, TeamStore};
+use crate::utils::now_utc;
+
+// Quién hizo un cambio: la cookie de sesión y la IP del cliente
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Actor {
+    pub session: String,
+    pub client_ip: String,
+}
+
+// Entrada de la auditoría. `entity` es la ruta del recurso sin /api (escuderias/ferrari/pilotos/...),
+// `before` es null si el cambio lo creó, `after` es null si lo eliminó, y `diff` son las
+// operaciones de JSON Patch que convierten `before` en `after`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub seq: u64,
+    pub timestamp: String,
+    pub session: String,
+    pub client_ip: String,
+    pub dataset: String,
+    pub operation: String,
+    pub entity: String,
+    pub before: Value,
+    pub after: Value,
+    pub diff: Vec<PatchOperation>,
+}
+
+// Filtros de GET /api/audit: ?entity=escuderias/ferrari&since=2024-10-19T15:00:00Z
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AuditQuery {
+    pub dataset: Option<String>,
+    // La entidad o cualquiera de las que están debajo de ella (sus pilotos)
+    pub entity: Option<String>,
+    // Fecha en formato RFC 3339, completa o solo el comienzo (2024-10-19)
+    pub since: Option<String>,
+}
+
+impl AuditQuery {
+    pub fn parse(query: &Map<String, Value>) -> Result<Self, Box<dyn Error>> {
+        let param = |name: &str| query.get(name).and_then(Value::as_str).filter(|value| !value.is_empty());
+
+        let since = param("since").map(str::to_string);
+        if let Some(since) = &since {
+            let valid = since.len() >= 4
+                && since[..4].chars().all(|c| c.is_ascii_digit())
+                && since.chars().all(|c| c.is_ascii_digit() || "-:TZ".contains(c));
+            if !valid {
+                return Err(ValidationError {
+                    errors: vec![FieldError::new("since", "Se esperaba una fecha como 2024-10-19T15:00:00Z")],
+                }
+                .into());
+            }
+        }
+
+        Ok(AuditQuery {
+            dataset: None,
+            entity: param("entity").map(|entity| entity.trim_matches('/').to_string()),
+            since,
+        })
+    }
+
+    pub fn matches(&self, entry: &AuditEntry) -> bool {
+        let dataset = self.dataset.as_ref().is_none_or(|dataset| entry.dataset == *dataset);
+        let entity = self.entity.as_ref().is_none_or(|entity| {
+            entry.entity == *entity || entry.entity.starts_with(&format!("{}/", entity))
+        });
+        // Las fechas tienen el mismo formato, así que se comparan como texto
+        let since = self.since.as_ref().is_none_or(|since| entry.timestamp.as_str() >= since.as_str());
+        dataset && entity && since
+    }
+}
+
+// Ruta y contenido de una entidad, o None si no existe
+pub type EntityState = Option<(String, Value)>;
+
+// Cambio de una entidad hecho por una operación que afecta a varias (un lote, una importación)
+#[derive(Debug, Clone, PartialEq)]
+pub struct EntityChange {
+    pub operation: String,
+    pub before: EntityState,
+    pub after: EntityState,
+}
+
+// Registro de auditoría de solo escritura al final (una entrada JSON por línea)
+pub struct AuditLog {
+    path: PathBuf,
+    // El archivo y el número de secuencia de la última entrada; solo se bloquea para escribir
+    file: Mutex<(File, u64)>,
+    // Un lock por conjunto de datos, que se mantiene mientras se aplica y registra un cambio:
+    // los cambios de un conjunto no esperan a los de otro
+    datasets: Mutex<HashMap<String, Arc<Mutex<()>>>>,
+}
+
+impl AuditLog {
+    pub fn open(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| format!("Error opening audit log {}: {}", path.display(), e))?;
+
+        let mut log = AuditLog {
+            path: path.to_path_buf(),
+            file: Mutex::new((file, 0)),
+            datasets: Mutex::new(HashMap::new()),
+        };
+        let last_seq = log.read_entries()?.last().map_or(0, |entry| entry.seq);
+        log.file.get_mut().unwrap().1 = last_seq;
+        Ok(log)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // Lock de los cambios de un conjunto de datos
+    fn dataset_lock(&self, dataset: &str) -> Arc<Mutex<()>> {
+        Arc::clone(self.datasets.lock().unwrap().entry(dataset.to_string()).or_default())
+    }
+
+    // Aplicar `change` y, si tiene éxito, registrarlo. `entity` lee la entidad afectada antes del
+    // cambio (con None) y después (con su resultado). El conjunto de datos queda bloqueado mientras
+    // tanto, así sus cambios registrados no se intercalan entre la lectura y la escritura.
+    pub fn record<T>(
+        &self,
+        actor: &Actor,
+        dataset: &str,
+        operation: &str,
+        entity: impl Fn(Option<&T>) -> Result<EntityState, Box<dyn Error>>,
+        change: impl FnOnce() -> Result<T, Box<dyn Error>>,
+    ) -> Result<T, Box<dyn Error>> {
+        let dataset_lock = self.dataset_lock(dataset);
+        let _changes = dataset_lock.lock().unwrap();
+        let before = entity(None)?;
+        let result = change()?;
+        let after = entity(Some(&result))?;
+        self.append(actor, dataset, operation, before, after);
+        Ok(result)
+    }
+
+    // Como `record`, para un cambio que afecta a varias entidades: `change` lo aplica y devuelve,
+    // junto con su resultado, el cambio de cada entidad. Se registra una entrada por cada una.
+    pub fn record_all<T>(
+        &self,
+        actor: &Actor,
+        dataset: &str,
+        change: impl FnOnce() -> Result<(T, Vec<EntityChange>), Box<dyn Error>>,
+    ) -> Result<T, Box<dyn Error>> {
+        let dataset_lock = self.dataset_lock(dataset);
+        let _changes = dataset_lock.lock().unwrap();
+        let (result, changes) = change()?;
+        for EntityChange { operation, before, after } in changes {
+            self.append(actor, dataset, &operation, before, after);
+        }
+        Ok(result)
+    }
+
+    // Agregar la entrada de un cambio ya aplicado
+    fn append(&self, actor: &Actor, dataset: &str, operation: &str, before: EntityState, after: EntityState) {
+        let Some(path) = after.as_ref().or(before.as_ref()).map(|(path, _)| path.clone()) else {
+            return;
+        };
+        let mut file = self.file.lock().unwrap();
+        let before = before.map_or(Value::Null, |(_, value)| value);
+        let after = after.map_or(Value::Null, |(_, value)| value);
+        let entry = AuditEntry {
+            seq: file.1 + 1,
+            timestamp: now_utc(),
+            session: actor.session.clone(),
+            client_ip: actor.client_ip.clone(),
+            dataset: dataset.to_string(),
+            operation: operation.to_string(),
+            entity: path,
+            diff: diff(&before, &after),
+            before,
+            after,
+        };
+
+        // El cambio ya se aplicó: si no se puede registrar se informa, pero no se revierte
+        let mut line = json!(entry).to_string();
+        line.push('\n');
+        let written = file.0.seek(SeekFrom::End(0)).and_then(|previous_len| {
+            let written = file.0.write_all(line.as_bytes()).and_then(|_| file.0.sync_data());
+            if written.is_err() {
+                let _ = file.0.set_len(previous_len);
+            }
+            written
+        });
+        match written {
+            Ok(()) => file.1 = entry.seq,
+            Err(e) => eprintln!("Error writing audit log {}: {}", self.path.display(), e),
+        }
+    }
+
+    // Entradas que cumplen los filtros, de la más antigua a la más reciente
+    pub fn query(&self, query: &AuditQuery) -> Result<Vec<AuditEntry>, Box<dyn Error>> {
+        let _file = self.file.lock().unwrap();
+        Ok(self.read_entries()?.into_iter().filter(|entry| query.matches(entry)).collect())
+    }
+
+    // Una última línea incompleta (caída durante la escritura) se descarta
+    fn read_entries(&self) -> Result<Vec<AuditEntry>, Box<dyn Error>> {
+        let reader = BufReader::new(File::open(&self.path)?);
+        let lines: Vec<String> = reader.lines().collect::<Result<_, _>>()?;
+
+        let mut entries = Vec::new();
+        for (i, line) in lines.iter().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str(line) {
+                Ok(entry) => entries.push(entry),
+                Err(e) if i == lines.len() - 1 => {
+                    eprintln!("Descartando entrada incompleta de la auditoría: {}", e);
+                }
+                Err(e) => {
+                    return Err(format!("Auditoría corrupta en la línea {}: {}", i + 1, e).into());
+                }
+            }
+        }
+
+        Ok(entries)
+    }
+}
+
+// Estado de una escudería para la auditoría
+pub fn team_entity(store: &dyn TeamStore, team_name: &str) -> Result<EntityState, Box<dyn Error>> {
+    Ok(store.get(team_name)?.as_ref().map(team_state))
+}
+
+// Ruta y contenido de una escudería
+pub fn team_state(team: &Team) -> (String, Value) {
+    (format!("escuderias/{}", team.id), json!(team))
+}
+
+// Estado de la escudería que devolvió el cambio (la que creó o restauró); antes del cambio no existía
+pub fn returned_team_entity(team: Option<&Team>) -> Result<EntityState, Box<dyn Error>> {
+    Ok(team.map(team_state))
+}
+
+// Cambio de cada escudería entre dos estados de los datos (sin las eliminadas), para la auditoría
+pub fn team_entity_changes(operation: &str, before: &[Team], after: &[Team]) -> Vec<EntityChange> {
+    team_changes(before, after)
+        .into_iter()
+        .map(|change| EntityChange {
+            operation: operation.to_string(),
+            before: before.iter().find(|team| team.id == change.id).map(team_state),
+            after: change.team.as_ref().map(team_state),
+        })
+        .collect()
+}
+
+// Estado de un piloto de una escudería para la auditoría
+pub fn driver_entity(store: &dyn TeamStore, team_name: &str, driver_name: &str) -> Result<EntityState, Box<dyn Error>> {
+    let Some(team) = store.get(team_name)? else {
+        return Ok(None);
+    };
+    Ok(driver_state(&team, driver_name))
+}
+
+// Ruta y contenido de un piloto de la escudería, o None si no está en ella
+pub fn driver_state(team: &Team, driver_name: &str) -> EntityState {
+    driver_position(team, driver_name).ok().map(|position| {
+        let driver = &team.drivers[position];
+        (format!("escuderias/{}/pilotos/{}", team.id, driver.id), json!(driver))
+    })
+}
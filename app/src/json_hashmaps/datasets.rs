@@ -0,0 +1,104 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use serde_json::json;
+
+use crate::json_hashmaps::search_index::IndexedStore;
+use crate::json_hashmaps::team_store::{purge_deleted, TeamStore};
+use crate::server_http::sse::EventStream;
+
+// Nombre del conjunto de datos que responde en /api/escuderias
+pub const DEFAULT_DATASET: &str = "default";
+
+// Error cuando la ruta pide un conjunto de datos que no está configurado
+#[derive(Debug)]
+pub struct DatasetNotFound(pub String);
+
+impl fmt::Display for DatasetNotFound {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "El conjunto de datos '{}' no existe", self.0)
+    }
+}
+
+impl Error for DatasetNotFound {}
+
+// Conjuntos de datos servidos por el proceso (p. ej. uno por temporada).
+// Cada uno tiene su propio almacenamiento, y por lo tanto su propio lock, y su índice de búsqueda.
+#[derive(Clone)]
+pub struct Datasets {
+    stores: BTreeMap<String, Arc<IndexedStore>>,
+    // Donde se publican los cambios de las escuderías, con el conjunto de datos como tema
+    events: Option<Arc<EventStream>>,
+}
+
+impl Datasets {
+    pub fn new(default_store: Arc<dyn TeamStore>) -> Result<Self, Box<dyn Error>> {
+        let mut datasets = Datasets { stores: BTreeMap::new(), events: None };
+        datasets.insert(DEFAULT_DATASET, default_store)?;
+        Ok(datasets)
+    }
+
+    // Conjuntos de datos que publican cada cambio en `events`
+    pub fn with_events(default_store: Arc<dyn TeamStore>, events: Arc<EventStream>) -> Result<Self, Box<dyn Error>> {
+        let mut datasets = Datasets { stores: BTreeMap::new(), events: Some(events) };
+        datasets.insert(DEFAULT_DATASET, default_store)?;
+        Ok(datasets)
+    }
+
+    pub fn insert(&mut self, name: &str, store: Arc<dyn TeamStore>) -> Result<(), Box<dyn Error>> {
+        let mut store = IndexedStore::new(store)?;
+        if let Some(events) = self.events.clone() {
+            let dataset = name.to_string();
+            store = store.with_listener(move |changes| {
+                for change in changes {
+                    let data = json!({ "dataset": dataset, "id": change.id, "team": change.team });
+                    events.publish(&dataset, change.kind.as_str(), &data);
+                }
+            });
+        }
+        self.stores.insert(name.to_string(), Arc::new(store));
+        Ok(())
+    }
+
+    pub fn default_store(&self) -> Arc<dyn TeamStore> {
+        self.stores[DEFAULT_DATASET].clone()
+    }
+
+    pub fn get(&self, name: &str) -> Result<Arc<dyn TeamStore>, Box<dyn Error>> {
+        Ok(self.indexed(name)?)
+    }
+
+    // Almacenamiento con su índice de búsqueda
+    pub fn indexed(&self, name: &str) -> Result<Arc<IndexedStore>, Box<dyn Error>> {
+        self.stores
+            .get(name)
+            .cloned()
+            .ok_or_else(|| DatasetNotFound(name.to_string()).into())
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.stores.keys().map(String::as_str).collect()
+    }
+
+    // Hilo que cada `interval` borra definitivamente las escuderías eliminadas hace más de `retention`
+    pub fn start_purge(self: Arc<Self>, retention: Duration, interval: Duration) {
+        thread::spawn(move || loop {
+            thread::sleep(interval);
+            for (name, store) in &self.stores {
+                match purge_deleted(store.as_ref(), retention) {
+                    Ok(0) => {}
+                    Ok(purged) => println!("Escuderías eliminadas purgadas en '{}': {}", name, purged),
+                    Err(e) => eprintln!("Error al purgar las escuderías eliminadas de '{}': {}", name, e),
+                }
+            }
+        });
+    }
+}
+
+// Los nombres forman parte de la ruta: solo letras, números, '-' y '_'
+pub fn is_valid_dataset_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
@@ -1,43 +1,71 @@
 use std::collections::HashMap;
-use std::fs;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use serde::Serialize;
 use serde_json::Value;
+use sha2::{Digest, Sha256};
 use std::error::Error;
-use crate::utils::get_current_dir;
-
-// Función para obtener los datos de F1 desde un archivo JSON
-pub fn get_f1_data() -> Result<HashMap<String, Value>, Box<dyn Error>> {
-    let current_dir = get_current_dir()?;  // Manejo de posibles errores al obtener el directorio actual
-    let f1_data_path = current_dir.join("data/f1_data.json");
+use crate::json_hashmaps::migrations::migrate;
 
+// Leer los datos de F1 de un archivo JSON, migrándolos al esquema actual si hace falta
+pub fn read_f1_data(f1_data_path: &Path) -> Result<HashMap<String, Value>, Box<dyn Error>> {
     // Leer el contenido del archivo
-    let f1_data = fs::read_to_string(&f1_data_path)
+    let f1_data = fs::read_to_string(f1_data_path)
         .map_err(|e| format!("Error reading f1_data.json: {}", e))?;
 
     // Parsear el contenido como un HashMap
-    let f1_data: HashMap<String, Value> = serde_json::from_str(&f1_data)
+    let mut f1_data: HashMap<String, Value> = serde_json::from_str(&f1_data)
         .map_err(|e| format!("Error parsing JSON data: {}", e))?;
 
+    // Actualizar documentos con un esquema anterior y guardar la versión migrada
+    if migrate(&mut f1_data)? {
+        write_f1_data(f1_data_path, &f1_data)?;
+    }
+
     Ok(f1_data)
 }
 
-// Función para escribir un HashMap en formato JSON a un archivo
-pub fn write_json_to_file(hashmap: &HashMap<String, Value>) -> Result<(), Box<dyn Error>> {
-    let pretty_json = serde_json::to_string_pretty(hashmap)
+// Checksum (SHA-256 en hexadecimal) del contenido de un archivo de datos
+pub fn checksum(content: &[u8]) -> String {
+    Sha256::digest(content).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+// Escribir los datos de F1 en formato JSON en la ruta indicada.
+// Se escribe primero un archivo temporal que luego reemplaza al original con un rename,
+// así un fallo a mitad de la escritura nunca deja el archivo truncado.
+// Devuelve el checksum del contenido escrito.
+pub fn write_f1_data<T: Serialize>(f1_data_path: &Path, data: &T) -> Result<String, Box<dyn Error>> {
+    let pretty_json = serde_json::to_string_pretty(data)
         .map_err(|e| format!("Error serializing HashMap to JSON: {}", e))?;
 
-    let current_dir = get_current_dir()?;  // Obtener el directorio actual
-    let f1_data_path = current_dir.join("data/f1_data.json");
+    // Asegurarse de que la carpeta de datos exista, si no, crearla
+    if let Some(data_dir) = f1_data_path.parent() {
+        if !data_dir.as_os_str().is_empty() && !data_dir.exists() {
+            fs::create_dir_all(data_dir)
+                .map_err(|e| format!("Error creating 'data' directory: {}", e))?;
+        }
+    }
+
+    let mut tmp_path = f1_data_path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_path);
 
-    // Asegurarse de que la carpeta 'data' exista, si no, crearla
-    let data_dir = current_dir.join("data");
-    if !data_dir.exists() {
-        fs::create_dir_all(&data_dir)
-            .map_err(|e| format!("Error creating 'data' directory: {}", e))?;
+    let written = File::create(&tmp_path).and_then(|mut file| {
+        file.write_all(pretty_json.as_bytes())?;
+        file.sync_all()
+    });
+    if let Err(e) = written.and_then(|_| fs::rename(&tmp_path, f1_data_path)) {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(format!("Error writing to {}: {}", f1_data_path.display(), e).into());
     }
 
-    // Escribir el contenido formateado en el archivo
-    fs::write(f1_data_path, pretty_json)
-        .map_err(|e| format!("Error writing to f1_data.json: {}", e))?;
+    // Sincronizar el directorio para que el rename sobreviva a una caída del sistema
+    if let Some(data_dir) = f1_data_path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        if let Ok(dir) = File::open(data_dir) {
+            let _ = dir.sync_all();
+        }
+    }
 
-    Ok(())
-}
\ No newline at end of file
+    Ok(checksum(pretty_json.as_bytes()))
+}
@@ -0,0 +1,311 @@
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::fmt;
+use serde_json::{json, Map, Value};
+
+use crate::json_hashmaps::models::{FieldError, Team, ValidationError};
+
+// Columnas fijas del CSV: una fila por piloto, con los datos de su escudería repetidos.
+// Una escudería sin pilotos ocupa una fila con las columnas del piloto vacías.
+pub const CSV_COLUMNS: [&str; 7] = [
+    "team_id",
+    "team_name",
+    "driver_id",
+    "driver_name",
+    "driver_age",
+    "driver_nationality",
+    "driver_transfers",
+];
+
+// Los campos adicionales de escuderías y pilotos van en columnas "team.campo" y "driver.campo"
+const TEAM_EXTRA_PREFIX: &str = "team.";
+const DRIVER_EXTRA_PREFIX: &str = "driver.";
+
+// Formatos de importación y exportación del conjunto de datos
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataFormat {
+    Json,
+    Csv,
+    Ndjson,
+    Yaml,
+}
+
+// Error cuando el Content-Type de una importación no es de ningún formato; la API lo responde con 415
+#[derive(Debug)]
+pub struct UnsupportedFormat(pub String);
+
+impl fmt::Display for UnsupportedFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Content-Type '{}' no soportado; use ?format=json|csv|ndjson|yaml", self.0)
+    }
+}
+
+impl Error for UnsupportedFormat {}
+
+impl DataFormat {
+    // Formato del parámetro ?format=
+    pub fn parse(name: &str) -> Result<DataFormat, Box<dyn Error>> {
+        match name.to_ascii_lowercase().as_str() {
+            "json" => Ok(DataFormat::Json),
+            "csv" => Ok(DataFormat::Csv),
+            "ndjson" | "jsonl" => Ok(DataFormat::Ndjson),
+            "yaml" | "yml" => Ok(DataFormat::Yaml),
+            _ => Err(format!("Formato '{}' no soportado; use json, csv, ndjson o yaml", name).into()),
+        }
+    }
+
+    // Formato según el Content-Type de un cuerpo (sin los parámetros como charset)
+    pub fn from_content_type(content_type: &str) -> Result<DataFormat, UnsupportedFormat> {
+        let media_type = content_type.split(';').next().unwrap_or("").trim().to_ascii_lowercase();
+        match media_type.as_str() {
+            "application/json" => Ok(DataFormat::Json),
+            "text/csv" => Ok(DataFormat::Csv),
+            "application/x-ndjson" | "application/ndjson" | "application/jsonl" => Ok(DataFormat::Ndjson),
+            "application/yaml" | "application/x-yaml" | "text/yaml" | "text/x-yaml" => Ok(DataFormat::Yaml),
+            _ => Err(UnsupportedFormat(content_type.to_string())),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DataFormat::Json => "json",
+            DataFormat::Csv => "csv",
+            DataFormat::Ndjson => "ndjson",
+            DataFormat::Yaml => "yaml",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            DataFormat::Json => "application/json",
+            DataFormat::Csv => "text/csv; charset=utf-8",
+            DataFormat::Ndjson => "application/x-ndjson",
+            DataFormat::Yaml => "application/yaml",
+        }
+    }
+
+    // Escuderías en este formato. JSON y YAML tienen la forma de data/f1_data.json ({"teams": [...]}),
+    // NDJSON una escudería por línea y CSV una fila por piloto.
+    pub fn write_teams(&self, teams: &[Team]) -> Result<String, Box<dyn Error>> {
+        match self {
+            DataFormat::Json => Ok(serde_json::to_string_pretty(&json!({ "teams": teams }))?),
+            DataFormat::Yaml => Ok(serde_yaml::to_string(&json!({ "teams": teams }))?),
+            DataFormat::Ndjson => {
+                let mut text = String::new();
+                for team in teams {
+                    text.push_str(&serde_json::to_string(team)?);
+                    text.push('\n');
+                }
+                Ok(text)
+            }
+            DataFormat::Csv => write_csv(teams),
+        }
+    }
+
+    // Escuderías de un texto en este formato, como JSON sin validar (se validan al importarlas).
+    // Los errores de formato se reportan como ValidationError con la fila o línea.
+    pub fn read_teams(&self, text: &str) -> Result<Vec<Value>, ValidationError> {
+        // Las hojas de cálculo suelen agregar un BOM al inicio del archivo
+        let text = text.trim_start_matches('\u{feff}');
+        match self {
+            DataFormat::Json => {
+                let document: Value = serde_json::from_str(text).map_err(|e| invalid("body", &e.to_string()))?;
+                teams_of_document(document)
+            }
+            DataFormat::Yaml => {
+                let document: Value = serde_yaml::from_str(text).map_err(|e| invalid("body", &e.to_string()))?;
+                teams_of_document(document)
+            }
+            DataFormat::Ndjson => text
+                .lines()
+                .enumerate()
+                .filter(|(_, line)| !line.trim().is_empty())
+                .map(|(i, line)| serde_json::from_str(line).map_err(|e| invalid(&format!("lines[{}]", i), &e.to_string())))
+                .collect(),
+            DataFormat::Csv => read_csv(text),
+        }
+    }
+}
+
+fn invalid(field: &str, message: &str) -> ValidationError {
+    ValidationError { errors: vec![FieldError::new(field, message)] }
+}
+
+// Se acepta el documento completo ({"teams": [...]}) o solo la lista de escuderías
+fn teams_of_document(document: Value) -> Result<Vec<Value>, ValidationError> {
+    match document {
+        Value::Array(teams) => Ok(teams),
+        Value::Object(mut document) => match document.remove("teams") {
+            Some(Value::Array(teams)) => Ok(teams),
+            _ => Err(invalid("teams", "Se esperaba una lista de escuderías")),
+        },
+        _ => Err(invalid("body", "Se esperaba un objeto con \"teams\" o una lista de escuderías")),
+    }
+}
+
+fn write_csv(teams: &[Team]) -> Result<String, Box<dyn Error>> {
+    let team_extra: BTreeSet<&String> = teams.iter().flat_map(|team| team.extra.keys()).collect();
+    let driver_extra: BTreeSet<&String> =
+        teams.iter().flat_map(|team| &team.drivers).flat_map(|driver| driver.extra.keys()).collect();
+
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    let mut header: Vec<String> = CSV_COLUMNS.iter().map(|column| column.to_string()).collect();
+    header.extend(team_extra.iter().map(|key| format!("{}{}", TEAM_EXTRA_PREFIX, key)));
+    header.extend(driver_extra.iter().map(|key| format!("{}{}", DRIVER_EXTRA_PREFIX, key)));
+    writer.write_record(&header)?;
+
+    for team in teams {
+        let team_cells: Vec<String> = team_extra.iter().map(|key| cell(team.extra.get(*key))).collect();
+        if team.drivers.is_empty() {
+            let mut row = vec![team.id.clone(), team.name.clone()];
+            row.resize(CSV_COLUMNS.len(), String::new());
+            row.extend(team_cells.iter().cloned());
+            row.resize(header.len(), String::new());
+            writer.write_record(&row)?;
+        }
+        for driver in &team.drivers {
+            let transfers = if driver.transfers.is_empty() { String::new() } else { serde_json::to_string(&driver.transfers)? };
+            let mut row = vec![
+                team.id.clone(),
+                team.name.clone(),
+                driver.id.clone(),
+                driver.name.clone(),
+                driver.age.to_string(),
+                driver.nationality.clone(),
+                transfers,
+            ];
+            row.extend(team_cells.iter().cloned());
+            row.extend(driver_extra.iter().map(|key| cell(driver.extra.get(*key))));
+            writer.write_record(&row)?;
+        }
+    }
+
+    Ok(String::from_utf8(writer.into_inner()?)?)
+}
+
+// Las cadenas van tal cual y los demás valores como JSON; un campo ausente queda vacío.
+// Una cadena que al leerla cambiaría ("007", "true", vacía o con espacios en los extremos)
+// se escribe como JSON entre comillas para que conserve su tipo
+fn cell(value: Option<&Value>) -> String {
+    match value {
+        None | Some(Value::Null) => String::new(),
+        Some(Value::String(text)) if text.is_empty() || text.trim() != text || cell_value(text) != Value::String(text.clone()) => {
+            Value::String(text.clone()).to_string()
+        }
+        Some(Value::String(text)) => text.clone(),
+        Some(value) => value.to_string(),
+    }
+}
+
+// Valor de una celda de un campo adicional: JSON si lo es (números, true, listas, "texto"...), si no el texto
+fn cell_value(text: &str) -> Value {
+    serde_json::from_str(text).unwrap_or_else(|_| Value::String(text.to_string()))
+}
+
+// Agrupar las filas por escudería (team_id o, si está vacío, team_name) en el orden en que aparecen
+fn read_csv(text: &str) -> Result<Vec<Value>, ValidationError> {
+    let mut reader = csv::ReaderBuilder::new().trim(csv::Trim::All).from_reader(text.as_bytes());
+    let header: Vec<String> = reader
+        .headers()
+        .map_err(|e| invalid("header", &e.to_string()))?
+        .iter()
+        .map(str::to_string)
+        .collect();
+
+    let mut errors = Vec::new();
+    for column in &header {
+        let known = CSV_COLUMNS.contains(&column.as_str())
+            || column.strip_prefix(TEAM_EXTRA_PREFIX).is_some_and(|key| !key.is_empty())
+            || column.strip_prefix(DRIVER_EXTRA_PREFIX).is_some_and(|key| !key.is_empty());
+        if !known {
+            errors.push(FieldError::new("header", &format!("Columna desconocida '{}'", column)));
+        }
+    }
+    if !header.iter().any(|column| column == "team_name") {
+        errors.push(FieldError::new("header", "Falta la columna 'team_name'"));
+    }
+    if !errors.is_empty() {
+        return Err(ValidationError { errors });
+    }
+
+    let mut teams: Vec<(String, Map<String, Value>)> = Vec::new();
+    for (i, record) in reader.records().enumerate() {
+        let field = |column: &str| format!("rows[{}].{}", i, column);
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                errors.push(FieldError::new(&format!("rows[{}]", i), &e.to_string()));
+                continue;
+            }
+        };
+        let row: Vec<(&str, &str)> = header.iter().map(String::as_str).zip(record.iter()).collect();
+        let get = |column: &str| row.iter().find(|(name, _)| *name == column).map_or("", |(_, value)| *value);
+
+        let key = if get("team_id").is_empty() { get("team_name") } else { get("team_id") };
+        if key.is_empty() {
+            errors.push(FieldError::new(&field("team_name"), "El nombre no puede estar vacío"));
+            continue;
+        }
+        let position = match teams.iter().position(|(team_key, _)| team_key == key) {
+            Some(position) => position,
+            None => {
+                let mut team = Map::new();
+                if !get("team_id").is_empty() {
+                    team.insert("id".to_string(), json!(get("team_id")));
+                }
+                team.insert("name".to_string(), json!(get("team_name")));
+                team.insert("drivers".to_string(), json!([]));
+                for (column, value) in &row {
+                    if let Some(extra) = column.strip_prefix(TEAM_EXTRA_PREFIX).filter(|_| !value.is_empty()) {
+                        team.insert(extra.to_string(), cell_value(value));
+                    }
+                }
+                teams.push((key.to_string(), team));
+                teams.len() - 1
+            }
+        };
+
+        // Fila de una escudería sin pilotos
+        let is_driver_column = |column: &str| column.starts_with("driver_") || column.starts_with(DRIVER_EXTRA_PREFIX);
+        if row.iter().all(|(column, value)| !is_driver_column(column) || value.is_empty()) {
+            continue;
+        }
+        let mut driver = Map::new();
+        for column in ["id", "name", "nationality"] {
+            let value = get(&format!("driver_{}", column));
+            if !value.is_empty() {
+                driver.insert(column.to_string(), json!(value));
+            }
+        }
+        if !get("driver_age").is_empty() {
+            match get("driver_age").parse::<u32>() {
+                Ok(age) => {
+                    driver.insert("age".to_string(), json!(age));
+                }
+                Err(_) => errors.push(FieldError::new(&field("driver_age"), "Debe ser un número entero")),
+            }
+        }
+        if !get("driver_transfers").is_empty() {
+            match serde_json::from_str::<Value>(get("driver_transfers")) {
+                Ok(transfers) => {
+                    driver.insert("transfers".to_string(), transfers);
+                }
+                Err(_) => errors.push(FieldError::new(&field("driver_transfers"), "Debe ser una lista JSON de traspasos")),
+            }
+        }
+        for (column, value) in &row {
+            if let Some(extra) = column.strip_prefix(DRIVER_EXTRA_PREFIX).filter(|_| !value.is_empty()) {
+                driver.insert(extra.to_string(), cell_value(value));
+            }
+        }
+        if let Some(Value::Array(drivers)) = teams[position].1.get_mut("drivers") {
+            drivers.push(Value::Object(driver));
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(teams.into_iter().map(|(_, team)| Value::Object(team)).collect())
+    } else {
+        Err(ValidationError { errors })
+    }
+}
@@ -0,0 +1,92 @@
+use std::error::Error;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+
+use crate::json_hashmaps::team_store::Mutation;
+
+// Entrada del journal: un cambio con su número de secuencia
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub seq: u64,
+    pub mutation: Mutation,
+}
+
+// Journal de solo escritura al final (una entrada JSON por línea).
+// Cada cambio se registra aquí antes de reescribir el archivo de datos.
+pub struct Journal {
+    path: PathBuf,
+    file: File,
+}
+
+impl Journal {
+    pub fn open(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| format!("Error opening journal {}: {}", path.display(), e))?;
+
+        Ok(Journal {
+            path: path.to_path_buf(),
+            file,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // Leer las entradas del journal. Una última línea incompleta (caída durante la escritura)
+    // se descarta, porque ese cambio nunca llegó a confirmarse.
+    pub fn read_entries(&self) -> Result<Vec<JournalEntry>, Box<dyn Error>> {
+        let reader = BufReader::new(File::open(&self.path)?);
+        let lines: Vec<String> = reader.lines().collect::<Result<_, _>>()?;
+
+        let mut entries = Vec::new();
+        for (i, line) in lines.iter().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str(line) {
+                Ok(entry) => entries.push(entry),
+                Err(e) if i == lines.len() - 1 => {
+                    eprintln!("Descartando entrada incompleta del journal: {}", e);
+                }
+                Err(e) => {
+                    return Err(format!("Journal corrupto en la línea {}: {}", i + 1, e).into());
+                }
+            }
+        }
+
+        Ok(entries)
+    }
+
+    // Agregar una entrada y sincronizarla a disco.
+    // Devuelve el tamaño previo del journal para poder deshacer la escritura.
+    pub fn append(&mut self, entry: &JournalEntry) -> Result<u64, Box<dyn Error>> {
+        let previous_len = self.file.seek(SeekFrom::End(0))?;
+        let mut line = serde_json::to_string(entry)?;
+        line.push('\n');
+
+        if let Err(e) = self.file.write_all(line.as_bytes()).and_then(|_| self.file.sync_data()) {
+            let _ = self.file.set_len(previous_len);
+            return Err(format!("Error writing journal {}: {}", self.path.display(), e).into());
+        }
+
+        Ok(previous_len)
+    }
+
+    // Deshacer las entradas escritas después de `len`
+    pub fn truncate(&mut self, len: u64) -> Result<(), Box<dyn Error>> {
+        self.file.set_len(len)?;
+        self.file.sync_data()?;
+        Ok(())
+    }
+
+    // Vaciar el journal una vez que sus cambios están en el archivo de datos
+    pub fn clear(&mut self) -> Result<(), Box<dyn Error>> {
+        self.truncate(0)
+    }
+}
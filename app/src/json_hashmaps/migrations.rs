@@ -0,0 +1,82 @@
+use std::collections::HashMap;
+use std::error::Error;
+use serde_json::{json, Value};
+
+use crate::json_hashmaps::models::{assign_ids, Team};
+
+// Versión del esquema que entiende el servidor. Se guarda en el archivo como "schema_version";
+// los documentos sin esa clave se consideran de la versión 0.
+pub const CURRENT_SCHEMA_VERSION: u64 = 2;
+
+type Migration = fn(&mut HashMap<String, Value>) -> Result<(), Box<dyn Error>>;
+
+// Migraciones en orden: (versión a la que llevan el documento, descripción, función)
+const MIGRATIONS: &[(u64, &str, Migration)] = &[
+    (1, "renombrar 'nacionality' a 'nationality' en los pilotos", rename_nacionality),
+    (2, "asignar identificadores a escuderías y pilotos", add_ids),
+];
+
+// Obtener la versión del esquema de un documento
+pub fn schema_version(f1_data: &HashMap<String, Value>) -> u64 {
+    f1_data.get("schema_version").and_then(Value::as_u64).unwrap_or(0)
+}
+
+// Aplicar al documento las migraciones pendientes.
+// Devuelve true si el documento cambió y debe guardarse de nuevo.
+pub fn migrate(f1_data: &mut HashMap<String, Value>) -> Result<bool, Box<dyn Error>> {
+    let version = schema_version(f1_data);
+    if version > CURRENT_SCHEMA_VERSION {
+        return Err(format!(
+            "La versión del esquema ({}) es más nueva que la soportada ({})",
+            version, CURRENT_SCHEMA_VERSION
+        ).into());
+    }
+
+    for (target, description, migration) in MIGRATIONS.iter().filter(|(target, _, _)| *target > version) {
+        migration(f1_data)
+            .map_err(|e| format!("Error en la migración a la versión {}: {}", target, e))?;
+        f1_data.insert("schema_version".to_string(), json!(target));
+        println!("Migración aplicada (v{}): {}", target, description);
+    }
+
+    Ok(version < CURRENT_SCHEMA_VERSION)
+}
+
+// v1: algunos pilotos usan "nacionality" y otros "nationality"; se deja solo "nationality"
+fn rename_nacionality(f1_data: &mut HashMap<String, Value>) -> Result<(), Box<dyn Error>> {
+    let teams = f1_data
+        .get_mut("teams")
+        .and_then(Value::as_array_mut)
+        .ok_or("Los datos no contienen una lista de escuderías")?;
+
+    for team in teams.iter_mut() {
+        let Some(drivers) = team.get_mut("drivers").and_then(Value::as_array_mut) else {
+            continue;
+        };
+        for driver in drivers.iter_mut().filter_map(Value::as_object_mut) {
+            if let Some(nationality) = driver.remove("nacionality") {
+                // Si el piloto ya tenía ambas claves se conserva "nationality"
+                driver.entry("nationality").or_insert(nationality);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// v2: las escuderías y los pilotos se identifican por "id" en lugar de por su nombre.
+// Se generan a partir del nombre ("Red Bull" → "red-bull") igual que para los datos nuevos,
+// sin repetir los que ya existan.
+fn add_ids(f1_data: &mut HashMap<String, Value>) -> Result<(), Box<dyn Error>> {
+    let teams = f1_data
+        .get_mut("teams")
+        .filter(|teams| teams.is_array())
+        .ok_or("Los datos no contienen una lista de escuderías")?;
+
+    let mut typed_teams: Vec<Team> = serde_json::from_value(teams.clone())
+        .map_err(|e| format!("Las escuderías no tienen el formato esperado: {}", e))?;
+    assign_ids(&mut typed_teams);
+    *teams = serde_json::to_value(typed_teams)?;
+
+    Ok(())
+}
@@ -1 +1,15 @@
-pub mod f1_data_hashmap;
\ No newline at end of file
+pub mod f1_data_hashmap;
+pub mod models;
+pub mod migrations;
+pub mod seasons;
+pub mod formats;
+pub mod team_store;
+pub mod journal;
+pub mod audit;
+pub mod snapshots;
+pub mod webhooks;
+pub mod datasets;
+pub mod search_index;
+pub mod patch;
+#[cfg(feature = "sqlite")]
+pub mod sqlite_store;
\ No newline at end of file
@@ -0,0 +1,286 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+
+use crate::json_hashmaps::f1_data_hashmap::checksum;
+use crate::json_hashmaps::seasons::Season;
+use crate::utils::slugify;
+
+// Reglas de validación para escuderías y pilotos
+pub const MAX_DRIVERS_PER_TEAM: usize = 4;
+pub const MIN_DRIVER_AGE: u32 = 16;
+pub const MAX_DRIVER_AGE: u32 = 60;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Driver {
+    // Identificador asignado por el servidor; no cambia al renombrar ni al traspasar el piloto
+    #[serde(default)]
+    pub id: String,
+    pub name: String,
+    pub age: u32,
+    // Se acepta la grafía anterior "nacionality" de los clientes antiguos
+    #[serde(alias = "nacionality")]
+    pub nationality: String,
+    // Traspasos del piloto entre escuderías, del más antiguo al más reciente
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub transfers: Vec<Transfer>,
+    // Campos adicionales que no forman parte del modelo se conservan tal cual
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+// Traspaso de un piloto (POST /api/pilotos/:driver/transfer)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Transfer {
+    pub from: String,
+    pub to: String,
+    pub date: String, // RFC 3339, UTC
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Team {
+    // Identificador asignado por el servidor; no cambia al renombrar la escudería
+    #[serde(default)]
+    pub id: String,
+    pub name: String,
+    pub drivers: Vec<Driver>,
+    // Fecha en que se eliminó (RFC 3339, UTC). Las escuderías eliminadas se conservan hasta que
+    // se purgan, pero no aparecen en las listas ni se pueden modificar; la asigna el servidor.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+// Documento completo de data/f1_data.json
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct F1Data {
+    #[serde(default)]
+    pub schema_version: u64,
+    // Última entrada del journal incluida en el archivo
+    #[serde(default)]
+    pub journal_seq: u64,
+    pub teams: Vec<Team>,
+    // Temporadas con sus carreras y resultados, ordenadas por año
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub seasons: Vec<Season>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl F1Data {
+    // Validar todas las escuderías del documento; los campos se reportan como "teams[i].campo"
+    pub fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+
+        for (i, team) in self.teams.iter().enumerate() {
+            for error in team.validate() {
+                errors.push(FieldError::new(&format!("teams[{}].{}", i, error.field), &error.message));
+            }
+            // Solo las escuderías no eliminadas deben tener nombres distintos
+            let is_repeated = |other: &Team| other.name == team.name && !other.is_deleted();
+            if !team.is_deleted() && self.teams[..i].iter().any(is_repeated) {
+                errors.push(FieldError::new(&format!("teams[{}].name", i), "La escudería está repetida"));
+            }
+        }
+
+        errors
+    }
+}
+
+// Asignación de identificadores únicos a partir de nombres: "Red Bull" → "red-bull", "red-bull-2"...
+#[derive(Debug)]
+struct IdAllocator {
+    used: HashSet<String>,
+    // Base para los nombres que no dejan ningún carácter en el identificador
+    fallback: &'static str,
+}
+
+impl IdAllocator {
+    fn new(fallback: &'static str) -> Self {
+        IdAllocator { used: HashSet::new(), fallback }
+    }
+
+    // Reservar un identificador existente; false si está vacío o ya estaba en uso
+    fn reserve(&mut self, id: &str) -> bool {
+        !id.is_empty() && self.used.insert(id.to_string())
+    }
+
+    // Nuevo identificador derivado del nombre, distinto de todos los reservados
+    fn allocate(&mut self, name: &str) -> String {
+        let slug = slugify(name);
+        let base = if slug.is_empty() { self.fallback } else { slug.as_str() };
+        let mut id = base.to_string();
+        let mut suffix = 2;
+        while !self.reserve(&id) {
+            id = format!("{}-{}", base, suffix);
+            suffix += 1;
+        }
+        id
+    }
+}
+
+// Asignar identificador a las escuderías y pilotos que no lo tienen o lo tienen repetido.
+// Los de las escuderías son únicos entre escuderías y los de los pilotos entre todos los pilotos.
+// Devuelve true si se asignó alguno.
+pub fn assign_ids(teams: &mut [Team]) -> bool {
+    let mut team_ids = IdAllocator::new("escuderia");
+    let mut driver_ids = IdAllocator::new("piloto");
+
+    // Primero se reservan los existentes, para que los nuevos no los tomen
+    let mut missing_teams = Vec::new();
+    let mut missing_drivers = Vec::new();
+    for (i, team) in teams.iter().enumerate() {
+        if !team_ids.reserve(&team.id) {
+            missing_teams.push(i);
+        }
+        for (j, driver) in team.drivers.iter().enumerate() {
+            if !driver_ids.reserve(&driver.id) {
+                missing_drivers.push((i, j));
+            }
+        }
+    }
+
+    for &i in &missing_teams {
+        teams[i].id = team_ids.allocate(&teams[i].name);
+    }
+    for &(i, j) in &missing_drivers {
+        let driver = &mut teams[i].drivers[j];
+        driver.id = driver_ids.allocate(&driver.name);
+    }
+    !missing_teams.is_empty() || !missing_drivers.is_empty()
+}
+
+// Error de un campo específico, p. ej. "drivers[0].age"
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: &str, message: &str) -> Self {
+        FieldError {
+            field: field.to_string(),
+            message: message.to_string(),
+        }
+    }
+}
+
+// Error devuelto cuando una escudería o un piloto no cumple las reglas del modelo
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationError {
+    pub errors: Vec<FieldError>,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let detail: Vec<String> = self
+            .errors
+            .iter()
+            .map(|e| format!("{}: {}", e.field, e.message))
+            .collect();
+        write!(f, "Datos inválidos ({})", detail.join("; "))
+    }
+}
+
+impl Error for ValidationError {}
+
+impl From<serde_path_to_error::Error<serde_json::Error>> for ValidationError {
+    fn from(error: serde_path_to_error::Error<serde_json::Error>) -> Self {
+        // La ruta "." corresponde al objeto raíz (p. ej. un campo faltante)
+        let path = error.path().to_string();
+        let field = if path == "." { "body".to_string() } else { path };
+        ValidationError {
+            errors: vec![FieldError {
+                field,
+                message: error.inner().to_string(),
+            }],
+        }
+    }
+}
+
+impl Driver {
+    // Construir un piloto a partir de un JSON y validarlo
+    pub fn from_value(value: Value) -> Result<Driver, ValidationError> {
+        let driver: Driver = serde_path_to_error::deserialize(value)?;
+        let errors = driver.validate("");
+        if errors.is_empty() {
+            Ok(driver)
+        } else {
+            Err(ValidationError { errors })
+        }
+    }
+
+    // Validar el piloto; `prefix` se antepone al nombre de cada campo con error
+    pub fn validate(&self, prefix: &str) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+
+        if self.name.trim().is_empty() {
+            errors.push(FieldError::new(&format!("{}name", prefix), "El nombre no puede estar vacío"));
+        }
+        if !(MIN_DRIVER_AGE..=MAX_DRIVER_AGE).contains(&self.age) {
+            errors.push(FieldError::new(
+                &format!("{}age", prefix),
+                &format!("La edad debe estar entre {} y {}", MIN_DRIVER_AGE, MAX_DRIVER_AGE),
+            ));
+        }
+        if self.nationality.trim().is_empty() {
+            errors.push(FieldError::new(&format!("{}nationality", prefix), "La nacionalidad no puede estar vacía"));
+        }
+
+        errors
+    }
+}
+
+impl Team {
+    pub fn is_deleted(&self) -> bool {
+        self.deleted_at.is_some()
+    }
+
+    // Versión de la escudería: hash de su contenido, cambia con cualquier modificación.
+    // Se usa como ETag y para If-Match.
+    pub fn version(&self) -> String {
+        let content = serde_json::to_vec(self).unwrap_or_default();
+        checksum(&content)[..16].to_string()
+    }
+
+    // Construir una escudería a partir de un JSON y validarla
+    pub fn from_value(value: Value) -> Result<Team, ValidationError> {
+        let team: Team = serde_path_to_error::deserialize(value)?;
+        let errors = team.validate();
+        if errors.is_empty() {
+            Ok(team)
+        } else {
+            Err(ValidationError { errors })
+        }
+    }
+
+    pub fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+
+        if self.name.trim().is_empty() {
+            errors.push(FieldError::new("name", "El nombre no puede estar vacío"));
+        }
+        if self.drivers.len() > MAX_DRIVERS_PER_TEAM {
+            errors.push(FieldError::new(
+                "drivers",
+                &format!("Una escudería puede tener como máximo {} pilotos", MAX_DRIVERS_PER_TEAM),
+            ));
+        }
+
+        for (i, driver) in self.drivers.iter().enumerate() {
+            let prefix = format!("drivers[{}].", i);
+            errors.extend(driver.validate(&prefix));
+
+            // Un piloto no puede aparecer dos veces en la misma escudería
+            if self.drivers[..i].iter().any(|other| other.name == driver.name) {
+                errors.push(FieldError::new(&format!("{}name", prefix), "El piloto está repetido en la escudería"));
+            }
+        }
+
+        errors
+    }
+}
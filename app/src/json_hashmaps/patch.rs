@@ -0,0 +1,272 @@
+use std::error::Error;
+use std::fmt;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::json_hashmaps::models::{FieldError, Team, ValidationError};
+use crate::json_hashmaps::team_store::ConflictError;
+
+pub const MERGE_PATCH_CONTENT_TYPE: &str = "application/merge-patch+json";
+pub const JSON_PATCH_CONTENT_TYPE: &str = "application/json-patch+json";
+
+// Error cuando el cuerpo de un PATCH no tiene un Content-Type de patch; la API lo responde con 415
+#[derive(Debug)]
+pub struct UnsupportedMediaType(pub String);
+
+impl fmt::Display for UnsupportedMediaType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Content-Type '{}' no soportado; use {} o {}",
+            self.0, MERGE_PATCH_CONTENT_TYPE, JSON_PATCH_CONTENT_TYPE
+        )
+    }
+}
+
+impl Error for UnsupportedMediaType {}
+
+// Cambio parcial de una escudería: JSON Merge Patch (RFC 7396) o JSON Patch (RFC 6902)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "format", content = "patch", rename_all = "snake_case")]
+pub enum TeamPatch {
+    Merge(Value),
+    Json(Vec<PatchOperation>),
+}
+
+// Operación de JSON Patch; las rutas son JSON Pointers (RFC 6901)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum PatchOperation {
+    Add { path: String, value: Value },
+    Remove { path: String },
+    Replace { path: String, value: Value },
+    Move { from: String, path: String },
+    Copy { from: String, path: String },
+    Test { path: String, value: Value },
+}
+
+impl TeamPatch {
+    // Interpretar el cuerpo según su Content-Type
+    pub fn from_body(content_type: &str, body: Value) -> Result<TeamPatch, Box<dyn Error>> {
+        // Ignorar parámetros como "; charset=utf-8"
+        let media_type = content_type.split(';').next().unwrap_or("").trim().to_lowercase();
+        match media_type.as_str() {
+            MERGE_PATCH_CONTENT_TYPE => Ok(TeamPatch::Merge(body)),
+            JSON_PATCH_CONTENT_TYPE => {
+                let operations = serde_path_to_error::deserialize(body).map_err(ValidationError::from)?;
+                Ok(TeamPatch::Json(operations))
+            }
+            _ => Err(Box::new(UnsupportedMediaType(content_type.to_string()))),
+        }
+    }
+
+    // Aplicar el cambio y devolver la escudería resultante, ya validada.
+    // Si una operación falla no se aplica ninguna.
+    pub fn apply(&self, team: &Team) -> Result<Team, Box<dyn Error>> {
+        let mut document = serde_json::to_value(team)?;
+        match self {
+            TeamPatch::Merge(patch) => merge_patch(&mut document, patch),
+            TeamPatch::Json(operations) => {
+                for (i, operation) in operations.iter().enumerate() {
+                    apply_operation(&mut document, operation, i)?;
+                }
+            }
+        }
+        let patched = Team::from_value(document)?;
+        check_ids_unchanged(team, &patched)?;
+        Ok(patched)
+    }
+}
+
+// Los identificadores los asigna el servidor: un patch que cambia el de la escudería o el de un
+// piloto es un error de validación en lugar de un cambio que se descarta sin avisar.
+// Los pilotos sin identificador (los nuevos) se aceptan.
+fn check_ids_unchanged(team: &Team, patched: &Team) -> Result<(), Box<dyn Error>> {
+    let mut errors = Vec::new();
+    if patched.id != team.id {
+        errors.push(FieldError::new("id", "El identificador lo asigna el servidor y no se puede modificar"));
+    }
+    for (i, driver) in patched.drivers.iter().enumerate() {
+        if !driver.id.is_empty() && !team.drivers.iter().any(|d| d.id == driver.id) {
+            errors.push(FieldError::new(
+                &format!("drivers[{}].id", i),
+                "El identificador lo asigna el servidor y no se puede modificar",
+            ));
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(ValidationError { errors }.into());
+    }
+    Ok(())
+}
+
+// RFC 7396: los objetos se combinan clave por clave, null borra la clave y cualquier otro valor reemplaza
+pub fn merge_patch(target: &mut Value, patch: &Value) {
+    let Value::Object(patch) = patch else {
+        *target = patch.clone();
+        return;
+    };
+
+    if !target.is_object() {
+        *target = Value::Object(Map::new());
+    }
+    let target = target.as_object_mut().unwrap();
+    for (key, value) in patch {
+        if value.is_null() {
+            target.remove(key);
+        } else {
+            merge_patch(target.entry(key.clone()).or_insert(Value::Null), value);
+        }
+    }
+}
+
+// Aplicar una operación de JSON Patch. Un `test` que no se cumple es un conflicto (409);
+// una ruta que no existe o no se puede usar es un error de validación (422).
+fn apply_operation(document: &mut Value, operation: &PatchOperation, index: usize) -> Result<(), Box<dyn Error>> {
+    let invalid = |message: String| -> Box<dyn Error> {
+        ValidationError {
+            errors: vec![FieldError::new(&format!("[{}]", index), &message)],
+        }
+        .into()
+    };
+
+    match operation {
+        PatchOperation::Add { path, value } => add(document, path, value.clone()).map_err(invalid),
+        PatchOperation::Remove { path } => remove(document, path).map(|_| ()).map_err(invalid),
+        PatchOperation::Replace { path, value } => {
+            let target = pointer_mut(document, path).map_err(invalid)?;
+            *target = value.clone();
+            Ok(())
+        }
+        PatchOperation::Move { from, path } => {
+            if path.starts_with(&format!("{}/", from)) {
+                return Err(invalid(format!("No se puede mover '{}' dentro de sí mismo", from)));
+            }
+            let value = remove(document, from).map_err(invalid)?;
+            add(document, path, value).map_err(invalid)
+        }
+        PatchOperation::Copy { from, path } => {
+            let value = pointer_mut(document, from).map_err(invalid)?.clone();
+            add(document, path, value).map_err(invalid)
+        }
+        PatchOperation::Test { path, value } => {
+            let current = pointer_mut(document, path).map_err(invalid)?;
+            if current != value {
+                return Err(Box::new(ConflictError(format!(
+                    "La prueba de la operación {} falló: '{}' es {}",
+                    index, path, current
+                ))));
+            }
+            Ok(())
+        }
+    }
+}
+
+fn add(document: &mut Value, path: &str, value: Value) -> Result<(), String> {
+    if path.is_empty() {
+        *document = value;
+        return Ok(());
+    }
+
+    let (parent, key) = split_pointer(path)?;
+    match pointer_mut(document, parent)? {
+        Value::Object(map) => {
+            map.insert(key, value);
+            Ok(())
+        }
+        Value::Array(items) => {
+            // "-" agrega al final del arreglo
+            let position = if key == "-" { items.len() } else { array_index(&key, items.len() + 1)? };
+            items.insert(position, value);
+            Ok(())
+        }
+        _ => Err(format!("'{}' no es un objeto ni un arreglo", parent)),
+    }
+}
+
+fn remove(document: &mut Value, path: &str) -> Result<Value, String> {
+    if path.is_empty() {
+        return Err("No se puede eliminar el documento completo".to_string());
+    }
+
+    let (parent, key) = split_pointer(path)?;
+    match pointer_mut(document, parent)? {
+        Value::Object(map) => map.remove(&key).ok_or_else(|| format!("La ruta '{}' no existe", path)),
+        Value::Array(items) => {
+            let position = array_index(&key, items.len())?;
+            Ok(items.remove(position))
+        }
+        _ => Err(format!("La ruta '{}' no existe", path)),
+    }
+}
+
+fn pointer_mut<'a>(document: &'a mut Value, path: &str) -> Result<&'a mut Value, String> {
+    if !path.is_empty() && !path.starts_with('/') {
+        return Err(format!("La ruta '{}' debe comenzar con '/'", path));
+    }
+    document.pointer_mut(path).ok_or_else(|| format!("La ruta '{}' no existe", path))
+}
+
+// Separar "/a/b/c" en la ruta del padre ("/a/b") y la última clave ya decodificada ("c")
+fn split_pointer(path: &str) -> Result<(&str, String), String> {
+    if !path.starts_with('/') {
+        return Err(format!("La ruta '{}' debe comenzar con '/'", path));
+    }
+    let (parent, key) = path.rsplit_once('/').unwrap();
+    Ok((parent, key.replace("~1", "/").replace("~0", "~")))
+}
+
+// Índice de un arreglo: solo dígitos, sin ceros a la izquierda, menor que `len`
+fn array_index(key: &str, len: usize) -> Result<usize, String> {
+    let valid = !key.is_empty() && key.chars().all(|c| c.is_ascii_digit()) && (key == "0" || !key.starts_with('0'));
+    match key.parse::<usize>() {
+        Ok(index) if valid && index < len => Ok(index),
+        _ => Err(format!("Índice de arreglo inválido: '{}'", key)),
+    }
+}
+
+// Operaciones de JSON Patch que convierten `before` en `after`. Los objetos se comparan clave por clave
+// y los arreglos posición por posición; cualquier otro valor distinto se reemplaza completo.
+pub fn diff(before: &Value, after: &Value) -> Vec<PatchOperation> {
+    let mut operations = Vec::new();
+    diff_at("", before, after, &mut operations);
+    operations
+}
+
+fn diff_at(path: &str, before: &Value, after: &Value, operations: &mut Vec<PatchOperation>) {
+    match (before, after) {
+        (Value::Object(before), Value::Object(after)) => {
+            for (key, value) in before {
+                let path = format!("{}/{}", path, escape_pointer(key));
+                match after.get(key) {
+                    Some(new_value) => diff_at(&path, value, new_value, operations),
+                    None => operations.push(PatchOperation::Remove { path }),
+                }
+            }
+            for (key, value) in after.iter().filter(|(key, _)| !before.contains_key(*key)) {
+                let path = format!("{}/{}", path, escape_pointer(key));
+                operations.push(PatchOperation::Add { path, value: value.clone() });
+            }
+        }
+        (Value::Array(before), Value::Array(after)) => {
+            for (index, (value, new_value)) in before.iter().zip(after).enumerate() {
+                diff_at(&format!("{}/{}", path, index), value, new_value, operations);
+            }
+            // Los elementos sobrantes se quitan desde el final para que los índices sigan siendo válidos
+            for index in (after.len()..before.len()).rev() {
+                operations.push(PatchOperation::Remove { path: format!("{}/{}", path, index) });
+            }
+            for value in after.iter().skip(before.len()) {
+                operations.push(PatchOperation::Add { path: format!("{}/-", path), value: value.clone() });
+            }
+        }
+        _ if before != after => operations.push(PatchOperation::Replace { path: path.to_string(), value: after.clone() }),
+        _ => {}
+    }
+}
+
+// Escapar una clave para usarla en un JSON Pointer ("a/b" -> "a~1b")
+fn escape_pointer(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
@@ -0,0 +1,433 @@
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::sync::{Arc, RwLock};
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::json_hashmaps::models::{Driver, Team};
+use crate::json_hashmaps::patch::TeamPatch;
+use crate::json_hashmaps::seasons::Season;
+use crate::json_hashmaps::team_store::{team_changes, Mutation, MutationObserver, SeasonsUpdate, TeamChange, TeamStore};
+
+// Peso de cada campo al calcular la relevancia
+const NAME_WEIGHT: f64 = 3.0;
+const NATIONALITY_WEIGHT: f64 = 1.0;
+
+// Puntaje de cada tipo de coincidencia de un término
+const EXACT_SCORE: f64 = 1.0;
+const PREFIX_SCORE: f64 = 0.7;
+const FUZZY_SCORE: f64 = 0.4;
+
+// Documento del índice: una escudería o un piloto de una escudería
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+enum DocKey {
+    Team(String),
+    Driver(String, String),
+}
+
+// Resultado de una búsqueda
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SearchResult {
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub team: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub driver: Option<String>,
+    pub score: f64,
+}
+
+// Índice invertido sobre los nombres de escuderías, los nombres de pilotos y sus nacionalidades
+#[derive(Debug, Default)]
+pub struct SearchIndex {
+    // término -> documentos que lo contienen, con el peso del campo
+    terms: BTreeMap<String, HashMap<DocKey, f64>>,
+    // identificador de la escudería -> términos indexados de la escudería y sus pilotos, para poder quitarlos
+    team_terms: HashMap<String, Vec<(String, DocKey)>>,
+    // Revisión del almacenamiento que refleja el índice
+    revision: u64,
+}
+
+impl SearchIndex {
+    pub fn build(teams: &[Team], revision: u64) -> Self {
+        let mut index = SearchIndex { revision, ..Default::default() };
+        for team in teams {
+            index.add_team(team);
+        }
+        index
+    }
+
+    pub fn add_team(&mut self, team: &Team) {
+        let team_key = DocKey::Team(team.name.clone());
+        let mut entries = Vec::new();
+        self.add_field(&team.name, &team_key, NAME_WEIGHT, &mut entries);
+
+        for Driver { name, nationality, .. } in &team.drivers {
+            let driver_key = DocKey::Driver(team.name.clone(), name.clone());
+            self.add_field(name, &driver_key, NAME_WEIGHT, &mut entries);
+            self.add_field(nationality, &driver_key, NATIONALITY_WEIGHT, &mut entries);
+        }
+
+        self.team_terms.insert(team.id.clone(), entries);
+    }
+
+    pub fn remove_team(&mut self, team_id: &str) {
+        for (term, key) in self.team_terms.remove(team_id).unwrap_or_default() {
+            if let Some(docs) = self.terms.get_mut(&term) {
+                docs.remove(&key);
+                if docs.is_empty() {
+                    self.terms.remove(&term);
+                }
+            }
+        }
+    }
+
+    fn add_field(&mut self, text: &str, key: &DocKey, weight: f64, entries: &mut Vec<(String, DocKey)>) {
+        for term in tokenize(text) {
+            let current = self.terms.entry(term.clone()).or_default().entry(key.clone()).or_default();
+            *current = current.max(weight);
+            entries.push((term, key.clone()));
+        }
+    }
+
+    // Buscar los documentos que contienen todos los términos de la consulta, ya sea completos,
+    // como prefijo o con hasta uno o dos errores de escritura. Los resultados se ordenan por relevancia.
+    pub fn search(&self, query: &str, limit: usize) -> Vec<SearchResult> {
+        let query_terms = tokenize(query);
+        if query_terms.is_empty() {
+            return Vec::new();
+        }
+
+        let mut scores: HashMap<&DocKey, f64> = HashMap::new();
+        for (i, query_term) in query_terms.iter().enumerate() {
+            let mut term_scores: HashMap<&DocKey, f64> = HashMap::new();
+            if let Some(docs) = self.terms.get(query_term) {
+                add_matches(&mut term_scores, docs, EXACT_SCORE);
+            }
+            // Los términos que empiezan con el buscado van seguidos en el índice ordenado
+            let prefixed = self
+                .terms
+                .range(query_term.clone()..)
+                .take_while(|(term, _)| term.starts_with(query_term.as_str()))
+                .filter(|(term, _)| *term != query_term);
+            for (_, docs) in prefixed {
+                add_matches(&mut term_scores, docs, PREFIX_SCORE);
+            }
+            // Solo si no hay coincidencias completas ni prefijos se recorren todos los términos
+            // buscando errores de escritura
+            if term_scores.is_empty() {
+                for (term, docs) in &self.terms {
+                    if let Some(match_score) = fuzzy_score(query_term, term) {
+                        add_matches(&mut term_scores, docs, match_score);
+                    }
+                }
+            }
+
+            // Todos los términos de la consulta deben coincidir
+            if i == 0 {
+                scores = term_scores;
+            } else {
+                scores = scores
+                    .into_iter()
+                    .filter_map(|(key, score)| term_scores.get(key).map(|term_score| (key, score + term_score)))
+                    .collect();
+            }
+        }
+
+        let mut results: Vec<(&DocKey, f64)> = scores.into_iter().collect();
+        results.sort_by(|(key_a, score_a), (key_b, score_b)| {
+            score_b.partial_cmp(score_a).unwrap_or(std::cmp::Ordering::Equal).then_with(|| key_a.cmp(key_b))
+        });
+
+        results
+            .into_iter()
+            .take(limit)
+            .map(|(key, score)| match key {
+                DocKey::Team(team) => SearchResult { kind: "team", team: team.clone(), driver: None, score },
+                DocKey::Driver(team, driver) => SearchResult {
+                    kind: "driver",
+                    team: team.clone(),
+                    driver: Some(driver.clone()),
+                    score,
+                },
+            })
+            .collect()
+    }
+}
+
+// Sumar los documentos de un término a los de un término de la consulta, con el mejor puntaje de cada uno
+fn add_matches<'a>(term_scores: &mut HashMap<&'a DocKey, f64>, docs: &'a HashMap<DocKey, f64>, match_score: f64) {
+    for (key, weight) in docs {
+        let score = term_scores.entry(key).or_default();
+        *score = score.max(match_score * weight);
+    }
+}
+
+// Puntaje de un término que coincide con el buscado con errores de escritura, si los errores son pocos
+fn fuzzy_score(query_term: &str, term: &str) -> Option<f64> {
+    // Errores permitidos según el largo del término buscado
+    let max_distance = match query_term.chars().count() {
+        0..=3 => return None,
+        4..=7 => 1,
+        _ => 2,
+    };
+    // También se compara contra el inicio del término para aceptar prefijos con errores ("hamol")
+    let prefix: String = term.chars().take(query_term.chars().count()).collect();
+    let distance = levenshtein(query_term, term).min(levenshtein(query_term, &prefix));
+    (distance <= max_distance).then(|| FUZZY_SCORE / distance as f64)
+}
+
+// Separar en términos en minúsculas y sin tildes
+fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.chars().map(fold_char).collect())
+        .collect()
+}
+
+fn fold_char(c: char) -> char {
+    match c.to_lowercase().next().unwrap_or(c) {
+        'á' | 'à' | 'ä' | 'â' => 'a',
+        'é' | 'è' | 'ë' | 'ê' => 'e',
+        'í' | 'ì' | 'ï' | 'î' => 'i',
+        'ó' | 'ò' | 'ö' | 'ô' => 'o',
+        'ú' | 'ù' | 'ü' | 'û' => 'u',
+        'ñ' => 'n',
+        c => c,
+    }
+}
+
+// Distancia de edición entre dos palabras
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+// Función a la que se avisa de los cambios de cada mutación
+pub type ChangeListener = Box<dyn Fn(&[TeamChange]) + Send + Sync>;
+
+// Almacenamiento con índice de búsqueda. Cada cambio hecho a través de él actualiza solo
+// la escudería afectada; si los datos cambiaron por otro medio (p. ej. al recargar el archivo)
+// el índice se reconstruye al buscar.
+pub struct IndexedStore {
+    inner: Arc<dyn TeamStore>,
+    index: RwLock<SearchIndex>,
+    listener: Option<ChangeListener>,
+}
+
+impl IndexedStore {
+    pub fn new(inner: Arc<dyn TeamStore>) -> Result<Self, Box<dyn Error>> {
+        let index = SearchIndex::build(&inner.list()?, inner.revision());
+        Ok(IndexedStore {
+            inner,
+            index: RwLock::new(index),
+            listener: None,
+        })
+    }
+
+    // Avisar a `listener` de las escuderías que cambia cada mutación. Se llama con el lock del
+    // índice tomado, así los avisos llegan en el mismo orden que los cambios.
+    pub fn with_listener(mut self, listener: impl Fn(&[TeamChange]) + Send + Sync + 'static) -> Self {
+        self.listener = Some(Box::new(listener));
+        self
+    }
+
+    // Escuderías visibles con esos nombres o identificadores, o todas si no se sabe cuáles cambian
+    fn visible_teams(&self, team_names: Option<&[String]>) -> Result<Vec<Team>, Box<dyn Error>> {
+        let Some(team_names) = team_names else {
+            return self.inner.list();
+        };
+        let mut teams: Vec<Team> = Vec::new();
+        for team_name in team_names {
+            if let Some(team) = self.inner.get(team_name)? {
+                if !teams.iter().any(|t| t.id == team.id) {
+                    teams.push(team);
+                }
+            }
+        }
+        Ok(teams)
+    }
+
+    pub fn search(&self, query: &str, limit: usize) -> Result<Vec<SearchResult>, Box<dyn Error>> {
+        {
+            let index = self.index.read().unwrap();
+            if index.revision == self.inner.revision() {
+                return Ok(index.search(query, limit));
+            }
+        }
+
+        let mut index = self.index.write().unwrap();
+        if index.revision != self.inner.revision() {
+            *index = SearchIndex::build(&self.inner.list()?, self.inner.revision());
+        }
+        Ok(index.search(query, limit))
+    }
+
+    // Volver a indexar una escudería con su estado actual, o quitarla si ya no existe
+    fn reindex_team(&self, index: &mut SearchIndex, team_name: &str) -> Result<(), Box<dyn Error>> {
+        match self.inner.get(team_name)? {
+            Some(team) => {
+                index.remove_team(&team.id);
+                index.add_team(&team);
+            }
+            None => index.remove_team(team_name),
+        }
+        Ok(())
+    }
+
+    // Aplicar un cambio y actualizar el índice. El lock del índice se mantiene durante el cambio
+    // para que dos cambios simultáneos no dejen el índice con un estado anterior.
+    fn apply_indexed(&self, mutation: Mutation, observe: Option<&mut MutationObserver>) -> Result<(), Box<dyn Error>> {
+        let mut index = self.index.write().unwrap();
+        let expected_revision = index.revision + 1;
+
+        // Con los identificadores de antes del cambio se quitan del índice las escuderías
+        // que el cambio elimina o renombra
+        let affected_teams = match mutation.affected_teams() {
+            Some(mut team_names) => {
+                for team_name in team_names.clone() {
+                    if let Some(team) = self.inner.get(&team_name)? {
+                        team_names.push(team.id);
+                    }
+                }
+                Some(team_names)
+            }
+            None => None,
+        };
+        let before = match self.listener {
+            Some(_) => Some(self.visible_teams(affected_teams.as_deref())?),
+            None => None,
+        };
+        match observe {
+            Some(observe) => self.inner.apply_observed(mutation, observe)?,
+            None => self.inner.apply_mutation(mutation)?,
+        }
+
+        if let (Some(listener), Some(before)) = (&self.listener, before) {
+            let changes = team_changes(&before, &self.visible_teams(affected_teams.as_deref())?);
+            if !changes.is_empty() {
+                listener(&changes);
+            }
+        }
+
+        let revision = self.inner.revision();
+        match affected_teams {
+            Some(team_names) if revision == expected_revision => {
+                for team_name in &team_names {
+                    self.reindex_team(&mut index, team_name)?;
+                }
+                index.revision = revision;
+            }
+            // Hubo otros cambios que el índice no conoce, o el cambio puede afectar a todas
+            _ => *index = SearchIndex::build(&self.inner.list()?, revision),
+        }
+        Ok(())
+    }
+}
+
+impl TeamStore for IndexedStore {
+    fn list(&self) -> Result<Vec<Team>, Box<dyn Error>> {
+        self.inner.list()
+    }
+
+    fn list_all(&self) -> Result<Vec<Team>, Box<dyn Error>> {
+        self.inner.list_all()
+    }
+
+    fn get(&self, team_name: &str) -> Result<Option<Team>, Box<dyn Error>> {
+        self.inner.get(team_name)
+    }
+
+    fn insert(&self, team: Team) -> Result<(), Box<dyn Error>> {
+        self.apply_mutation(Mutation::Insert { team })
+    }
+
+    fn replace(&self, team_name: &str, team: Team) -> Result<(), Box<dyn Error>> {
+        self.apply_mutation(Mutation::Replace { team_name: team_name.to_string(), team })
+    }
+
+    fn delete(&self, team_name: &str) -> Result<(), Box<dyn Error>> {
+        self.apply_mutation(Mutation::Delete { team_name: team_name.to_string() })
+    }
+
+    fn patch_team(&self, team_name: &str, patch: &TeamPatch) -> Result<(), Box<dyn Error>> {
+        self.apply_mutation(Mutation::PatchTeam {
+            team_name: team_name.to_string(),
+            patch: patch.clone(),
+        })
+    }
+
+    fn patch_driver(
+        &self,
+        team_name: &str,
+        driver_name: &str,
+        changes: &Map<String, Value>,
+    ) -> Result<(), Box<dyn Error>> {
+        self.apply_mutation(Mutation::PatchDriver {
+            team_name: team_name.to_string(),
+            driver_name: driver_name.to_string(),
+            changes: changes.clone(),
+        })
+    }
+
+    fn insert_driver(&self, team_name: &str, driver: Driver) -> Result<(), Box<dyn Error>> {
+        self.apply_mutation(Mutation::InsertDriver { team_name: team_name.to_string(), driver })
+    }
+
+    fn replace_driver(&self, team_name: &str, driver_name: &str, driver: Driver) -> Result<(), Box<dyn Error>> {
+        self.apply_mutation(Mutation::ReplaceDriver {
+            team_name: team_name.to_string(),
+            driver_name: driver_name.to_string(),
+            driver,
+        })
+    }
+
+    fn delete_driver(&self, team_name: &str, driver_name: &str) -> Result<(), Box<dyn Error>> {
+        self.apply_mutation(Mutation::DeleteDriver {
+            team_name: team_name.to_string(),
+            driver_name: driver_name.to_string(),
+        })
+    }
+
+    fn apply_mutation(&self, mutation: Mutation) -> Result<(), Box<dyn Error>> {
+        self.apply_indexed(mutation, None)
+    }
+
+    fn apply_observed(&self, mutation: Mutation, observe: &mut MutationObserver) -> Result<(), Box<dyn Error>> {
+        self.apply_indexed(mutation, Some(observe))
+    }
+
+    fn replace_all(&self, teams: Vec<Team>) -> Result<(), Box<dyn Error>> {
+        self.apply_mutation(Mutation::ReplaceAll { teams })
+    }
+
+    fn revision(&self) -> u64 {
+        self.inner.revision()
+    }
+
+    fn seasons(&self) -> Result<Vec<Season>, Box<dyn Error>> {
+        self.inner.seasons()
+    }
+
+    // Las temporadas no cambian las escuderías: si nadie más modificó los datos, el índice
+    // sigue vigente con la nueva revisión
+    fn update_seasons(&self, update: &mut SeasonsUpdate) -> Result<(), Box<dyn Error>> {
+        let mut index = self.index.write().unwrap();
+        let expected_revision = index.revision + 1;
+        self.inner.update_seasons(update)?;
+        if self.inner.revision() == expected_revision {
+            index.revision = expected_revision;
+        }
+        Ok(())
+    }
+}
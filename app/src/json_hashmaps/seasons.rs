@@ -0,0 +1,331 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+use crate::json_hashmaps::models::{FieldError, Team};
+
+// Puntos del 1.º al 10.º lugar según el sistema estándar; la vuelta rápida suma uno más
+// si el piloto terminó entre los diez primeros
+pub const POINTS: [u32; 10] = [25, 18, 15, 12, 10, 8, 6, 4, 2, 1];
+pub const FASTEST_LAP_POINTS: u32 = 1;
+
+// Años aceptados para una temporada
+pub const MIN_SEASON_YEAR: u32 = 1950;
+pub const MAX_SEASON_YEAR: u32 = 2100;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Season {
+    // Se toma de la ruta si no viene en el cuerpo
+    #[serde(default)]
+    pub year: u32,
+    // Carreras ordenadas por ronda
+    #[serde(default)]
+    pub races: Vec<Race>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Race {
+    // Se toma de la ruta si no viene en el cuerpo
+    #[serde(default)]
+    pub round: u32,
+    pub name: String,
+    pub circuit: String,
+    pub date: String, // AAAA-MM-DD
+    // Resultados de los pilotos clasificados; una carrera sin resultados todavía no se corrió
+    #[serde(default)]
+    pub results: Vec<RaceResult>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RaceResult {
+    pub position: u32,
+    // Identificadores del piloto y de la escudería con la que corrió (se acepta el nombre)
+    pub driver: String,
+    pub team: String,
+    #[serde(default)]
+    pub fastest_lap: bool,
+    // Los asigna el servidor según la posición y la vuelta rápida
+    #[serde(default)]
+    pub points: u32,
+}
+
+// Temporada o carrera que no existe
+#[derive(Debug)]
+pub enum SeasonNotFound {
+    Season(u32),
+    Race(u32, u32),
+}
+
+impl fmt::Display for SeasonNotFound {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SeasonNotFound::Season(year) => write!(f, "La temporada {} no existe", year),
+            SeasonNotFound::Race(year, round) => write!(f, "La ronda {} de la temporada {} no existe", round, year),
+        }
+    }
+}
+
+impl Error for SeasonNotFound {}
+
+// Puntos de un resultado
+pub fn result_points(position: u32, fastest_lap: bool) -> u32 {
+    let Some(&points) = POINTS.get(position.wrapping_sub(1) as usize) else {
+        return 0;
+    };
+    if fastest_lap { points + FASTEST_LAP_POINTS } else { points }
+}
+
+impl Season {
+    // Validar la temporada contra las escuderías existentes; los campos se reportan como "races[i].campo"
+    pub fn validate(&self, teams: &[Team]) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+
+        if !(MIN_SEASON_YEAR..=MAX_SEASON_YEAR).contains(&self.year) {
+            errors.push(FieldError::new(
+                "year",
+                &format!("El año debe estar entre {} y {}", MIN_SEASON_YEAR, MAX_SEASON_YEAR),
+            ));
+        }
+        for (i, race) in self.races.iter().enumerate() {
+            let prefix = format!("races[{}].", i);
+            errors.extend(race.validate(self.year, teams, &prefix));
+            if self.races[..i].iter().any(|other| other.round == race.round) {
+                errors.push(FieldError::new(&format!("{}round", prefix), "La ronda está repetida en la temporada"));
+            }
+        }
+
+        errors
+    }
+
+    // Dejar los resultados con identificadores y puntos, y las carreras ordenadas por ronda
+    pub fn normalize(&mut self, teams: &[Team]) {
+        for race in &mut self.races {
+            race.normalize(teams);
+        }
+        self.races.sort_by_key(|race| race.round);
+    }
+
+    pub fn race(&self, round: u32) -> Option<&Race> {
+        self.races.iter().find(|race| race.round == round)
+    }
+}
+
+impl Race {
+    // Validar la carrera de la temporada `year`; `prefix` se antepone al nombre de cada campo con error
+    pub fn validate(&self, year: u32, teams: &[Team], prefix: &str) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        let field = |name: &str| format!("{}{}", prefix, name);
+
+        if self.round == 0 {
+            errors.push(FieldError::new(&field("round"), "La ronda debe ser mayor que 0"));
+        }
+        if self.name.trim().is_empty() {
+            errors.push(FieldError::new(&field("name"), "El nombre no puede estar vacío"));
+        }
+        if self.circuit.trim().is_empty() {
+            errors.push(FieldError::new(&field("circuit"), "El circuito no puede estar vacío"));
+        }
+        match parse_date(&self.date) {
+            None => errors.push(FieldError::new(&field("date"), "Debe ser una fecha AAAA-MM-DD")),
+            Some((date_year, _, _)) if date_year != year => {
+                errors.push(FieldError::new(&field("date"), &format!("La fecha debe ser del año {}", year)))
+            }
+            Some(_) => {}
+        }
+
+        let mut fastest_laps = 0;
+        for (i, result) in self.results.iter().enumerate() {
+            let result_field = |name: &str| field(&format!("results[{}].{}", i, name));
+            let previous = &self.results[..i];
+
+            if result.position == 0 {
+                errors.push(FieldError::new(&result_field("position"), "La posición debe ser mayor que 0"));
+            } else if previous.iter().any(|other| other.position == result.position) {
+                errors.push(FieldError::new(&result_field("position"), "La posición está repetida en la carrera"));
+            }
+            match find_driver(teams, &result.driver) {
+                None => errors.push(FieldError::new(&result_field("driver"), "El piloto no existe")),
+                Some(driver) if previous.iter().any(|other| find_driver(teams, &other.driver) == Some(driver)) => {
+                    errors.push(FieldError::new(&result_field("driver"), "El piloto está repetido en la carrera"))
+                }
+                Some(_) => {}
+            }
+            if find_team(teams, &result.team).is_none() {
+                errors.push(FieldError::new(&result_field("team"), "La escudería no existe"));
+            }
+            if result.fastest_lap {
+                fastest_laps += 1;
+                if fastest_laps == 2 {
+                    errors.push(FieldError::new(&result_field("fastest_lap"), "Solo un piloto puede tener la vuelta rápida"));
+                }
+            }
+        }
+
+        errors
+    }
+
+    // Reemplazar los nombres de pilotos y escuderías por sus identificadores, calcular los puntos
+    // y ordenar los resultados por posición. Se llama después de validar la carrera.
+    pub fn normalize(&mut self, teams: &[Team]) {
+        for result in &mut self.results {
+            if let Some(driver) = find_driver(teams, &result.driver) {
+                result.driver = driver.to_string();
+            }
+            if let Some(team) = find_team(teams, &result.team) {
+                result.team = team.id.clone();
+            }
+            result.points = result_points(result.position, result.fastest_lap);
+        }
+        self.results.sort_by_key(|result| result.position);
+    }
+}
+
+// Escudería con ese identificador o, si no hay ninguna, con ese nombre (incluidas las eliminadas,
+// porque los resultados de temporadas anteriores pueden ser de escuderías que ya no compiten)
+fn find_team<'a>(teams: &'a [Team], key: &str) -> Option<&'a Team> {
+    teams
+        .iter()
+        .find(|team| team.id == key)
+        .or_else(|| teams.iter().find(|team| team.name == key))
+}
+
+// Identificador del piloto con ese identificador o nombre, en cualquier escudería
+fn find_driver<'a>(teams: &'a [Team], key: &str) -> Option<&'a str> {
+    let drivers = || teams.iter().flat_map(|team| &team.drivers);
+    drivers()
+        .find(|driver| driver.id == key)
+        .or_else(|| drivers().find(|driver| driver.name == key))
+        .map(|driver| driver.id.as_str())
+}
+
+// Fecha AAAA-MM-DD como (año, mes, día), si es válida
+fn parse_date(date: &str) -> Option<(u32, u32, u32)> {
+    let parts: Vec<&str> = date.split('-').collect();
+    let [year, month, day] = parts.as_slice() else {
+        return None;
+    };
+    if year.len() != 4 || month.len() != 2 || day.len() != 2 {
+        return None;
+    }
+    let (year, month, day): (u32, u32, u32) = (year.parse().ok()?, month.parse().ok()?, day.parse().ok()?);
+    let leap_year = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let days_in_month = match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if leap_year => 29,
+        2 => 28,
+        _ => return None,
+    };
+    (1..=days_in_month).contains(&day).then_some((year, month, day))
+}
+
+// Posición de un piloto en el campeonato
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DriverStanding {
+    pub position: usize,
+    pub driver: String,
+    pub name: String,
+    // Escudería de su último resultado
+    pub team: String,
+    pub points: u32,
+    pub wins: u32,
+    pub podiums: u32,
+}
+
+// Posición de una escudería en el campeonato de constructores
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ConstructorStanding {
+    pub position: usize,
+    pub team: String,
+    pub name: String,
+    pub points: u32,
+    pub wins: u32,
+}
+
+// Puntos y llegadas acumulados de un piloto o una escudería
+#[derive(Default)]
+struct Tally {
+    points: u32,
+    // Cantidad de veces que terminó en cada posición, para desempatar
+    finishes: Vec<u32>,
+    // Último resultado, para el piloto: la escudería con la que corrió
+    last_team: String,
+}
+
+impl Tally {
+    fn add(&mut self, result: &RaceResult) {
+        self.points += result.points;
+        let index = result.position as usize - 1;
+        if self.finishes.len() <= index {
+            self.finishes.resize(index + 1, 0);
+        }
+        self.finishes[index] += 1;
+        self.last_team = result.team.clone();
+    }
+
+    fn count(&self, positions: usize) -> u32 {
+        self.finishes.iter().take(positions).sum()
+    }
+}
+
+// Ordenar por puntos y desempatar por la cantidad de victorias, luego de segundos lugares, etc.
+// Si siguen empatados, por identificador, para que el orden sea estable.
+fn rank(tallies: HashMap<String, Tally>) -> Vec<(String, Tally)> {
+    let mut ranked: Vec<(String, Tally)> = tallies.into_iter().collect();
+    ranked.sort_by(|(a_id, a), (b_id, b)| {
+        let positions = a.finishes.len().max(b.finishes.len());
+        let finishes = |tally: &Tally| (0..positions).map(|i| tally.finishes.get(i).copied().unwrap_or(0)).collect::<Vec<u32>>();
+        b.points
+            .cmp(&a.points)
+            .then_with(|| finishes(b).cmp(&finishes(a)))
+            .then_with(|| a_id.cmp(b_id))
+    });
+    ranked
+}
+
+// Campeonato de pilotos de la temporada; los nombres se toman de `teams`
+pub fn driver_standings(season: &Season, teams: &[Team]) -> Vec<DriverStanding> {
+    let mut tallies: HashMap<String, Tally> = HashMap::new();
+    for result in season.races.iter().flat_map(|race| &race.results) {
+        tallies.entry(result.driver.clone()).or_default().add(result);
+    }
+
+    rank(tallies)
+        .into_iter()
+        .enumerate()
+        .map(|(i, (driver, tally))| DriverStanding {
+            position: i + 1,
+            name: teams
+                .iter()
+                .flat_map(|team| &team.drivers)
+                .find(|d| d.id == driver)
+                .map_or_else(|| driver.clone(), |d| d.name.clone()),
+            driver,
+            team: tally.last_team.clone(),
+            points: tally.points,
+            wins: tally.count(1),
+            podiums: tally.count(3),
+        })
+        .collect()
+}
+
+// Campeonato de constructores: suma de los puntos de los pilotos de cada escudería
+pub fn constructor_standings(season: &Season, teams: &[Team]) -> Vec<ConstructorStanding> {
+    let mut tallies: HashMap<String, Tally> = HashMap::new();
+    for result in season.races.iter().flat_map(|race| &race.results) {
+        tallies.entry(result.team.clone()).or_default().add(result);
+    }
+
+    rank(tallies)
+        .into_iter()
+        .enumerate()
+        .map(|(i, (team, tally))| ConstructorStanding {
+            position: i + 1,
+            name: find_team(teams, &team).map_or_else(|| team.clone(), |t| t.name.clone()),
+            team,
+            points: tally.points,
+            wins: tally.count(1),
+        })
+        .collect()
+}
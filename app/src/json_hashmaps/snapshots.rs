@@ -0,0 +1,178 @@
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::json_hashmaps::f1_data_hashmap::{read_f1_data, write_f1_data};
+use crate::json_hashmaps::migrations::CURRENT_SCHEMA_VERSION;
+use crate::json_hashmaps::models::{F1Data, ValidationError};
+use crate::json_hashmaps::team_store::TeamStore;
+
+const SNAPSHOT_PREFIX: &str = "f1_data-";
+
+// Información de una copia guardada en la carpeta de snapshots
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SnapshotInfo {
+    pub id: String,
+    pub created_at: u64, // Timestamp en milisegundos
+    pub size: u64,
+}
+
+// Error cuando se pide una copia que no existe
+#[derive(Debug)]
+pub struct SnapshotNotFound(pub String);
+
+impl std::fmt::Display for SnapshotNotFound {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "La copia '{}' no existe", self.0)
+    }
+}
+
+impl Error for SnapshotNotFound {}
+
+// Copias de los datos con fecha en el nombre (f1_data-<milisegundos>.json).
+// Solo se conservan las `retention` más recientes.
+pub struct SnapshotManager {
+    dir: PathBuf,
+    retention: usize,
+}
+
+impl SnapshotManager {
+    pub fn new(dir: &Path, retention: usize) -> Self {
+        SnapshotManager {
+            dir: dir.to_path_buf(),
+            retention: retention.max(1),
+        }
+    }
+
+    // Listar las copias, de la más reciente a la más antigua
+    pub fn list(&self) -> Result<Vec<SnapshotInfo>, Box<dyn Error>> {
+        if !self.dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut snapshots = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            let Some(id) = file_name.strip_suffix(".json") else {
+                continue;
+            };
+            let Some(created_at) = id.strip_prefix(SNAPSHOT_PREFIX).and_then(|ts| ts.parse().ok()) else {
+                continue;
+            };
+            snapshots.push(SnapshotInfo {
+                id: id.to_string(),
+                created_at,
+                size: entry.metadata()?.len(),
+            });
+        }
+
+        snapshots.sort_by_key(|snapshot| std::cmp::Reverse(snapshot.created_at));
+        Ok(snapshots)
+    }
+
+    // Guardar una copia del contenido actual del almacenamiento
+    pub fn take(&self, store: &dyn TeamStore) -> Result<SnapshotInfo, Box<dyn Error>> {
+        let data = F1Data {
+            schema_version: CURRENT_SCHEMA_VERSION,
+            journal_seq: 0,
+            teams: store.list_all()?,
+            seasons: store.seasons()?,
+            extra: Map::new(),
+        };
+
+        // Dos copias en el mismo milisegundo no deben sobrescribirse
+        let mut created_at = now_millis();
+        while self.path_for(&format!("{}{}", SNAPSHOT_PREFIX, created_at)).exists() {
+            created_at += 1;
+        }
+        let id = format!("{}{}", SNAPSHOT_PREFIX, created_at);
+        let path = self.path_for(&id);
+        write_f1_data(&path, &data)?;
+        self.prune()?;
+
+        Ok(SnapshotInfo {
+            id,
+            created_at,
+            size: fs::metadata(&path)?.len(),
+        })
+    }
+
+    // Guardar una copia solo si los datos cambiaron desde la última
+    pub fn take_if_changed(&self, store: &dyn TeamStore) -> Result<Option<SnapshotInfo>, Box<dyn Error>> {
+        if let Some(latest) = self.list()?.first() {
+            let latest = self.load(&latest.id)?;
+            if latest.teams == store.list_all()? && latest.seasons == store.seasons()? {
+                return Ok(None);
+            }
+        }
+        self.take(store).map(Some)
+    }
+
+    // Leer una copia, migrándola al esquema actual y validando su contenido
+    pub fn load(&self, id: &str) -> Result<F1Data, Box<dyn Error>> {
+        // El id no puede contener separadores de ruta
+        let valid_id = id.starts_with(SNAPSHOT_PREFIX)
+            && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+        let path = self.path_for(id);
+        if !valid_id || !path.exists() {
+            return Err(Box::new(SnapshotNotFound(id.to_string())));
+        }
+
+        let raw = read_f1_data(&path)?;
+        let data: F1Data = serde_path_to_error::deserialize(Value::Object(raw.into_iter().collect()))
+            .map_err(ValidationError::from)?;
+        let errors = data.validate();
+        if !errors.is_empty() {
+            return Err(Box::new(ValidationError { errors }));
+        }
+
+        Ok(data)
+    }
+
+    // Restaurar una copia en el almacenamiento. Antes se guarda una copia del estado actual,
+    // que se devuelve para poder deshacer la restauración.
+    pub fn restore(&self, id: &str, store: &dyn TeamStore) -> Result<SnapshotInfo, Box<dyn Error>> {
+        let data = self.load(id)?;
+        let backup = self.take(store)?;
+        store.replace_all(data.teams)?;
+        store.update_seasons(&mut |seasons| {
+            seasons.clone_from(&data.seasons);
+            Ok(())
+        })?;
+        Ok(backup)
+    }
+
+    // Hilo que guarda una copia cada `interval` si los datos cambiaron
+    pub fn start_periodic(self: Arc<Self>, store: Arc<dyn TeamStore>, interval: Duration) {
+        thread::spawn(move || loop {
+            thread::sleep(interval);
+            match self.take_if_changed(store.as_ref()) {
+                Ok(Some(snapshot)) => println!("Copia de los datos guardada: {}", snapshot.id),
+                Ok(None) => {}
+                Err(e) => eprintln!("Error al guardar la copia de los datos: {}", e),
+            }
+        });
+    }
+
+    // Borrar las copias más antiguas que exceden la retención
+    fn prune(&self) -> Result<(), Box<dyn Error>> {
+        for snapshot in self.list()?.iter().skip(self.retention) {
+            fs::remove_file(self.path_for(&snapshot.id))?;
+        }
+        Ok(())
+    }
+
+    fn path_for(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", id))
+    }
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64
+}
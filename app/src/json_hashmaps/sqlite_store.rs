@@ -0,0 +1,643 @@
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+use serde_json::{Map, Value};
+
+use crate::json_hashmaps::f1_data_hashmap::read_f1_data;
+use crate::json_hashmaps::models::{assign_ids, Driver, F1Data, Team};
+use crate::json_hashmaps::patch::TeamPatch;
+use crate::json_hashmaps::seasons::Season;
+use crate::json_hashmaps::team_store::{
+    add_driver, apply_driver_patch, check_unique_drivers, check_version, driver_position, keep_ids, remove_driver,
+    set_driver, transferred_driver, without_ids, BatchError, ConflictError, Mutation, MutationObserver, SeasonsUpdate,
+    StorageError, TeamStore,
+};
+
+// Migraciones del esquema de la base de datos, en orden.
+// PRAGMA user_version guarda cuántas se han aplicado.
+const MIGRATIONS: &[&str] = &[
+    // v1: escuderías y pilotos en tablas separadas
+    "CREATE TABLE teams (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        name TEXT NOT NULL UNIQUE,
+        extra TEXT NOT NULL DEFAULT '{}'
+    );
+    CREATE TABLE drivers (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        team_id INTEGER NOT NULL REFERENCES teams(id) ON DELETE CASCADE,
+        position INTEGER NOT NULL,
+        name TEXT NOT NULL,
+        age INTEGER NOT NULL,
+        nationality TEXT NOT NULL,
+        extra TEXT NOT NULL DEFAULT '{}',
+        UNIQUE (team_id, name)
+    );
+    CREATE TABLE metadata (
+        key TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+    );",
+    // v2: historial de traspasos de cada piloto (JSON)
+    "ALTER TABLE drivers ADD COLUMN transfers TEXT NOT NULL DEFAULT '[]';",
+    // v3: identificadores de escuderías y pilotos; los NULL se completan al abrir la base de datos
+    "ALTER TABLE teams ADD COLUMN slug TEXT;
+    CREATE UNIQUE INDEX teams_slug ON teams(slug);
+    ALTER TABLE drivers ADD COLUMN slug TEXT;
+    CREATE UNIQUE INDEX drivers_slug ON drivers(slug);",
+    // v4: eliminación lógica. El nombre solo es único entre las escuderías no eliminadas, así que se
+    // reconstruye la tabla (se aplica con las claves foráneas desactivadas, sin borrar los pilotos).
+    "CREATE TABLE teams_v4 (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        name TEXT NOT NULL,
+        extra TEXT NOT NULL DEFAULT '{}',
+        slug TEXT,
+        deleted_at TEXT
+    );
+    INSERT INTO teams_v4 (id, name, extra, slug) SELECT id, name, extra, slug FROM teams;
+    DROP TABLE teams;
+    ALTER TABLE teams_v4 RENAME TO teams;
+    CREATE UNIQUE INDEX teams_slug ON teams(slug);
+    CREATE UNIQUE INDEX teams_name ON teams(name) WHERE deleted_at IS NULL;",
+    // v5: temporadas, con sus carreras y resultados (JSON)
+    "CREATE TABLE seasons (
+        year INTEGER PRIMARY KEY,
+        races TEXT NOT NULL DEFAULT '[]'
+    );",
+];
+
+// Fila de la escudería no eliminada con ese identificador o, si no hay ninguna, con ese nombre
+// (un slug NULL queda al final con DESC)
+const TEAM_BY_KEY: &str =
+    "SELECT id FROM teams WHERE (slug = ?1 OR name = ?1) AND deleted_at IS NULL ORDER BY slug = ?1 DESC LIMIT 1";
+
+// Fila de la escudería eliminada con ese identificador o, si no hay ninguna, de la última eliminada con ese nombre
+const DELETED_TEAM_BY_KEY: &str = "SELECT id, name FROM teams WHERE (slug = ?1 OR name = ?1) AND deleted_at IS NOT NULL
+     ORDER BY slug = ?1 DESC, deleted_at DESC LIMIT 1";
+
+// Almacenamiento de escuderías en SQLite; cada cambio modifica solo las filas afectadas
+pub struct SqliteStore {
+    conn: Mutex<Connection>,
+    revision: AtomicU64,
+    // Rechazar los cambios que dejan a un piloto en dos escuderías
+    unique_drivers: bool,
+}
+
+impl SqliteStore {
+    // Abrir (o crear) la base de datos y aplicar las migraciones pendientes
+    pub fn open(db_path: &Path) -> Result<Self, Box<dyn Error>> {
+        if let Some(data_dir) = db_path.parent() {
+            fs::create_dir_all(data_dir)?;
+        }
+        Self::from_connection(Connection::open(db_path)?)
+    }
+
+    pub fn open_in_memory() -> Result<Self, Box<dyn Error>> {
+        Self::from_connection(Connection::open_in_memory()?)
+    }
+
+    fn from_connection(mut conn: Connection) -> Result<Self, Box<dyn Error>> {
+        run_migrations(&mut conn)?;
+        conn.pragma_update(None, "foreign_keys", true)?;
+        let tx = conn.transaction()?;
+        assign_missing_ids(&tx)?;
+        tx.commit()?;
+        Ok(SqliteStore {
+            conn: Mutex::new(conn),
+            revision: AtomicU64::new(0),
+            unique_drivers: false,
+        })
+    }
+
+    pub fn with_unique_drivers(mut self, unique_drivers: bool) -> Self {
+        self.unique_drivers = unique_drivers;
+        self
+    }
+
+    // Importar las escuderías de un archivo JSON la primera vez que se usa la base de datos.
+    // Devuelve true si se realizó la importación.
+    pub fn import_json_once(&self, json_path: &Path) -> Result<bool, Box<dyn Error>> {
+        let imported = self.transaction(|tx| {
+            let imported: Option<String> = tx
+                .query_row("SELECT value FROM metadata WHERE key = 'json_import'", [], |row| row.get(0))
+                .optional()?;
+            if imported.is_some() || !json_path.exists() {
+                return Ok(None);
+            }
+
+            let raw = read_f1_data(json_path)?;
+            let mut data: F1Data = serde_path_to_error::deserialize(Value::Object(raw.into_iter().collect()))
+                .map_err(|e| format!("Error parsing JSON data ({}): {}", e.path(), e.inner()))?;
+            assign_ids(&mut data.teams);
+
+            for team in &data.teams {
+                insert_team_rows(tx, team)?;
+            }
+            insert_season_rows(tx, &data.seasons)?;
+            tx.execute(
+                "INSERT INTO metadata (key, value) VALUES ('json_import', ?1)",
+                [json_path.display().to_string()],
+            )?;
+            Ok(Some(data.teams.len()))
+        })?;
+
+        if let Some(count) = imported {
+            println!("Importadas {} escuderías desde {}", count, json_path.display());
+        }
+        Ok(imported.is_some())
+    }
+
+    // Ejecutar una operación dentro de una transacción; si falla no se guarda ningún cambio.
+    // Los errores propios de SQLite se reportan como StorageError.
+    fn transaction<T>(
+        &self,
+        operation: impl FnOnce(&Transaction) -> Result<T, Box<dyn Error>>,
+    ) -> Result<T, Box<dyn Error>> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction().map_err(StorageError::boxed)?;
+        let result = operation(&tx).map_err(|e| {
+            if e.is::<rusqlite::Error>() { StorageError::boxed(e) } else { e }
+        })?;
+        tx.commit().map_err(StorageError::boxed)?;
+        Ok(result)
+    }
+
+    // Transacción que modifica los datos: si se confirma, aumenta la revisión
+    fn write_transaction(
+        &self,
+        operation: impl FnOnce(&Transaction) -> Result<(), Box<dyn Error>>,
+    ) -> Result<(), Box<dyn Error>> {
+        self.transaction(operation)?;
+        self.revision.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+impl TeamStore for SqliteStore {
+    fn list(&self) -> Result<Vec<Team>, Box<dyn Error>> {
+        let teams = self.transaction(|tx| load_teams(tx, None))?;
+        Ok(teams.into_iter().filter(|team| !team.is_deleted()).collect())
+    }
+
+    fn list_all(&self) -> Result<Vec<Team>, Box<dyn Error>> {
+        self.transaction(|tx| load_teams(tx, None))
+    }
+
+    fn get(&self, team_name: &str) -> Result<Option<Team>, Box<dyn Error>> {
+        self.transaction(|tx| Ok(load_teams(tx, Some(team_name))?.pop()))
+    }
+
+    fn insert(&self, team: Team) -> Result<(), Box<dyn Error>> {
+        self.apply_mutation(Mutation::Insert { team })
+    }
+
+    fn replace(&self, team_name: &str, team: Team) -> Result<(), Box<dyn Error>> {
+        self.apply_mutation(Mutation::Replace { team_name: team_name.to_string(), team })
+    }
+
+    fn delete(&self, team_name: &str) -> Result<(), Box<dyn Error>> {
+        self.apply_mutation(Mutation::Delete { team_name: team_name.to_string() })
+    }
+
+    fn patch_team(&self, team_name: &str, patch: &TeamPatch) -> Result<(), Box<dyn Error>> {
+        self.apply_mutation(Mutation::PatchTeam {
+            team_name: team_name.to_string(),
+            patch: patch.clone(),
+        })
+    }
+
+    fn patch_driver(
+        &self,
+        team_name: &str,
+        driver_name: &str,
+        changes: &Map<String, Value>,
+    ) -> Result<(), Box<dyn Error>> {
+        self.apply_mutation(Mutation::PatchDriver {
+            team_name: team_name.to_string(),
+            driver_name: driver_name.to_string(),
+            changes: changes.clone(),
+        })
+    }
+
+    fn insert_driver(&self, team_name: &str, driver: Driver) -> Result<(), Box<dyn Error>> {
+        self.apply_mutation(Mutation::InsertDriver { team_name: team_name.to_string(), driver })
+    }
+
+    fn replace_driver(&self, team_name: &str, driver_name: &str, driver: Driver) -> Result<(), Box<dyn Error>> {
+        self.apply_mutation(Mutation::ReplaceDriver {
+            team_name: team_name.to_string(),
+            driver_name: driver_name.to_string(),
+            driver,
+        })
+    }
+
+    fn delete_driver(&self, team_name: &str, driver_name: &str) -> Result<(), Box<dyn Error>> {
+        self.apply_mutation(Mutation::DeleteDriver {
+            team_name: team_name.to_string(),
+            driver_name: driver_name.to_string(),
+        })
+    }
+
+    fn apply_mutation(&self, mutation: Mutation) -> Result<(), Box<dyn Error>> {
+        self.write_transaction(|tx| {
+            let before = if self.unique_drivers { Some(load_teams(tx, None)?) } else { None };
+            apply_to_rows(tx, &mutation)?;
+            assign_missing_ids(tx)?;
+            match before {
+                Some(before) => check_unique_drivers(&before, &load_teams(tx, None)?),
+                None => Ok(()),
+            }
+        })
+    }
+
+    // Cada paso se aplica y recibe sus identificadores antes de leer las escuderías para el observador
+    fn apply_observed(&self, mutation: Mutation, observe: &mut MutationObserver) -> Result<(), Box<dyn Error>> {
+        self.write_transaction(|tx| {
+            let first = load_teams(tx, None)?;
+            let steps: Vec<&Mutation> = match &mutation {
+                Mutation::Batch { mutations } => mutations.iter().collect(),
+                mutation => vec![mutation],
+            };
+            let mut before = first.clone();
+            for (index, step) in steps.into_iter().enumerate() {
+                apply_to_rows(tx, step).map_err(|error| match &mutation {
+                    Mutation::Batch { .. } if !error.is::<rusqlite::Error>() => BatchError::boxed(index, error),
+                    _ => error,
+                })?;
+                assign_missing_ids(tx)?;
+                let after = load_teams(tx, None)?;
+                observe(index, &before, &after);
+                before = after;
+            }
+            match self.unique_drivers {
+                true => check_unique_drivers(&first, &before),
+                false => Ok(()),
+            }
+        })
+    }
+
+    fn replace_all(&self, teams: Vec<Team>) -> Result<(), Box<dyn Error>> {
+        self.apply_mutation(Mutation::ReplaceAll { teams })
+    }
+
+    fn revision(&self) -> u64 {
+        self.revision.load(Ordering::SeqCst)
+    }
+
+    fn seasons(&self) -> Result<Vec<Season>, Box<dyn Error>> {
+        self.transaction(|tx| load_seasons(tx))
+    }
+
+    fn update_seasons(&self, update: &mut SeasonsUpdate) -> Result<(), Box<dyn Error>> {
+        self.write_transaction(|tx| {
+            let mut seasons = load_seasons(tx)?;
+            update(&mut seasons)?;
+            apply_to_rows(tx, &Mutation::ReplaceSeasons { seasons })
+        })
+    }
+}
+
+// Aplicar un cambio modificando solo las filas afectadas
+fn apply_to_rows(tx: &Transaction, mutation: &Mutation) -> Result<(), Box<dyn Error>> {
+    match mutation {
+        Mutation::Insert { team } => {
+            // Verificar si el equipo ya existe
+            if name_exists(tx, &team.name)? {
+                return Err("El equipo ya existe".into());
+            }
+
+            insert_team_rows(tx, &without_ids(team.clone()))
+        }
+        Mutation::Replace { team_name, team } => replace_team_rows(tx, team_name, team),
+        Mutation::Delete { team_name } => {
+            let Some(id) = team_id(tx, team_name)? else {
+                return Err(format!("El equipo '{}' no existe", team_name).into());
+            };
+            // Los pilotos se eliminan en cascada
+            tx.execute("DELETE FROM teams WHERE id = ?1", [id])?;
+            Ok(())
+        }
+        Mutation::SoftDelete { team_name, date } => {
+            let Some(id) = team_id(tx, team_name)? else {
+                return Err(format!("El equipo '{}' no existe", team_name).into());
+            };
+            tx.execute("UPDATE teams SET deleted_at = ?1 WHERE id = ?2", params![date, id])?;
+            Ok(())
+        }
+        Mutation::Restore { team_name } => {
+            let deleted: Option<(i64, String)> = tx
+                .query_row(DELETED_TEAM_BY_KEY, [team_name], |row| Ok((row.get(0)?, row.get(1)?)))
+                .optional()?;
+            let Some((id, name)) = deleted else {
+                return Err(format!("El equipo '{}' no está eliminado", team_name).into());
+            };
+            if name_exists(tx, &name)? {
+                return Err(Box::new(ConflictError(format!(
+                    "Ya existe otra escudería llamada '{}'; renómbrela antes de restaurar esta",
+                    name
+                ))));
+            }
+            tx.execute("UPDATE teams SET deleted_at = NULL WHERE id = ?1", [id])?;
+            Ok(())
+        }
+        Mutation::Purge { before } => {
+            tx.execute("DELETE FROM teams WHERE deleted_at < ?1", [before])?;
+            Ok(())
+        }
+        Mutation::PatchTeam { team_name, patch } => {
+            let team = load_team(tx, team_name)?;
+            replace_team_rows(tx, team_name, &patch.apply(&team)?)
+        }
+        Mutation::PatchDriver { team_name, driver_name, changes } => {
+            let team = load_team(tx, team_name)?;
+            let patched_team = apply_driver_patch(&team, driver_name, changes)?;
+
+            // Solo se actualiza la fila del piloto modificado
+            let position = driver_position(&team, driver_name)?;
+            update_driver_row(tx, team_id(tx, team_name)?.unwrap_or_default(), position, &patched_team.drivers[position])
+        }
+        Mutation::InsertDriver { team_name, driver } => {
+            append_driver(tx, team_name, Driver { id: String::new(), ..driver.clone() })
+        }
+        Mutation::ReplaceDriver { team_name, driver_name, driver } => {
+            let team = load_team(tx, team_name)?;
+            let updated_team = set_driver(&team, driver_name, driver.clone())?;
+
+            let position = driver_position(&team, driver_name)?;
+            update_driver_row(tx, team_id(tx, team_name)?.unwrap_or_default(), position, &updated_team.drivers[position])
+        }
+        Mutation::DeleteDriver { team_name, driver_name } => {
+            let team = load_team(tx, team_name)?;
+            remove_driver(&team, driver_name)?;
+
+            // Borrar la fila y mover los pilotos siguientes una posición hacia atrás
+            let id = team_id(tx, team_name)?.unwrap_or_default();
+            let position = driver_position(&team, driver_name)?;
+            tx.execute("DELETE FROM drivers WHERE team_id = ?1 AND position = ?2", params![id, position])?;
+            tx.execute(
+                "UPDATE drivers SET position = position - 1 WHERE team_id = ?1 AND position > ?2",
+                params![id, position],
+            )?;
+            Ok(())
+        }
+        Mutation::TransferDriver { driver_name, from, to, date } => {
+            // El piloto conserva su identificador: se borra su fila antes de insertarlo en el destino
+            let driver = transferred_driver(&load_team(tx, from)?, driver_name, &load_team(tx, to)?, date)?;
+            apply_to_rows(tx, &Mutation::DeleteDriver { team_name: from.clone(), driver_name: driver_name.clone() })?;
+            append_driver(tx, to, driver)
+        }
+        Mutation::ReplaceAll { teams } => {
+            let mut teams = teams.clone();
+            assign_ids(&mut teams);
+            tx.execute("DELETE FROM teams", [])?;
+            for team in &teams {
+                insert_team_rows(tx, team)?;
+            }
+            Ok(())
+        }
+        Mutation::ReplaceSeasons { seasons } => {
+            tx.execute("DELETE FROM seasons", [])?;
+            insert_season_rows(tx, seasons)
+        }
+        Mutation::CheckVersion { team_name, versions } => {
+            check_version(load_teams(tx, Some(team_name))?.first(), team_name, versions)
+        }
+        Mutation::Batch { mutations } => {
+            for (index, mutation) in mutations.iter().enumerate() {
+                // Los errores de SQLite no son de la operación: se reportan como StorageError
+                apply_to_rows(tx, mutation).map_err(|error| {
+                    if error.is::<rusqlite::Error>() { error } else { BatchError::boxed(index, error) }
+                })?;
+            }
+            Ok(())
+        }
+    }
+}
+
+fn load_team(tx: &Transaction, team_name: &str) -> Result<Team, Box<dyn Error>> {
+    load_teams(tx, Some(team_name))?
+        .pop()
+        .ok_or_else(|| format!("El equipo '{}' no existe", team_name).into())
+}
+
+fn run_migrations(conn: &mut Connection) -> Result<(), Box<dyn Error>> {
+    let version: usize = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+
+    for (i, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        let tx = conn.transaction()?;
+        tx.execute_batch(migration)
+            .map_err(|e| format!("Error en la migración {} de SQLite: {}", i + 1, e))?;
+        tx.pragma_update(None, "user_version", i + 1)?;
+        tx.commit()?;
+    }
+
+    Ok(())
+}
+
+// Reemplazar la fila de la escudería y todos sus pilotos, conservando los identificadores
+fn replace_team_rows(tx: &Transaction, team_name: &str, team: &Team) -> Result<(), Box<dyn Error>> {
+    let current = load_team(tx, team_name)?;
+    let id = team_id(tx, team_name)?.unwrap_or_default();
+
+    // Si se renombra, el nuevo nombre no puede pertenecer a otra escudería
+    if team.name != current.name && name_exists(tx, &team.name)? {
+        return Err(format!("El equipo '{}' ya existe", team.name).into());
+    }
+    let team = keep_ids(&current, team.clone());
+
+    tx.execute(
+        "UPDATE teams SET name = ?1, extra = ?2 WHERE id = ?3",
+        params![team.name, Value::Object(team.extra.clone()).to_string(), id],
+    )?;
+    tx.execute("DELETE FROM drivers WHERE team_id = ?1", [id])?;
+    insert_driver_rows(tx, id, 0, &team.drivers)
+}
+
+fn team_id(conn: &Connection, team_name: &str) -> Result<Option<i64>, Box<dyn Error>> {
+    Ok(conn.query_row(TEAM_BY_KEY, [team_name], |row| row.get(0)).optional()?)
+}
+
+fn name_exists(conn: &Connection, team_name: &str) -> Result<bool, Box<dyn Error>> {
+    Ok(conn
+        .query_row("SELECT 1 FROM teams WHERE name = ?1 AND deleted_at IS NULL", [team_name], |row| row.get::<_, i64>(0))
+        .optional()?
+        .is_some())
+}
+
+// Un identificador vacío se guarda como NULL y se asigna después del cambio
+fn slug(id: &str) -> Option<&str> {
+    (!id.is_empty()).then_some(id)
+}
+
+fn insert_team_rows(tx: &Transaction, team: &Team) -> Result<(), Box<dyn Error>> {
+    tx.execute(
+        "INSERT INTO teams (slug, name, extra, deleted_at) VALUES (?1, ?2, ?3, ?4)",
+        params![slug(&team.id), team.name, Value::Object(team.extra.clone()).to_string(), team.deleted_at],
+    )?;
+    insert_driver_rows(tx, tx.last_insert_rowid(), 0, &team.drivers)
+}
+
+// Agregar un piloto al final de la escudería
+fn append_driver(tx: &Transaction, team_name: &str, driver: Driver) -> Result<(), Box<dyn Error>> {
+    let team = load_team(tx, team_name)?;
+    let updated_team = add_driver(&team, driver)?;
+
+    let id = team_id(tx, team_name)?.unwrap_or_default();
+    let position = team.drivers.len();
+    insert_driver_rows(tx, id, position, &updated_team.drivers[position..])
+}
+
+// Actualizar la fila del piloto en la posición indicada; su identificador no cambia
+fn update_driver_row(tx: &Transaction, team_id: i64, position: usize, driver: &Driver) -> Result<(), Box<dyn Error>> {
+    tx.execute(
+        "UPDATE drivers SET name = ?1, age = ?2, nationality = ?3, extra = ?4, transfers = ?5
+         WHERE team_id = ?6 AND position = ?7",
+        params![
+            driver.name,
+            driver.age,
+            driver.nationality,
+            Value::Object(driver.extra.clone()).to_string(),
+            serde_json::to_string(&driver.transfers)?,
+            team_id,
+            position,
+        ],
+    )?;
+    Ok(())
+}
+
+// Asignar identificador a las escuderías y pilotos que no lo tienen (slug NULL),
+// con las mismas reglas que los demás almacenamientos
+fn assign_missing_ids(tx: &Transaction) -> Result<(), Box<dyn Error>> {
+    let missing: i64 = tx.query_row(
+        "SELECT (SELECT COUNT(*) FROM teams WHERE slug IS NULL) + (SELECT COUNT(*) FROM drivers WHERE slug IS NULL)",
+        [],
+        |row| row.get(0),
+    )?;
+    if missing == 0 {
+        return Ok(());
+    }
+
+    // Se asignan considerando también los de las escuderías eliminadas, que no se reutilizan
+    let mut teams = load_teams(tx, None)?;
+    assign_ids(&mut teams);
+    for team in teams.iter().filter(|team| !team.is_deleted()) {
+        tx.execute(
+            "UPDATE teams SET slug = ?1 WHERE name = ?2 AND slug IS NULL AND deleted_at IS NULL",
+            params![team.id, team.name],
+        )?;
+        for driver in &team.drivers {
+            tx.execute(
+                "UPDATE drivers SET slug = ?1
+                 WHERE team_id = (SELECT id FROM teams WHERE name = ?2 AND deleted_at IS NULL) AND name = ?3 AND slug IS NULL",
+                params![driver.id, team.name, driver.name],
+            )?;
+        }
+    }
+    Ok(())
+}
+
+// Insertar los pilotos a partir de la posición `first_position`
+fn insert_driver_rows(
+    tx: &Transaction,
+    team_id: i64,
+    first_position: usize,
+    drivers: &[Driver],
+) -> Result<(), Box<dyn Error>> {
+    let mut stmt = tx.prepare(
+        "INSERT INTO drivers (slug, team_id, position, name, age, nationality, extra, transfers)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+    )?;
+    for (position, driver) in drivers.iter().enumerate() {
+        stmt.execute(params![
+            slug(&driver.id),
+            team_id,
+            first_position + position,
+            driver.name,
+            driver.age,
+            driver.nationality,
+            Value::Object(driver.extra.clone()).to_string(),
+            serde_json::to_string(&driver.transfers)?,
+        ])?;
+    }
+    Ok(())
+}
+
+// Cargar las escuderías (todas, incluidas las eliminadas, o solo la indicada) con sus pilotos, en orden de inserción
+fn load_teams(conn: &Connection, team_name: Option<&str>) -> Result<Vec<Team>, Box<dyn Error>> {
+    let mut team_stmt = conn.prepare(&format!(
+        "SELECT id, slug, name, extra, deleted_at FROM teams WHERE ?1 IS NULL OR id = ({}) ORDER BY id",
+        TEAM_BY_KEY
+    ))?;
+    let mut driver_stmt = conn.prepare(
+        "SELECT slug, name, age, nationality, extra, transfers FROM drivers WHERE team_id = ?1 ORDER BY position",
+    )?;
+
+    let rows = team_stmt.query_map([team_name], |row| {
+        Ok((
+            row.get::<_, i64>(0)?,
+            row.get::<_, Option<String>>(1)?,
+            row.get::<_, String>(2)?,
+            row.get::<_, String>(3)?,
+            row.get::<_, Option<String>>(4)?,
+        ))
+    })?;
+
+    let mut teams = Vec::new();
+    for row in rows {
+        let (row_id, id, name, extra, deleted_at) = row?;
+        let drivers = driver_stmt
+            .query_map([row_id], |row| {
+                Ok((
+                    row.get::<_, Option<String>>(0)?,
+                    row.get(1)?,
+                    row.get(2)?,
+                    row.get(3)?,
+                    row.get::<_, String>(4)?,
+                    row.get::<_, String>(5)?,
+                ))
+            })?
+            .map(|row| {
+                let (id, name, age, nationality, extra, transfers) = row?;
+                Ok(Driver {
+                    id: id.unwrap_or_default(),
+                    name,
+                    age,
+                    nationality,
+                    transfers: serde_json::from_str(&transfers)?,
+                    extra: serde_json::from_str(&extra)?,
+                })
+            })
+            .collect::<Result<Vec<Driver>, Box<dyn Error>>>()?;
+
+        teams.push(Team {
+            id: id.unwrap_or_default(),
+            name,
+            drivers,
+            deleted_at,
+            extra: serde_json::from_str(&extra)?,
+        });
+    }
+
+    Ok(teams)
+}
+
+fn insert_season_rows(tx: &Transaction, seasons: &[Season]) -> Result<(), Box<dyn Error>> {
+    for season in seasons {
+        tx.execute(
+            "INSERT INTO seasons (year, races) VALUES (?1, ?2)",
+            params![season.year, serde_json::to_string(&season.races)?],
+        )?;
+    }
+    Ok(())
+}
+
+fn load_seasons(conn: &Connection) -> Result<Vec<Season>, Box<dyn Error>> {
+    let mut stmt = conn.prepare("SELECT year, races FROM seasons ORDER BY year")?;
+    let rows = stmt.query_map([], |row| Ok((row.get::<_, u32>(0)?, row.get::<_, String>(1)?)))?;
+    rows.map(|row| {
+        let (year, races) = row?;
+        Ok(Season { year, races: serde_json::from_str(&races)? })
+    })
+    .collect()
+}
@@ -0,0 +1,1133 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{mpsc, Arc, Mutex, RwLock};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use notify::{EventKind, RecursiveMode, Watcher};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::json_hashmaps::f1_data_hashmap::{checksum, read_f1_data, write_f1_data};
+use crate::json_hashmaps::journal::{Journal, JournalEntry};
+use crate::json_hashmaps::migrations::migrate;
+use crate::json_hashmaps::models::{assign_ids, Driver, F1Data, FieldError, Team, Transfer, ValidationError};
+use crate::json_hashmaps::patch::TeamPatch;
+use crate::json_hashmaps::seasons::Season;
+use crate::utils::format_utc_timestamp;
+
+// Cambio de las temporadas para TeamStore::update_seasons
+pub type SeasonsUpdate<'a> = dyn FnMut(&mut Vec<Season>) -> Result<(), Box<dyn Error>> + 'a;
+
+// Observador de TeamStore::apply_observed: recibe la posición del paso y las escuderías
+// (incluidas las eliminadas) antes y después de aplicarlo
+pub type MutationObserver<'a> = dyn FnMut(usize, &[Team], &[Team]) + 'a;
+
+// Almacenamiento de escuderías usado por la API.
+// Cada implementación maneja su propia sincronización, por lo que se comparte como Arc<dyn TeamStore>.
+// Las escuderías y los pilotos se indican por su identificador o, si ninguno coincide, por su nombre.
+// Las escuderías eliminadas (con deleted_at) solo aparecen en `list_all` y para todo lo demás no existen.
+pub trait TeamStore: Send + Sync {
+    fn list(&self) -> Result<Vec<Team>, Box<dyn Error>>;
+    // Todas las escuderías, incluidas las eliminadas que todavía no se purgaron
+    fn list_all(&self) -> Result<Vec<Team>, Box<dyn Error>>;
+    fn get(&self, team_name: &str) -> Result<Option<Team>, Box<dyn Error>>;
+    fn insert(&self, team: Team) -> Result<(), Box<dyn Error>>;
+    fn replace(&self, team_name: &str, team: Team) -> Result<(), Box<dyn Error>>;
+    // Eliminar definitivamente; la API usa Mutation::SoftDelete
+    fn delete(&self, team_name: &str) -> Result<(), Box<dyn Error>>;
+    // Aplicar un Merge Patch o un JSON Patch de forma atómica
+    fn patch_team(&self, team_name: &str, patch: &TeamPatch) -> Result<(), Box<dyn Error>>;
+    fn patch_driver(
+        &self,
+        team_name: &str,
+        driver_name: &str,
+        changes: &Map<String, Value>,
+    ) -> Result<(), Box<dyn Error>>;
+    fn insert_driver(&self, team_name: &str, driver: Driver) -> Result<(), Box<dyn Error>>;
+    fn replace_driver(&self, team_name: &str, driver_name: &str, driver: Driver) -> Result<(), Box<dyn Error>>;
+    fn delete_driver(&self, team_name: &str, driver_name: &str) -> Result<(), Box<dyn Error>>;
+    // Aplicar cualquier cambio, incluidos los compuestos (Mutation::Batch) de forma atómica
+    fn apply_mutation(&self, mutation: Mutation) -> Result<(), Box<dyn Error>>;
+    // Como `apply_mutation`, llamando a `observe` en cada paso del cambio (cada mutación de un lote o,
+    // si no es un lote, la única). Se llama con los datos bloqueados y antes de guardarlos, así lo que
+    // observa es exactamente lo que se aplica; si el cambio falla, lo observado no se debe usar.
+    fn apply_observed(&self, mutation: Mutation, observe: &mut MutationObserver) -> Result<(), Box<dyn Error>>;
+    // Reemplazar todas las escuderías de una vez (p. ej. al restaurar una copia)
+    fn replace_all(&self, teams: Vec<Team>) -> Result<(), Box<dyn Error>>;
+    // Número que aumenta con cada cambio de los datos, incluidos los hechos fuera de la API
+    fn revision(&self) -> u64;
+    // Temporadas con sus carreras y resultados, ordenadas por año
+    fn seasons(&self) -> Result<Vec<Season>, Box<dyn Error>>;
+    // Modificar las temporadas de forma atómica; si `update` falla no se guarda ningún cambio
+    fn update_seasons(&self, update: &mut SeasonsUpdate) -> Result<(), Box<dyn Error>>;
+}
+
+// Cambio sobre las escuderías; es lo que se registra en el journal.
+// Los campos `team_name`, `driver_name`, `from` y `to` aceptan el identificador o el nombre
+// (las entradas anteriores a los identificadores usan el nombre).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Mutation {
+    Insert {
+        team: Team,
+    },
+    Replace {
+        team_name: String,
+        team: Team,
+    },
+    // Eliminar definitivamente
+    Delete {
+        team_name: String,
+    },
+    // Marcar la escudería como eliminada en `date`
+    SoftDelete {
+        team_name: String,
+        date: String,
+    },
+    // Deshacer la eliminación; falla con ConflictError si otra escudería ya usa su nombre
+    Restore {
+        team_name: String,
+    },
+    // Eliminar definitivamente las escuderías eliminadas antes de `before` (RFC 3339)
+    Purge {
+        before: String,
+    },
+    PatchTeam {
+        team_name: String,
+        patch: TeamPatch,
+    },
+    PatchDriver {
+        team_name: String,
+        driver_name: String,
+        changes: Map<String, Value>,
+    },
+    InsertDriver {
+        team_name: String,
+        driver: Driver,
+    },
+    ReplaceDriver {
+        team_name: String,
+        driver_name: String,
+        driver: Driver,
+    },
+    DeleteDriver {
+        team_name: String,
+        driver_name: String,
+    },
+    // Mover un piloto de `from` a `to`, registrando el traspaso en el piloto
+    TransferDriver {
+        driver_name: String,
+        from: String,
+        to: String,
+        date: String,
+    },
+    ReplaceAll {
+        teams: Vec<Team>,
+    },
+    // Reemplazar todas las temporadas; no está en la lista de escuderías, se aplica con `apply_to_data`
+    ReplaceSeasons {
+        seasons: Vec<Season>,
+    },
+    // Falla con PreconditionFailed si la escudería no está en ninguna de las versiones ("*" acepta cualquiera)
+    CheckVersion {
+        team_name: String,
+        versions: Vec<String>,
+    },
+    // Varios cambios que se aplican todos o ninguno
+    Batch {
+        mutations: Vec<Mutation>,
+    },
+}
+
+impl Mutation {
+    // Aplicar el cambio sobre la lista de escuderías y asignar identificador a las escuderías
+    // y pilotos nuevos. La asignación solo depende de los datos, así el journal se reaplica igual.
+    pub fn apply(&self, teams: &mut Vec<Team>) -> Result<(), Box<dyn Error>> {
+        self.apply_change(teams)?;
+        assign_ids(teams);
+        Ok(())
+    }
+
+    // Como `apply`, llamando a `observe` después de cada paso (ver TeamStore::apply_observed)
+    pub fn apply_observed(&self, teams: &mut Vec<Team>, observe: &mut MutationObserver) -> Result<(), Box<dyn Error>> {
+        match self {
+            Mutation::Batch { mutations } => {
+                for (index, mutation) in mutations.iter().enumerate() {
+                    let before = teams.clone();
+                    mutation.apply(teams).map_err(|error| BatchError::boxed(index, error))?;
+                    observe(index, &before, teams);
+                }
+                Ok(())
+            }
+            mutation => {
+                let before = teams.clone();
+                mutation.apply(teams)?;
+                observe(0, &before, teams);
+                Ok(())
+            }
+        }
+    }
+
+    // Aplicar con o sin observador
+    fn apply_with(&self, teams: &mut Vec<Team>, observe: Option<&mut MutationObserver>) -> Result<(), Box<dyn Error>> {
+        match observe {
+            Some(observe) => self.apply_observed(teams, observe),
+            None => self.apply(teams),
+        }
+    }
+
+    // Aplicar el cambio sobre el documento completo: las temporadas o las escuderías
+    pub fn apply_to_data(&self, data: &mut F1Data, observe: Option<&mut MutationObserver>) -> Result<(), Box<dyn Error>> {
+        match self {
+            Mutation::ReplaceSeasons { seasons } => {
+                data.seasons = seasons.clone();
+                Ok(())
+            }
+            mutation => mutation.apply_with(&mut data.teams, observe),
+        }
+    }
+
+    fn apply_change(&self, teams: &mut Vec<Team>) -> Result<(), Box<dyn Error>> {
+        match self {
+            Mutation::Insert { team } => insert_team(teams, without_ids(team.clone())),
+            Mutation::Replace { team_name, team } => replace_team(teams, team_name, team.clone()),
+            Mutation::Delete { team_name } => delete_team(teams, team_name),
+            Mutation::SoftDelete { team_name, date } => update_team(teams, team_name, |team| {
+                Ok(Team { deleted_at: Some(date.clone()), ..team.clone() })
+            }),
+            Mutation::Restore { team_name } => restore_team(teams, team_name),
+            Mutation::Purge { before } => {
+                teams.retain(|team| team.deleted_at.as_ref().is_none_or(|deleted_at| deleted_at >= before));
+                Ok(())
+            }
+            Mutation::PatchTeam { team_name, patch } => {
+                let Some(team) = find_team(teams, team_name) else {
+                    return Err(format!("El equipo '{}' no existe", team_name).into());
+                };
+                let patched_team = patch.apply(team)?;
+                replace_team(teams, team_name, patched_team)
+            }
+            Mutation::PatchDriver { team_name, driver_name, changes } => {
+                update_team(teams, team_name, |team| apply_driver_patch(team, driver_name, changes))
+            }
+            Mutation::InsertDriver { team_name, driver } => {
+                let driver = Driver { id: String::new(), ..driver.clone() };
+                update_team(teams, team_name, |team| add_driver(team, driver))
+            }
+            Mutation::ReplaceDriver { team_name, driver_name, driver } => {
+                update_team(teams, team_name, |team| set_driver(team, driver_name, driver.clone()))
+            }
+            Mutation::DeleteDriver { team_name, driver_name } => {
+                update_team(teams, team_name, |team| remove_driver(team, driver_name))
+            }
+            Mutation::TransferDriver { driver_name, from, to, date } => {
+                let (Some(from_team), Some(to_team)) = (find_team(teams, from), find_team(teams, to)) else {
+                    let missing = if find_team(teams, from).is_none() { from } else { to };
+                    return Err(format!("El equipo '{}' no existe", missing).into());
+                };
+                let driver = transferred_driver(from_team, driver_name, to_team, date)?;
+                let (from, to) = (from_team.id.clone(), to_team.id.clone());
+                update_team(teams, &from, |team| remove_driver(team, driver_name))?;
+                update_team(teams, &to, |team| add_driver(team, driver))
+            }
+            Mutation::ReplaceAll { teams: new_teams } => {
+                *teams = new_teams.clone();
+                Ok(())
+            }
+            Mutation::ReplaceSeasons { .. } => Err("Las temporadas no se pueden cambiar junto con las escuderías".into()),
+            Mutation::CheckVersion { team_name, versions } => check_version(find_team(teams, team_name), team_name, versions),
+            Mutation::Batch { mutations } => {
+                for (index, mutation) in mutations.iter().enumerate() {
+                    mutation.apply(teams).map_err(|error| BatchError::boxed(index, error))?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    // Escuderías que el cambio puede modificar, por su identificador o nombre antes del cambio
+    // y por su nombre después. None si pueden ser todas.
+    pub fn affected_teams(&self) -> Option<Vec<String>> {
+        match self {
+            Mutation::Insert { team } => Some(vec![team.name.clone()]),
+            Mutation::Replace { team_name, team } => Some(vec![team_name.clone(), team.name.clone()]),
+            Mutation::Delete { team_name }
+            | Mutation::SoftDelete { team_name, .. }
+            | Mutation::Restore { team_name }
+            | Mutation::PatchTeam { team_name, .. }
+            | Mutation::PatchDriver { team_name, .. }
+            | Mutation::InsertDriver { team_name, .. }
+            | Mutation::ReplaceDriver { team_name, .. }
+            | Mutation::DeleteDriver { team_name, .. } => Some(vec![team_name.clone()]),
+            Mutation::TransferDriver { from, to, .. } => Some(vec![from.clone(), to.clone()]),
+            // Purge solo quita escuderías eliminadas, que ya no se ven
+            Mutation::CheckVersion { .. } | Mutation::Purge { .. } | Mutation::ReplaceSeasons { .. } => Some(Vec::new()),
+            Mutation::ReplaceAll { .. } => None,
+            Mutation::Batch { mutations } => mutations
+                .iter()
+                .map(Mutation::affected_teams)
+                .collect::<Option<Vec<_>>>()
+                .map(|names| names.concat()),
+        }
+    }
+}
+
+// Cómo cambió una escudería visible en la API después de una mutación
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Created,
+    Updated,
+    Deleted,
+}
+
+impl ChangeKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChangeKind::Created => "created",
+            ChangeKind::Updated => "updated",
+            ChangeKind::Deleted => "deleted",
+        }
+    }
+}
+
+// Cambio de una escudería; `team` es su estado nuevo, o None si se eliminó
+#[derive(Debug, Clone, PartialEq)]
+pub struct TeamChange {
+    pub kind: ChangeKind,
+    pub id: String,
+    pub team: Option<Team>,
+}
+
+// Comparar dos estados de las mismas escuderías por identificador. Los cambios de pilotos
+// son cambios de su escudería; una restauración aparece como creación.
+pub fn team_changes(before: &[Team], after: &[Team]) -> Vec<TeamChange> {
+    let previous: HashMap<&str, &Team> = before.iter().map(|team| (team.id.as_str(), team)).collect();
+    let mut changes: Vec<TeamChange> = after
+        .iter()
+        .filter_map(|team| {
+            let kind = match previous.get(team.id.as_str()) {
+                None => ChangeKind::Created,
+                Some(previous) if *previous != team => ChangeKind::Updated,
+                Some(_) => return None,
+            };
+            Some(TeamChange { kind, id: team.id.clone(), team: Some(team.clone()) })
+        })
+        .collect();
+    changes.extend(
+        before
+            .iter()
+            .filter(|team| !after.iter().any(|current| current.id == team.id))
+            .map(|team| TeamChange { kind: ChangeKind::Deleted, id: team.id.clone(), team: None }),
+    );
+    changes
+}
+
+// Error al leer o escribir el almacenamiento; la API lo responde con 500
+#[derive(Debug)]
+pub struct StorageError(pub String);
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Error de almacenamiento: {}", self.0)
+    }
+}
+
+impl Error for StorageError {}
+
+impl StorageError {
+    pub fn boxed(error: impl fmt::Display) -> Box<dyn Error> {
+        Box::new(StorageError(error.to_string()))
+    }
+}
+
+// Error cuando el cambio pedido choca con el estado actual de los datos (el archivo cambió en disco,
+// un `test` de JSON Patch falló o un piloto quedaría en dos escuderías); la API lo responde con 409
+#[derive(Debug)]
+pub struct ConflictError(pub String);
+
+impl fmt::Display for ConflictError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Error for ConflictError {}
+
+// Error de un cambio dentro de un Mutation::Batch, con su posición en el lote
+#[derive(Debug)]
+pub struct BatchError {
+    pub index: usize,
+    pub error: Box<dyn Error>,
+}
+
+impl fmt::Display for BatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Operación {}: {}", self.index, self.error)
+    }
+}
+
+impl Error for BatchError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(self.error.as_ref())
+    }
+}
+
+impl BatchError {
+    pub fn boxed(index: usize, error: Box<dyn Error>) -> Box<dyn Error> {
+        Box::new(BatchError { index, error })
+    }
+
+    // Error original de un cambio, quitando los BatchError que lo envuelven
+    pub fn unwrap_nested(error: Box<dyn Error>) -> Box<dyn Error> {
+        match error.downcast::<BatchError>() {
+            Ok(batch_error) => BatchError::unwrap_nested(batch_error.error),
+            Err(error) => error,
+        }
+    }
+}
+
+// Error cuando la escudería no está en la versión indicada por If-Match; la API lo responde con 412
+#[derive(Debug)]
+pub struct PreconditionFailed(pub String);
+
+impl fmt::Display for PreconditionFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Error for PreconditionFailed {}
+
+// Almacenamiento respaldado por un archivo JSON (data/f1_data.json por defecto).
+// Cada cambio se agrega primero al journal (f1_data.journal) y luego se reescribe el archivo
+// de forma atómica; al abrir se reaplican los cambios del journal que no llegaron al archivo.
+// Los cambios hechos al archivo fuera del servidor se detectan comparando su checksum.
+pub struct JsonFileStore {
+    path: PathBuf,
+    data: RwLock<F1Data>,
+    journal: Mutex<Journal>,
+    // Checksum del archivo tal como el servidor lo leyó o escribió por última vez
+    disk_checksum: Mutex<String>,
+    revision: AtomicU64,
+    // Rechazar los cambios que dejan a un piloto en dos escuderías
+    unique_drivers: bool,
+}
+
+impl JsonFileStore {
+    // Abrir el archivo de datos, aplicando las migraciones y el journal pendientes
+    pub fn open(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let raw = read_f1_data(path)?;
+        let mut data: F1Data = serde_path_to_error::deserialize(Value::Object(raw.into_iter().collect()))
+            .map_err(|e| format!("Error parsing JSON data ({}): {}", e.path(), e.inner()))?;
+
+        let mut journal = Journal::open(&path.with_extension("journal"))?;
+        let pending: Vec<JournalEntry> = journal
+            .read_entries()?
+            .into_iter()
+            .filter(|entry| entry.seq > data.journal_seq)
+            .collect();
+
+        // Las escuderías agregadas al archivo fuera del servidor no tienen identificador
+        let assigned = assign_ids(&mut data.teams);
+        if !pending.is_empty() {
+            // En el journal solo hay cambios que se aplicaron bien: si uno falla, el archivo o el journal
+            // están dañados y no se toca ninguno de los dos para que se puedan corregir a mano
+            for entry in &pending {
+                entry.mutation.apply_to_data(&mut data, None).map_err(|e| {
+                    format!(
+                        "No se pudo reaplicar la entrada {} del journal {}: {}",
+                        entry.seq,
+                        journal.path().display(),
+                        e
+                    )
+                })?;
+                data.journal_seq = entry.seq;
+            }
+            println!("Reaplicados {} cambios del journal", pending.len());
+        }
+        if assigned || !pending.is_empty() {
+            write_f1_data(path, &data)?;
+        }
+        journal.clear()?;
+
+        Ok(JsonFileStore {
+            path: path.to_path_buf(),
+            data: RwLock::new(data),
+            journal: Mutex::new(journal),
+            disk_checksum: Mutex::new(checksum(&fs::read(path)?)),
+            revision: AtomicU64::new(0),
+            unique_drivers: false,
+        })
+    }
+
+    pub fn with_unique_drivers(mut self, unique_drivers: bool) -> Self {
+        self.unique_drivers = unique_drivers;
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // Recargar el archivo si fue modificado fuera del servidor (p. ej. con scripts/json_management.py).
+    // Devuelve true si se cargaron datos nuevos; si el archivo no es válido se conservan los actuales.
+    pub fn reload(&self) -> Result<bool, Box<dyn Error>> {
+        let mut data = self.data.write().unwrap();
+        self.reload_locked(&mut data)
+    }
+
+    // Vigilar el archivo de datos (inotify en Linux) y recargarlo cuando cambie.
+    // Se vigila la carpeta porque las escrituras atómicas reemplazan el archivo con un rename.
+    pub fn watch(self: Arc<Self>) -> Result<(), Box<dyn Error>> {
+        let (tx, rx) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(tx)?;
+        let data_dir = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        watcher.watch(data_dir, RecursiveMode::NonRecursive)?;
+
+        thread::spawn(move || {
+            let _watcher = watcher; // Si se destruye deja de recibir eventos
+            while let Ok(event) = rx.recv() {
+                let Ok(event) = event else {
+                    continue;
+                };
+                let is_data_file = event.paths.iter().any(|p| p.file_name() == self.path.file_name());
+                let is_change = matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_));
+                if !is_data_file || !is_change {
+                    continue;
+                }
+
+                // Esperar a que termine la escritura y descartar los eventos que generó
+                thread::sleep(Duration::from_millis(100));
+                while rx.try_recv().is_ok() {}
+
+                match self.reload() {
+                    Ok(true) => println!("Datos recargados desde {}", self.path.display()),
+                    Ok(false) => {}
+                    Err(e) => eprintln!("No se recargó {}: {}", self.path.display(), e),
+                }
+            }
+        });
+
+        Ok(())
+    }
+
+    // Comparar el checksum del archivo con el último conocido y cargarlo si cambió.
+    // Se llama con el lock de escritura de los datos tomado.
+    fn reload_locked(&self, data: &mut F1Data) -> Result<bool, Box<dyn Error>> {
+        let mut disk_checksum = self.disk_checksum.lock().unwrap();
+        let content = match fs::read(&self.path) {
+            Ok(content) => content,
+            // Si el archivo se borró, la próxima escritura lo vuelve a crear
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(StorageError::boxed(e)),
+        };
+        let current_checksum = checksum(&content);
+        if current_checksum == *disk_checksum {
+            return Ok(false);
+        }
+
+        let mut raw: HashMap<String, Value> = serde_json::from_slice(&content)
+            .map_err(|e| format!("Error parsing JSON data: {}", e))?;
+        let migrated = migrate(&mut raw)?;
+        let mut new_data: F1Data = serde_path_to_error::deserialize(Value::Object(raw.into_iter().collect()))
+            .map_err(ValidationError::from)?;
+        let errors = new_data.validate();
+        if !errors.is_empty() {
+            return Err(ValidationError { errors }.into());
+        }
+        let assigned = assign_ids(&mut new_data.teams);
+
+        // Un archivo con un esquema anterior o sin identificadores se guarda ya completo
+        *disk_checksum = if migrated || assigned {
+            write_f1_data(&self.path, &new_data).map_err(StorageError::boxed)?
+        } else {
+            current_checksum
+        };
+        *data = new_data;
+        self.revision.fetch_add(1, Ordering::SeqCst);
+        Ok(true)
+    }
+
+    // Si el archivo cambió en disco y todavía no se había recargado, no se sobrescribe:
+    // se cargan los datos nuevos y se reporta el conflicto para que el cliente reintente
+    fn check_disk_unchanged(&self, data: &mut F1Data) -> Result<(), Box<dyn Error>> {
+        match self.reload_locked(data) {
+            Ok(false) => Ok(()),
+            Ok(true) => Err(Box::new(ConflictError(
+                "El archivo de datos cambió en disco; se recargaron los datos, vuelva a intentar".to_string(),
+            ))),
+            Err(e) if e.is::<StorageError>() => Err(e),
+            Err(e) => Err(Box::new(ConflictError(format!(
+                "El archivo de datos cambió en disco y no es válido: {}",
+                e
+            )))),
+        }
+    }
+
+    fn apply(&self, mutation: Mutation) -> Result<(), Box<dyn Error>> {
+        self.commit(mutation, None)
+    }
+
+    // Aplicar un cambio sobre una copia de los datos, registrarlo en el journal y guardar el archivo.
+    // Si algo falla, los datos en memoria no se modifican.
+    fn commit(&self, mutation: Mutation, observe: Option<&mut MutationObserver>) -> Result<(), Box<dyn Error>> {
+        let mut data = self.data.write().unwrap();
+        self.check_disk_unchanged(&mut data)?;
+        self.commit_locked(&mut data, mutation, observe)
+    }
+
+    // Como `commit`, con los datos ya bloqueados y verificados contra el disco
+    fn commit_locked(&self, data: &mut F1Data, mutation: Mutation, observe: Option<&mut MutationObserver>) -> Result<(), Box<dyn Error>> {
+        let mut updated = data.clone();
+        mutation.apply_to_data(&mut updated, observe)?;
+        if self.unique_drivers {
+            check_unique_drivers(&data.teams, &updated.teams)?;
+        }
+        updated.journal_seq += 1;
+
+        let mut journal = self.journal.lock().unwrap();
+        let entry = JournalEntry { seq: updated.journal_seq, mutation };
+        let previous_len = journal.append(&entry).map_err(StorageError::boxed)?;
+
+        let written_checksum = match write_f1_data(&self.path, &updated) {
+            Ok(written_checksum) => written_checksum,
+            Err(e) => {
+                // El cambio no llegó al archivo: quitarlo del journal para no reaplicarlo al reiniciar
+                if let Err(undo) = journal.truncate(previous_len) {
+                    eprintln!("No se pudo deshacer la entrada {} del journal: {}", entry.seq, undo);
+                }
+                return Err(StorageError::boxed(e));
+            }
+        };
+        *self.disk_checksum.lock().unwrap() = written_checksum;
+
+        // El archivo ya contiene el cambio; si no se puede vaciar el journal, la entrada
+        // se ignora al reiniciar porque su secuencia no es mayor que journal_seq
+        if let Err(e) = journal.clear() {
+            eprintln!("No se pudo vaciar el journal {}: {}", journal.path().display(), e);
+        }
+
+        *data = updated;
+        self.revision.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+impl TeamStore for JsonFileStore {
+    fn list(&self) -> Result<Vec<Team>, Box<dyn Error>> {
+        Ok(live_teams(&self.data.read().unwrap().teams))
+    }
+
+    fn list_all(&self) -> Result<Vec<Team>, Box<dyn Error>> {
+        Ok(self.data.read().unwrap().teams.clone())
+    }
+
+    fn get(&self, team_name: &str) -> Result<Option<Team>, Box<dyn Error>> {
+        Ok(find_team(&self.data.read().unwrap().teams, team_name).cloned())
+    }
+
+    fn insert(&self, team: Team) -> Result<(), Box<dyn Error>> {
+        self.apply(Mutation::Insert { team })
+    }
+
+    fn replace(&self, team_name: &str, team: Team) -> Result<(), Box<dyn Error>> {
+        self.apply(Mutation::Replace { team_name: team_name.to_string(), team })
+    }
+
+    fn delete(&self, team_name: &str) -> Result<(), Box<dyn Error>> {
+        self.apply(Mutation::Delete { team_name: team_name.to_string() })
+    }
+
+    fn patch_team(&self, team_name: &str, patch: &TeamPatch) -> Result<(), Box<dyn Error>> {
+        self.apply(Mutation::PatchTeam {
+            team_name: team_name.to_string(),
+            patch: patch.clone(),
+        })
+    }
+
+    fn patch_driver(
+        &self,
+        team_name: &str,
+        driver_name: &str,
+        changes: &Map<String, Value>,
+    ) -> Result<(), Box<dyn Error>> {
+        self.apply(Mutation::PatchDriver {
+            team_name: team_name.to_string(),
+            driver_name: driver_name.to_string(),
+            changes: changes.clone(),
+        })
+    }
+
+    fn insert_driver(&self, team_name: &str, driver: Driver) -> Result<(), Box<dyn Error>> {
+        self.apply(Mutation::InsertDriver { team_name: team_name.to_string(), driver })
+    }
+
+    fn replace_driver(&self, team_name: &str, driver_name: &str, driver: Driver) -> Result<(), Box<dyn Error>> {
+        self.apply(Mutation::ReplaceDriver {
+            team_name: team_name.to_string(),
+            driver_name: driver_name.to_string(),
+            driver,
+        })
+    }
+
+    fn delete_driver(&self, team_name: &str, driver_name: &str) -> Result<(), Box<dyn Error>> {
+        self.apply(Mutation::DeleteDriver {
+            team_name: team_name.to_string(),
+            driver_name: driver_name.to_string(),
+        })
+    }
+
+    fn apply_mutation(&self, mutation: Mutation) -> Result<(), Box<dyn Error>> {
+        self.apply(mutation)
+    }
+
+    fn apply_observed(&self, mutation: Mutation, observe: &mut MutationObserver) -> Result<(), Box<dyn Error>> {
+        self.commit(mutation, Some(observe))
+    }
+
+    fn replace_all(&self, teams: Vec<Team>) -> Result<(), Box<dyn Error>> {
+        self.apply(Mutation::ReplaceAll { teams })
+    }
+
+    fn revision(&self) -> u64 {
+        self.revision.load(Ordering::SeqCst)
+    }
+
+    fn seasons(&self) -> Result<Vec<Season>, Box<dyn Error>> {
+        Ok(self.data.read().unwrap().seasons.clone())
+    }
+
+    // El resultado de `update` se guarda como Mutation::ReplaceSeasons, así pasa por el journal
+    // como cualquier otro cambio
+    fn update_seasons(&self, update: &mut SeasonsUpdate) -> Result<(), Box<dyn Error>> {
+        let mut data = self.data.write().unwrap();
+        self.check_disk_unchanged(&mut data)?;
+
+        let mut seasons = data.seasons.clone();
+        update(&mut seasons)?;
+        self.commit_locked(&mut data, Mutation::ReplaceSeasons { seasons }, None)
+    }
+}
+
+// Almacenamiento en memoria, sin persistencia (usado en las pruebas)
+#[derive(Default)]
+pub struct MemoryStore {
+    teams: RwLock<Vec<Team>>,
+    seasons: RwLock<Vec<Season>>,
+    revision: AtomicU64,
+    unique_drivers: bool,
+}
+
+impl MemoryStore {
+    pub fn new(mut teams: Vec<Team>) -> Self {
+        assign_ids(&mut teams);
+        MemoryStore {
+            teams: RwLock::new(teams),
+            seasons: RwLock::new(Vec::new()),
+            revision: AtomicU64::new(0),
+            unique_drivers: false,
+        }
+    }
+
+    pub fn with_unique_drivers(mut self, unique_drivers: bool) -> Self {
+        self.unique_drivers = unique_drivers;
+        self
+    }
+
+    fn apply(&self, mutation: Mutation) -> Result<(), Box<dyn Error>> {
+        self.commit(mutation, None)
+    }
+
+    fn commit(&self, mutation: Mutation, observe: Option<&mut MutationObserver>) -> Result<(), Box<dyn Error>> {
+        if let Mutation::ReplaceSeasons { seasons } = mutation {
+            return self.update_seasons(&mut |current| {
+                *current = seasons.clone();
+                Ok(())
+            });
+        }
+
+        let mut teams = self.teams.write().unwrap();
+        let mut updated = teams.clone();
+        mutation.apply_with(&mut updated, observe)?;
+        if self.unique_drivers {
+            check_unique_drivers(&teams, &updated)?;
+        }
+        *teams = updated;
+        self.revision.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+impl TeamStore for MemoryStore {
+    fn list(&self) -> Result<Vec<Team>, Box<dyn Error>> {
+        Ok(live_teams(&self.teams.read().unwrap()))
+    }
+
+    fn list_all(&self) -> Result<Vec<Team>, Box<dyn Error>> {
+        Ok(self.teams.read().unwrap().clone())
+    }
+
+    fn get(&self, team_name: &str) -> Result<Option<Team>, Box<dyn Error>> {
+        Ok(find_team(&self.teams.read().unwrap(), team_name).cloned())
+    }
+
+    fn insert(&self, team: Team) -> Result<(), Box<dyn Error>> {
+        self.apply(Mutation::Insert { team })
+    }
+
+    fn replace(&self, team_name: &str, team: Team) -> Result<(), Box<dyn Error>> {
+        self.apply(Mutation::Replace { team_name: team_name.to_string(), team })
+    }
+
+    fn delete(&self, team_name: &str) -> Result<(), Box<dyn Error>> {
+        self.apply(Mutation::Delete { team_name: team_name.to_string() })
+    }
+
+    fn patch_team(&self, team_name: &str, patch: &TeamPatch) -> Result<(), Box<dyn Error>> {
+        self.apply(Mutation::PatchTeam {
+            team_name: team_name.to_string(),
+            patch: patch.clone(),
+        })
+    }
+
+    fn patch_driver(
+        &self,
+        team_name: &str,
+        driver_name: &str,
+        changes: &Map<String, Value>,
+    ) -> Result<(), Box<dyn Error>> {
+        self.apply(Mutation::PatchDriver {
+            team_name: team_name.to_string(),
+            driver_name: driver_name.to_string(),
+            changes: changes.clone(),
+        })
+    }
+
+    fn insert_driver(&self, team_name: &str, driver: Driver) -> Result<(), Box<dyn Error>> {
+        self.apply(Mutation::InsertDriver { team_name: team_name.to_string(), driver })
+    }
+
+    fn replace_driver(&self, team_name: &str, driver_name: &str, driver: Driver) -> Result<(), Box<dyn Error>> {
+        self.apply(Mutation::ReplaceDriver {
+            team_name: team_name.to_string(),
+            driver_name: driver_name.to_string(),
+            driver,
+        })
+    }
+
+    fn delete_driver(&self, team_name: &str, driver_name: &str) -> Result<(), Box<dyn Error>> {
+        self.apply(Mutation::DeleteDriver {
+            team_name: team_name.to_string(),
+            driver_name: driver_name.to_string(),
+        })
+    }
+
+    fn apply_mutation(&self, mutation: Mutation) -> Result<(), Box<dyn Error>> {
+        self.apply(mutation)
+    }
+
+    fn apply_observed(&self, mutation: Mutation, observe: &mut MutationObserver) -> Result<(), Box<dyn Error>> {
+        self.commit(mutation, Some(observe))
+    }
+
+    fn replace_all(&self, teams: Vec<Team>) -> Result<(), Box<dyn Error>> {
+        self.apply(Mutation::ReplaceAll { teams })
+    }
+
+    fn revision(&self) -> u64 {
+        self.revision.load(Ordering::SeqCst)
+    }
+
+    fn seasons(&self) -> Result<Vec<Season>, Box<dyn Error>> {
+        Ok(self.seasons.read().unwrap().clone())
+    }
+
+    fn update_seasons(&self, update: &mut SeasonsUpdate) -> Result<(), Box<dyn Error>> {
+        let mut seasons = self.seasons.write().unwrap();
+        let mut updated = seasons.clone();
+        update(&mut updated)?;
+        *seasons = updated;
+        self.revision.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+// Purgar las escuderías eliminadas hace más de `retention`; devuelve cuántas se purgaron.
+// Si no hay ninguna no se aplica ningún cambio, así los datos no se reescriben.
+pub fn purge_deleted(store: &dyn TeamStore, retention: Duration) -> Result<usize, Box<dyn Error>> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let before = format_utc_timestamp(now.saturating_sub(retention).as_secs());
+    let expired = store
+        .list_all()?
+        .iter()
+        .filter(|team| team.deleted_at.as_ref().is_some_and(|deleted_at| *deleted_at < before))
+        .count();
+    if expired > 0 {
+        store.apply_mutation(Mutation::Purge { before })?;
+    }
+    Ok(expired)
+}
+
+// Operaciones sobre la lista de escuderías compartidas por las implementaciones
+
+fn live_teams(teams: &[Team]) -> Vec<Team> {
+    teams.iter().filter(|team| !team.is_deleted()).cloned().collect()
+}
+
+// Posición de la escudería no eliminada con ese identificador o, si no hay ninguna, con ese nombre
+fn team_position(teams: &[Team], team_name: &str) -> Option<usize> {
+    let live = |team: &&Team| !team.is_deleted();
+    teams
+        .iter()
+        .position(|team| live(&team) && team.id == team_name)
+        .or_else(|| teams.iter().position(|team| live(&team) && team.name == team_name))
+}
+
+pub(crate) fn find_team<'a>(teams: &'a [Team], team_name: &str) -> Option<&'a Team> {
+    team_position(teams, team_name).map(|pos| &teams[pos])
+}
+
+fn insert_team(teams: &mut Vec<Team>, team: Team) -> Result<(), Box<dyn Error>> {
+    // Verificar si el equipo ya existe
+    if teams.iter().any(|t| t.name == team.name && !t.is_deleted()) {
+        return Err("El equipo ya existe".into());
+    }
+
+    teams.push(team);
+    Ok(())
+}
+
+fn replace_team(teams: &mut [Team], team_name: &str, team: Team) -> Result<(), Box<dyn Error>> {
+    // Verificar si el equipo existe
+    let Some(pos) = team_position(teams, team_name) else {
+        return Err(format!("El equipo '{}' no existe", team_name).into());
+    };
+
+    // Si se renombra, el nuevo nombre no puede pertenecer a otra escudería
+    if team.name != teams[pos].name && teams.iter().any(|t| t.name == team.name && !t.is_deleted()) {
+        return Err(format!("El equipo '{}' ya existe", team.name).into());
+    }
+
+    teams[pos] = keep_ids(&teams[pos], team);
+    Ok(())
+}
+
+fn delete_team(teams: &mut Vec<Team>, team_name: &str) -> Result<(), Box<dyn Error>> {
+    let Some(pos) = team_position(teams, team_name) else {
+        return Err(format!("El equipo '{}' no existe", team_name).into());
+    };
+
+    teams.remove(pos);
+    Ok(())
+}
+
+// Quitar la marca de eliminada a la escudería eliminada con ese identificador o, si no hay ninguna,
+// a la última eliminada con ese nombre
+fn restore_team(teams: &mut [Team], team_name: &str) -> Result<(), Box<dyn Error>> {
+    let deleted = || teams.iter().enumerate().filter(|(_, team)| team.is_deleted());
+    let by_id = deleted().find(|(_, team)| team.id == team_name);
+    let by_name = || {
+        deleted()
+            .filter(|(_, team)| team.name == team_name)
+            .max_by(|(_, a), (_, b)| a.deleted_at.cmp(&b.deleted_at))
+    };
+    let Some(pos) = by_id.or_else(by_name).map(|(pos, _)| pos) else {
+        return Err(format!("El equipo '{}' no está eliminado", team_name).into());
+    };
+
+    if teams.iter().any(|t| t.name == teams[pos].name && !t.is_deleted()) {
+        return Err(Box::new(ConflictError(format!(
+            "Ya existe otra escudería llamada '{}'; renómbrela antes de restaurar esta",
+            teams[pos].name
+        ))));
+    }
+
+    teams[pos].deleted_at = None;
+    Ok(())
+}
+
+// Reemplazar una escudería por el resultado de `change`
+fn update_team(
+    teams: &mut [Team],
+    team_name: &str,
+    change: impl FnOnce(&Team) -> Result<Team, Box<dyn Error>>,
+) -> Result<(), Box<dyn Error>> {
+    let Some(pos) = team_position(teams, team_name) else {
+        return Err(format!("El equipo '{}' no existe", team_name).into());
+    };
+
+    teams[pos] = change(&teams[pos])?;
+    Ok(())
+}
+
+// Quitar los identificadores y la fecha de eliminación que envía el cliente: los asigna el servidor
+pub(crate) fn without_ids(mut team: Team) -> Team {
+    team.id.clear();
+    team.deleted_at = None;
+    for driver in &mut team.drivers {
+        driver.id.clear();
+    }
+    team
+}
+
+// Nueva versión de `current` que conserva sus identificadores: el de la escudería y el de cada
+// piloto que ya estaba en ella (por su identificador o, si no lo trae, por su nombre).
+// Los pilotos nuevos quedan sin identificador. La fecha de eliminación tampoco cambia.
+pub(crate) fn keep_ids(current: &Team, mut team: Team) -> Team {
+    team.id = current.id.clone();
+    team.deleted_at = current.deleted_at.clone();
+    let mut available: Vec<&Driver> = current.drivers.iter().collect();
+    for driver in &mut team.drivers {
+        let previous = available
+            .iter()
+            .position(|d| !driver.id.is_empty() && d.id == driver.id)
+            .or_else(|| available.iter().position(|d| d.name == driver.name));
+        driver.id = previous.map(|pos| available.remove(pos).id.clone()).unwrap_or_default();
+    }
+    team
+}
+
+pub(crate) fn check_version(team: Option<&Team>, team_name: &str, versions: &[String]) -> Result<(), Box<dyn Error>> {
+    let Some(team) = team else {
+        return Err(Box::new(PreconditionFailed(format!("El equipo '{}' no existe", team_name))));
+    };
+
+    let current = team.version();
+    if !versions.iter().any(|version| version == "*" || *version == current) {
+        return Err(Box::new(PreconditionFailed(format!(
+            "El equipo '{}' cambió; su versión actual es \"{}\"",
+            team_name, current
+        ))));
+    }
+    Ok(())
+}
+
+// Con la restricción de pilotos únicos, un piloto no puede pertenecer a más de una escudería.
+// Solo se rechazan los cambios que agregan una repetición: las que ya existían en los datos
+// no impiden modificar otras escuderías.
+pub(crate) fn check_unique_drivers(before: &[Team], after: &[Team]) -> Result<(), Box<dyn Error>> {
+    let teams_before = teams_by_driver(before);
+    for (driver, teams) in teams_by_driver(after) {
+        let previous_count = teams_before.get(&driver).map_or(0, Vec::len);
+        if teams.len() > 1 && teams.len() > previous_count {
+            return Err(Box::new(ConflictError(format!(
+                "El piloto '{}' ya pertenece a otra escudería ({})",
+                driver,
+                teams.join(", ")
+            ))));
+        }
+    }
+    Ok(())
+}
+
+// Escuderías no eliminadas de cada piloto; la identidad del piloto es su nombre sin distinguir mayúsculas
+fn teams_by_driver(teams: &[Team]) -> HashMap<String, Vec<String>> {
+    let mut teams_by_driver: HashMap<String, Vec<String>> = HashMap::new();
+    for team in teams.iter().filter(|team| !team.is_deleted()) {
+        for driver in &team.drivers {
+            teams_by_driver.entry(driver.name.trim().to_lowercase()).or_default().push(team.name.clone());
+        }
+    }
+    teams_by_driver
+}
+
+// Piloto de `from_team` con el traspaso a `to_team` agregado a su historial; conserva su identificador
+pub(crate) fn transferred_driver(from_team: &Team, driver_name: &str, to_team: &Team, date: &str) -> Result<Driver, Box<dyn Error>> {
+    if from_team.id == to_team.id {
+        return Err(format!("El conductor '{}' ya pertenece al equipo '{}'", driver_name, to_team.name).into());
+    }
+
+    let mut driver = from_team.drivers[driver_position(from_team, driver_name)?].clone();
+    driver.transfers.push(Transfer {
+        from: from_team.name.clone(),
+        to: to_team.name.clone(),
+        date: date.to_string(),
+    });
+    Ok(driver)
+}
+
+// Posición del piloto con ese identificador o, si no hay ninguno, con ese nombre
+pub(crate) fn driver_position(team: &Team, driver_name: &str) -> Result<usize, Box<dyn Error>> {
+    team.drivers
+        .iter()
+        .position(|d| d.id == driver_name)
+        .or_else(|| team.drivers.iter().position(|d| d.name == driver_name))
+        .ok_or_else(|| format!("El conductor '{}' no existe en el equipo '{}'", driver_name, team.name).into())
+}
+
+// Validar la escudería completa después de modificar sus pilotos
+fn validated(team: Team) -> Result<Team, Box<dyn Error>> {
+    let errors = team.validate();
+    if !errors.is_empty() {
+        return Err(ValidationError { errors }.into());
+    }
+    Ok(team)
+}
+
+// Agregar un piloto al final de la escudería
+pub(crate) fn add_driver(team: &Team, driver: Driver) -> Result<Team, Box<dyn Error>> {
+    if team.drivers.iter().any(|d| d.name == driver.name) {
+        return Err(format!("El conductor '{}' ya existe en el equipo '{}'", driver.name, team.name).into());
+    }
+
+    let mut updated = team.clone();
+    updated.drivers.push(driver);
+    validated(updated)
+}
+
+// Reemplazar un piloto conservando su posición en la escudería
+pub(crate) fn set_driver(team: &Team, driver_name: &str, driver: Driver) -> Result<Team, Box<dyn Error>> {
+    let position = driver_position(team, driver_name)?;
+
+    let mut updated = team.clone();
+    updated.drivers[position] = Driver { id: team.drivers[position].id.clone(), ..driver };
+    validated(updated)
+}
+
+pub(crate) fn remove_driver(team: &Team, driver_name: &str) -> Result<Team, Box<dyn Error>> {
+    let position = driver_position(team, driver_name)?;
+
+    let mut updated = team.clone();
+    updated.drivers.remove(position);
+    Ok(updated)
+}
+
+// Aplicar cambios a un piloto y devolver la escudería resultante, ya validada
+pub(crate) fn apply_driver_patch(
+    team: &Team,
+    driver_name: &str,
+    changes: &Map<String, Value>,
+) -> Result<Team, Box<dyn Error>> {
+    // Aplicar los cambios sobre el JSON de la escudería para validarla completa
+    let position = driver_position(team, driver_name)?;
+    let mut patched_team = serde_json::to_value(team)?;
+    let Some(driver) = patched_team["drivers"][position].as_object_mut() else {
+        return Err(format!("El conductor '{}' no existe en el equipo '{}'", driver_name, team.name).into());
+    };
+
+    for (key, value) in changes {
+        // El identificador lo asigna el servidor: enviarlo sin cambios se acepta, cambiarlo no
+        if key == "id" {
+            if driver.get("id") != Some(value) {
+                return Err(ValidationError {
+                    errors: vec![FieldError::new("id", "El identificador lo asigna el servidor y no se puede modificar")],
+                }
+                .into());
+            }
+            continue;
+        }
+        // "nacionality" es un alias de "nationality": no dejar ambas claves en el piloto
+        if key == "nacionality" {
+            driver.remove("nationality");
+        }
+        driver.insert(key.clone(), value.clone());
+    }
+
+    Ok(Team::from_value(patched_team)?)
+}
@@ -0,0 +1,367 @@
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{ErrorKind, Read};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+use crate::json_hashmaps::f1_data_hashmap::write_f1_data;
+use crate::json_hashmaps::models::{FieldError, ValidationError};
+use crate::server_http::sse::{EventStream, SseEvent};
+use crate::utils::now_utc;
+
+// Tipos de cambio a los que se puede suscribir un webhook
+pub const WEBHOOK_EVENTS: &[&str] = &["created", "updated", "deleted"];
+
+// Entregas fallidas que se conservan
+const DEAD_LETTER_CAPACITY: usize = 100;
+
+// Tiempo máximo de espera por la respuesta del receptor
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Error cuando no existe el webhook o la entrega pedida; la API lo responde con 404
+#[derive(Debug)]
+pub enum WebhookNotFound {
+    Webhook(String),
+    Delivery(u64),
+}
+
+impl fmt::Display for WebhookNotFound {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WebhookNotFound::Webhook(id) => write!(f, "El webhook '{}' no existe", id),
+            WebhookNotFound::Delivery(id) => write!(f, "La entrega {} no está entre las fallidas", id),
+        }
+    }
+}
+
+impl Error for WebhookNotFound {}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Webhook {
+    pub id: String,
+    pub dataset: String,
+    pub url: String,
+    // Clave con la que se firman las entregas; solo se muestra al registrar el webhook
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub secret: String,
+    // Tipos de cambio que se envían; vacío para todos
+    #[serde(default)]
+    pub events: Vec<String>,
+    pub created_at: String,
+}
+
+// Envío de un cambio a un webhook. Las que agotan los intentos quedan con `failed_at`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Delivery {
+    pub id: u64,
+    pub webhook_id: String,
+    pub dataset: String,
+    pub url: String,
+    pub event: String,
+    pub payload: Value,
+    pub attempts: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failed_at: Option<String>,
+    // Momento del próximo intento, en milisegundos
+    #[serde(skip)]
+    next_attempt: u64,
+}
+
+// Lo que se guarda en el archivo; las entregas pendientes se pierden al reiniciar
+#[derive(Default, Serialize, Deserialize)]
+struct WebhookState {
+    webhooks: Vec<Webhook>,
+    dead_letters: VecDeque<Delivery>,
+    #[serde(skip)]
+    queue: Vec<Delivery>,
+    #[serde(skip)]
+    last_delivery_id: u64,
+}
+
+// Webhooks registrados y sus entregas. Cada cambio publicado en el stream de eventos se envía
+// firmado a los webhooks de su conjunto de datos; si el receptor falla se reintenta con espera
+// exponencial (`retry_delay`, el doble, ...) y después de `max_attempts` queda entre las fallidas.
+pub struct Webhooks {
+    path: PathBuf,
+    max_attempts: u32,
+    retry_delay: Duration,
+    state: Mutex<WebhookState>,
+    changed: Condvar,
+}
+
+impl Webhooks {
+    pub fn open(path: &Path, max_attempts: u32, retry_delay: Duration) -> Result<Self, Box<dyn Error>> {
+        let mut state: WebhookState = match fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str(&content)
+                .map_err(|e| format!("Error reading webhooks {}: {}", path.display(), e))?,
+            Err(e) if e.kind() == ErrorKind::NotFound => WebhookState::default(),
+            Err(e) => return Err(format!("Error reading webhooks {}: {}", path.display(), e).into()),
+        };
+        state.last_delivery_id = state.dead_letters.iter().map(|delivery| delivery.id).max().unwrap_or(0);
+
+        Ok(Webhooks {
+            path: path.to_path_buf(),
+            max_attempts: max_attempts.max(1),
+            retry_delay,
+            state: Mutex::new(state),
+            changed: Condvar::new(),
+        })
+    }
+
+    // Registrar un webhook a partir del cuerpo {"url": ..., "events": [...], "secret": ...}.
+    // Si no se indica la clave se genera una.
+    pub fn register(&self, dataset: &str, body: &Value) -> Result<Webhook, Box<dyn Error>> {
+        let mut errors = Vec::new();
+        let url = body.get("url").and_then(Value::as_str).unwrap_or_default();
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            errors.push(FieldError::new("url", "Se esperaba una URL http:// o https://"));
+        }
+        let events: Vec<String> = match body.get("events") {
+            None | Some(Value::Null) => Vec::new(),
+            Some(Value::Array(events)) => events.iter().map(|event| event.as_str().unwrap_or_default().to_string()).collect(),
+            Some(_) => vec![String::new()],
+        };
+        if events.iter().any(|event| !WEBHOOK_EVENTS.contains(&event.as_str())) {
+            errors.push(FieldError::new("events", &format!("Los tipos válidos son {}", WEBHOOK_EVENTS.join(", "))));
+        }
+        let secret = match body.get("secret") {
+            None | Some(Value::Null) => random_hex(32),
+            Some(Value::String(secret)) if secret.len() >= 16 => secret.clone(),
+            Some(_) => {
+                errors.push(FieldError::new("secret", "La clave debe tener al menos 16 caracteres"));
+                String::new()
+            }
+        };
+        if !errors.is_empty() {
+            return Err(ValidationError { errors }.into());
+        }
+
+        let webhook = Webhook {
+            id: format!("wh_{}", random_hex(8)),
+            dataset: dataset.to_string(),
+            url: url.to_string(),
+            secret,
+            events,
+            created_at: now_utc(),
+        };
+        let mut state = self.state.lock().unwrap();
+        state.webhooks.push(webhook.clone());
+        self.save(&state)?;
+        Ok(webhook)
+    }
+
+    // Webhooks del conjunto de datos, sin sus claves
+    pub fn list(&self, dataset: &str) -> Vec<Webhook> {
+        let state = self.state.lock().unwrap();
+        state
+            .webhooks
+            .iter()
+            .filter(|webhook| webhook.dataset == dataset)
+            .map(|webhook| Webhook { secret: String::new(), ..webhook.clone() })
+            .collect()
+    }
+
+    // Eliminar un webhook y descartar sus entregas pendientes
+    pub fn delete(&self, dataset: &str, id: &str) -> Result<(), Box<dyn Error>> {
+        let mut state = self.state.lock().unwrap();
+        let Some(pos) = state.webhooks.iter().position(|webhook| webhook.dataset == dataset && webhook.id == id) else {
+            return Err(WebhookNotFound::Webhook(id.to_string()).into());
+        };
+        state.webhooks.remove(pos);
+        state.queue.retain(|delivery| delivery.webhook_id != id);
+        self.save(&state)
+    }
+
+    // Entregas que agotaron los intentos, de la más antigua a la más reciente
+    pub fn dead_letters(&self, dataset: &str) -> Vec<Delivery> {
+        let state = self.state.lock().unwrap();
+        state.dead_letters.iter().filter(|delivery| delivery.dataset == dataset).cloned().collect()
+    }
+
+    // Volver a intentar una entrega fallida, con todos sus intentos
+    pub fn retry(&self, dataset: &str, delivery_id: u64) -> Result<Delivery, Box<dyn Error>> {
+        let mut state = self.state.lock().unwrap();
+        let Some(pos) = state.dead_letters.iter().position(|d| d.dataset == dataset && d.id == delivery_id) else {
+            return Err(WebhookNotFound::Delivery(delivery_id).into());
+        };
+        if !state.webhooks.iter().any(|webhook| webhook.id == state.dead_letters[pos].webhook_id) {
+            return Err(WebhookNotFound::Webhook(state.dead_letters[pos].webhook_id.clone()).into());
+        }
+
+        let mut delivery = state.dead_letters.remove(pos).unwrap();
+        delivery.attempts = 0;
+        delivery.failed_at = None;
+        delivery.next_attempt = now_millis();
+        state.queue.push(delivery.clone());
+        self.save(&state)?;
+        self.changed.notify_all();
+        Ok(delivery)
+    }
+
+    // Hilos que toman los cambios de `events` y los entregan. Las entregas se hacen de a una,
+    // así un receptor lento demora a los demás como máximo DELIVERY_TIMEOUT por intento.
+    pub fn start(self: Arc<Self>, events: Arc<EventStream>) {
+        let webhooks = Arc::clone(&self);
+        thread::spawn(move || {
+            let mut last_id = events.last_id();
+            loop {
+                for event in events.wait_after(last_id, Duration::from_secs(60)) {
+                    last_id = event.id;
+                    webhooks.enqueue(&event);
+                }
+            }
+        });
+
+        thread::spawn(move || {
+            let client = match reqwest::blocking::Client::builder().timeout(DELIVERY_TIMEOUT).build() {
+                Ok(client) => client,
+                Err(e) => return eprintln!("Error creating the webhook client: {}", e),
+            };
+            loop {
+                let delivery = self.next_due();
+                self.deliver(&client, delivery);
+            }
+        });
+    }
+
+    fn enqueue(&self, event: &SseEvent) {
+        let data: Value = serde_json::from_str(&event.data).unwrap_or_default();
+        let payload = json!({
+            "event": event.event,
+            "event_id": event.id,
+            "timestamp": now_utc(),
+            "dataset": event.topic,
+            "team_id": data["id"],
+            "team": data["team"],
+        });
+
+        let mut state = self.state.lock().unwrap();
+        let webhooks: Vec<Webhook> = state
+            .webhooks
+            .iter()
+            .filter(|webhook| webhook.dataset == event.topic)
+            .filter(|webhook| webhook.events.is_empty() || webhook.events.contains(&event.event))
+            .cloned()
+            .collect();
+        for webhook in webhooks {
+            state.last_delivery_id += 1;
+            let delivery = Delivery {
+                id: state.last_delivery_id,
+                webhook_id: webhook.id,
+                dataset: webhook.dataset,
+                url: webhook.url,
+                event: event.event.clone(),
+                payload: payload.clone(),
+                attempts: 0,
+                last_error: None,
+                failed_at: None,
+                next_attempt: now_millis(),
+            };
+            state.queue.push(delivery);
+        }
+        self.changed.notify_all();
+    }
+
+    // Esperar la próxima entrega cuyo intento ya corresponde
+    fn next_due(&self) -> Delivery {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            let now = now_millis();
+            let next = state.queue.iter().enumerate().min_by_key(|(_, delivery)| (delivery.next_attempt, delivery.id));
+            let wait = match next {
+                Some((pos, delivery)) if delivery.next_attempt <= now => return state.queue.remove(pos),
+                Some((_, delivery)) => Duration::from_millis(delivery.next_attempt - now),
+                None => Duration::from_secs(60),
+            };
+            state = self.changed.wait_timeout(state, wait).unwrap().0;
+        }
+    }
+
+    fn deliver(&self, client: &reqwest::blocking::Client, mut delivery: Delivery) {
+        // Si el webhook se eliminó mientras tanto la entrega se descarta
+        let secret = {
+            let state = self.state.lock().unwrap();
+            match state.webhooks.iter().find(|webhook| webhook.id == delivery.webhook_id) {
+                Some(webhook) => webhook.secret.clone(),
+                None => return,
+            }
+        };
+
+        let body = delivery.payload.to_string();
+        let response = client
+            .post(&delivery.url)
+            .header("Content-Type", "application/json")
+            .header("X-Webhook-Id", &delivery.webhook_id)
+            .header("X-Webhook-Delivery", delivery.id.to_string())
+            .header("X-Webhook-Event", &delivery.event)
+            .header("X-Webhook-Signature", format!("sha256={}", sign(&secret, &body)))
+            .body(body)
+            .send();
+        let error = match response {
+            Ok(response) if response.status().is_success() => return,
+            Ok(response) => format!("El receptor respondió {}", response.status()),
+            Err(e) => format!("Error enviando la entrega: {}", e),
+        };
+
+        delivery.attempts += 1;
+        delivery.last_error = Some(error);
+        let mut state = self.state.lock().unwrap();
+        if delivery.attempts >= self.max_attempts {
+            eprintln!("Entrega {} al webhook {} fallida: {}", delivery.id, delivery.webhook_id, delivery.last_error.as_deref().unwrap_or_default());
+            delivery.failed_at = Some(now_utc());
+            state.dead_letters.push_back(delivery);
+            if state.dead_letters.len() > DEAD_LETTER_CAPACITY {
+                state.dead_letters.pop_front();
+            }
+            if let Err(e) = self.save(&state) {
+                eprintln!("{}", e);
+            }
+        } else {
+            let backoff = self.retry_delay.saturating_mul(1 << (delivery.attempts - 1).min(16));
+            delivery.next_attempt = now_millis() + backoff.as_millis() as u64;
+            state.queue.push(delivery);
+        }
+    }
+
+    fn save(&self, state: &WebhookState) -> Result<(), Box<dyn Error>> {
+        write_f1_data(&self.path, state)?;
+        Ok(())
+    }
+}
+
+// Firma de una entrega: HMAC-SHA256 del cuerpo con la clave del webhook, en hexadecimal
+pub fn sign(secret: &str, body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC acepta claves de cualquier largo");
+    mac.update(body.as_bytes());
+    mac.finalize().into_bytes().iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+// Bytes aleatorios del sistema en hexadecimal. Donde no existe /dev/urandom se usa un hash
+// de la hora y un contador, que no es impredecible: conviene indicar la clave al registrar.
+fn random_hex(len: usize) -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let mut bytes = vec![0u8; len];
+    if File::open("/dev/urandom").and_then(|mut file| file.read_exact(&mut bytes)).is_err() {
+        let mut seed = Sha256::new();
+        seed.update(SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos().to_be_bytes());
+        seed.update(COUNTER.fetch_add(1, Ordering::Relaxed).to_be_bytes());
+        seed.update(std::process::id().to_be_bytes());
+        bytes = seed.finalize().iter().cycle().take(len).copied().collect();
+    }
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64
+}
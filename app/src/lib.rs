@@ -1,4 +1,6 @@
-pub mod utils;
-pub mod json_hashmaps;
-pub mod http_functions;
-pub mod server_http;
\ No newline at end of file
+pub mod utils;
+pub mod json_hashmaps;
+pub mod http_functions;
+pub mod server_http;
+pub mod cli;
+pub mod config;
\ No newline at end of file
@@ -9,18 +9,37 @@
 //
 // Repositorio: https://github.com/joctan-tec/http_server
 
-mod utils;
-use utils::print_hashmap;
-mod json_hashmaps;
-use json_hashmaps::f1_data_hashmap::get_f1_data;
-mod http_functions;
-use http_functions::functions::{post_team, put_team, delete_team, patch_driver};
-mod server_http;
+use http_server_proyecto1_so::json_hashmaps::audit::{driver_entity, returned_team_entity, team_entity, AuditLog, AuditQuery};
+use http_server_proyecto1_so::json_hashmaps::datasets::Datasets;
+use http_server_proyecto1_so::json_hashmaps::formats::DataFormat;
+use http_server_proyecto1_so::json_hashmaps::models::Driver;
+use http_server_proyecto1_so::json_hashmaps::snapshots::SnapshotManager;
+use http_server_proyecto1_so::json_hashmaps::webhooks::Webhooks;
+use http_server_proyecto1_so::json_hashmaps::f1_data_hashmap::checksum;
+use http_server_proyecto1_so::json_hashmaps::team_store::{BatchError, JsonFileStore, TeamStore};
+#[cfg(feature = "sqlite")]
+use http_server_proyecto1_so::json_hashmaps::sqlite_store::SqliteStore;
+use http_server_proyecto1_so::http_functions::functions::{get_teams, list_teams, get_team, post_team, put_team, delete_team, restore_team, patch_team, patch_driver};
+use http_server_proyecto1_so::http_functions::functions::{get_drivers, get_driver, post_driver, put_driver, delete_driver, transfer_driver, search};
+use http_server_proyecto1_so::http_functions::batch::apply_batch;
+use http_server_proyecto1_so::http_functions::handlers::{ApiRequest, ApiResponse, ApiRoutes};
+use http_server_proyecto1_so::http_functions::import_export::{export_teams, import_teams, ImportMode};
+use http_server_proyecto1_so::http_functions::seasons::{
+    delete_race, delete_season, get_constructor_standings, get_driver_standings, get_race, get_season, get_seasons, put_race,
+    put_season,
+};
+use http_server_proyecto1_so::http_functions::query::ListQuery;
+use http_server_proyecto1_so::server_http::server::Server;
+use http_server_proyecto1_so::server_http::sse::EventStream;
+use http_server_proyecto1_so::cli::run_snapshots_command;
+use http_server_proyecto1_so::config::Config;
+use http_server_proyecto1_so::utils::percent_encode;
 
-use serde_json::Value;
-use server_http::server::Server;
+use serde_json::{json, Value};
 
 use std::collections::HashMap;
+use std::error::Error;
+use std::path::Path;
 use std::{io::Write, net::TcpStream, sync::Arc, sync::RwLock};
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::time::{SystemTime, UNIX_EPOCH};
@@ -74,244 +93,493 @@ fn clean_expired_cookies(cookies: &Arc<RwLock<HashMap<usize, Cookie>>>, max_age:
     cookies_map.retain(|_, cookie| now - cookie.created_at < max_age);
 }
 
+// Crear el almacenamiento de un conjunto de datos. Con la feature "sqlite" y F1_STORAGE=sqlite
+// se usa un archivo .db junto al JSON (data/f1_data.db), importando el JSON la primera vez.
+fn open_store(json_path: &Path, unique_drivers: bool) -> Result<Arc<dyn TeamStore>, Box<dyn Error>> {
+    #[cfg(feature = "sqlite")]
+    if std::env::var("F1_STORAGE").as_deref() == Ok("sqlite") {
+        let store = SqliteStore::open(&json_path.with_extension("db"))?.with_unique_drivers(unique_drivers);
+        store.import_json_once(json_path)?;
+        return Ok(Arc::new(store));
+    }
+
+    // Recargar los cambios que se hagan al archivo fuera del servidor (scripts/json_management.py)
+    let store = Arc::new(JsonFileStore::open(json_path)?.with_unique_drivers(unique_drivers));
+    if let Err(e) = Arc::clone(&store).watch() {
+        eprintln!("No se pudo vigilar {}: {}", json_path.display(), e);
+    }
+    Ok(store)
+}
+
+
+// Formato, modo y ?dry_run= de POST /api/import. Sin ?format= se usa el Content-Type del cuerpo.
+fn import_options(request: &ApiRequest) -> Result<(DataFormat, ImportMode, bool), Box<dyn Error>> {
+    let format = match request.query("format") {
+        Some(format) => DataFormat::parse(format)?,
+        None => DataFormat::from_content_type(request.header("Content-Type").unwrap_or("application/json"))?,
+    };
+    let mode = ImportMode::parse(request.query("mode").unwrap_or("merge"))?;
+    let dry_run = request
+        .query("dry_run")
+        .unwrap_or("false")
+        .parse()
+        .map_err(|_| "dry_run debe ser true o false")?;
+    Ok((format, mode, dry_run))
+}
+
+// Leer una variable de entorno numérica, usando `default` si no existe o no es válida
+fn env_or<T: std::str::FromStr>(name: &str, default: T) -> T {
+    std::env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default)
+}
+
 fn main() {
-    let data_shared = Arc::new(RwLock::new(get_f1_data().unwrap()));
+    // Subcomandos de administración: no levantan el servidor
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("snapshots") {
+        if let Err(e) = run_snapshots_command(&args[1..]) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let config = Config::load(&args).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(2);
+    });
+
+    // Cambios de las escuderías para GET /api/escuderias/events (se guardan los 1000 más recientes)
+    let events = Arc::new(EventStream::new(env_or("F1_EVENTS_BUFFER", 1000)));
+    Arc::clone(&events).start(Duration::from_secs(env_or("F1_EVENTS_KEEP_ALIVE_SECS", 15)));
+
+    // Cada conjunto de datos tiene su propio almacenamiento
+    let mut datasets = Datasets::with_events(open_store(&config.data_path, config.unique_drivers).unwrap(), Arc::clone(&events)).unwrap();
+    for (name, path) in &config.datasets {
+        datasets.insert(name, open_store(path, config.unique_drivers).unwrap()).unwrap();
+    }
+    println!("Conjuntos de datos: {}", datasets.names().join(", "));
+    let datasets = Arc::new(datasets);
+    let store = datasets.default_store();
+    // Registro de los cambios hechos por la API (data/audit.jsonl)
+    let audit = Arc::new(AuditLog::open(&config.data_path.with_file_name("audit.jsonl")).unwrap());
+    // Webhooks registrados (data/webhooks.json). Cada entrega se intenta hasta 5 veces, esperando
+    // 2, 4, 8 y 16 segundos entre intentos.
+    let webhooks = Arc::new(Webhooks::open(
+        &config.data_path.with_file_name("webhooks.json"),
+        env_or("F1_WEBHOOK_MAX_ATTEMPTS", 5),
+        Duration::from_secs(env_or("F1_WEBHOOK_RETRY_SECS", 2)),
+    ).unwrap());
+    Arc::clone(&webhooks).start(Arc::clone(&events));
     let cookies: Arc<RwLock<HashMap<usize, Cookie>>> = Arc::new(RwLock::new(HashMap::new()));
     let cookie_counter = Arc::new(AtomicUsize::new(0)); // Contador para cookies
 
+    // Copias periódicas de los datos en data/snapshots (cada hora, se conservan 24 por defecto)
+    let snapshots = Arc::new(SnapshotManager::new(
+        &config.data_path.with_file_name("snapshots"),
+        env_or("F1_SNAPSHOT_RETENTION", 24),
+    ));
+    Arc::clone(&snapshots).start_periodic(
+        Arc::clone(&store),
+        Duration::from_secs(env_or("F1_SNAPSHOT_INTERVAL_SECS", 3600)),
+    );
+
+    // Las escuderías eliminadas se pueden restaurar durante 30 días; después se purgan
+    Arc::clone(&datasets).start_purge(
+        Duration::from_secs(env_or("F1_DELETED_RETENTION_SECS", 2_592_000)),
+        Duration::from_secs(env_or("F1_PURGE_INTERVAL_SECS", 3600)),
+    );
+
     let mut server = Server::new(20); // Pool de 20 hilos
 
-    // Ruta para obtener escuderías
-    let data_shared_clone = Arc::clone(&data_shared);
+    // Cada solicitud usa la cookie de sesión del cliente o una nueva
     let cookies_clone = Arc::clone(&cookies);
     let cookie_counter_clone = Arc::clone(&cookie_counter);
-    server.add_route(
-        "GET",
-        "/api/escuderias",
-        move |stream: &mut TcpStream, request: HashMap<String, Value>| {
-            clean_expired_cookies(&cookies_clone, 60); // Limpiar cookies expiradas (60 segundos)
-            let data = data_shared_clone.read().unwrap();
-            let cookie_value = handle_cookie(&request, &cookies_clone, &cookie_counter_clone);
-
-            let response = format!(
-                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nSet-Cookie: session={}; Max-Age=60; HttpOnly\r\n\r\n{}",
-                cookie_value,
-                serde_json::to_string(&*data).unwrap()
-            );
-
-            stream.write(response.as_bytes()).unwrap();
-            stream.flush().unwrap();
-        },
-    );
+    let mut routes = ApiRoutes::new(&mut server, Arc::clone(&datasets), Arc::clone(&audit), move |request: &HashMap<String, Value>| {
+        clean_expired_cookies(&cookies_clone, 60); // Limpiar cookies expiradas (60 segundos)
+        handle_cookie(request, &cookies_clone, &cookie_counter_clone)
+    });
 
     // Ruta para obtener escuderías
-    let data_shared_clone = Arc::clone(&data_shared);
-    let cookies_clone = Arc::clone(&cookies);
-    let cookie_counter_clone = Arc::clone(&cookie_counter);
-    server.add_route(
-        "GET",
-        "/api/escuderias_lenta",
-        move |stream: &mut TcpStream, request: HashMap<String, Value>| {
-            clean_expired_cookies(&cookies_clone, 60); // Limpiar cookies expiradas (60 segundos)
-            let data = data_shared_clone.read().unwrap();
-            let cookie_value = handle_cookie(&request, &cookies_clone, &cookie_counter_clone);
-            thread::sleep(Duration::from_secs(5)); // Simular una operación lenta
-            let response = format!(
-                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nSet-Cookie: session={}; Max-Age=60; HttpOnly\r\n\r\n{}",
-                cookie_value,
-                serde_json::to_string(&*data).unwrap()
-            );
+    routes.dataset_route("GET", "/escuderias", "500 Internal Server Error", |request: &ApiRequest| {
+        // Filtros, orden y paginación (?driver.nationality=British&sort=-name&limit=10&cursor=...)
+        let page = list_teams(request.store()?.as_ref(), &ListQuery::parse(&request.query_map())?)?;
+        let body = json!({
+            "teams": request.project(json!(page.items))?,
+            "total": page.total,
+            "limit": page.limit,
+            "next_cursor": page.next_cursor,
+            "prev_cursor": page.prev_cursor
+        });
+        // La lista no tiene versión propia: el ETag es el hash de la respuesta
+        let etag = checksum(body.to_string().as_bytes())[..16].to_string();
+        let response = match request.links(&page) {
+            Some(links) => ApiResponse::ok(body).header("Link", &links),
+            None => ApiResponse::ok(body),
+        };
+        Ok(response.cached(&request.request, &etag))
+    });
 
-            stream.write(response.as_bytes()).unwrap();
-            stream.flush().unwrap();
-        },
-    );
+    // Ruta para obtener una escudería
+    routes.team_route("GET", "/escuderias/:name", "404 Not Found", |request: &ApiRequest| {
+        let team = get_team(&request.param("name"), request.store()?.as_ref())?;
+        let body = json!({ "team": request.project(json!(team))? });
+        // Con ?fields la respuesta es otra representación y necesita su propio ETag
+        let etag = match request.query("fields") {
+            Some(_) => checksum(body.to_string().as_bytes())[..16].to_string(),
+            None => team.version(),
+        };
+        Ok(ApiResponse::ok(body).cached(&request.request, &etag))
+    });
 
-        // Ruta para agregar una nueva escudería
-        let data_shared_clone = Arc::clone(&data_shared);
-        let cookies_clone = Arc::clone(&cookies);
-        let cookie_counter_clone = Arc::clone(&cookie_counter);
-        server.add_route(
-            "POST",
-            "/api/escuderias_lenta",
-            move |stream: &mut TcpStream, request: HashMap<String, Value>| {
-                clean_expired_cookies(&cookies_clone, 60); // Limpiar cookies expiradas
-                let mut data = data_shared_clone.write().unwrap();
-                let cookie_value = handle_cookie(&request, &cookies_clone, &cookie_counter_clone);
-                
-                thread::sleep(Duration::from_secs(5)); // Simular una operación lenta
-
-                let mut response = String::new();
-                if let Some(body) = request.get("body") {
-                    match post_team(body.clone(), &mut data) {
-                        Ok(_) => {
-                            response = format!(
-                                "HTTP/1.1 201 Created\r\nContent-Type: application/json\r\nSet-Cookie: session={}; Max-Age=60; HttpOnly\r\n\r\n{{\"message\": \"Team added\"}}",
-                                cookie_value
-                            );
-                        }
-                        Err(e) => {
-                            response = format!("HTTP/1.1 400 Bad Request\r\nContent-Type: application/json\r\nSet-Cookie: session={}; Max-Age=60; HttpOnly\r\n\r\n{{\"error\": \"{}\"}}", cookie_value, e);
-                        }
-                    }
-                } else {
-                    response = format!("HTTP/1.1 400 Bad Request\r\nContent-Type: application/json\r\nSet-Cookie: session={}; Max-Age=60; HttpOnly\r\n\r\n{{\"error\": \"Invalid request body\"}}", cookie_value);
-                }
-    
-                stream.write(response.as_bytes()).unwrap();
-                stream.flush().unwrap();
-            },
-        );
+    // Ruta para obtener escuderías
+    routes.route("GET", "/api/escuderias_lenta", "500 Internal Server Error", |request: &ApiRequest| {
+        thread::sleep(Duration::from_secs(5)); // Simular una operación lenta
+        Ok(ApiResponse::ok(json!({ "teams": get_teams(request.store()?.as_ref())? })))
+    });
 
     // Ruta para agregar una nueva escudería
-    let data_shared_clone = Arc::clone(&data_shared);
-    let cookies_clone = Arc::clone(&cookies);
-    let cookie_counter_clone = Arc::clone(&cookie_counter);
-    server.add_route(
-        "POST",
-        "/api/escuderias",
-        move |stream: &mut TcpStream, request: HashMap<String, Value>| {
-            clean_expired_cookies(&cookies_clone, 60); // Limpiar cookies expiradas
-            let mut data = data_shared_clone.write().unwrap();
-            let cookie_value = handle_cookie(&request, &cookies_clone, &cookie_counter_clone);
-
-            let mut response = String::new();
-            if let Some(body) = request.get("body") {
-                match post_team(body.clone(), &mut data) {
-                    Ok(_) => {
-                        response = format!(
-                            "HTTP/1.1 201 Created\r\nContent-Type: application/json\r\nSet-Cookie: session={}; Max-Age=60; HttpOnly\r\n\r\n{{\"message\": \"Team added\"}}",
-                            cookie_value
-                        );
-                    }
-                    Err(e) => {
-                        response = format!("HTTP/1.1 400 Bad Request\r\nContent-Type: application/json\r\nSet-Cookie: session={}; Max-Age=60; HttpOnly\r\n\r\n{{\"error\": \"{}\"}}", cookie_value, e);
-                    }
-                }
-            } else {
-                response = format!("HTTP/1.1 400 Bad Request\r\nContent-Type: application/json\r\nSet-Cookie: session={}; Max-Age=60; HttpOnly\r\n\r\n{{\"error\": \"Invalid request body\"}}", cookie_value);
-            }
+    routes.route("POST", "/api/escuderias_lenta", "400 Bad Request", |request: &ApiRequest| {
+        thread::sleep(Duration::from_secs(5)); // Simular una operación lenta
+        let body = request.body()?;
+        let store = request.store()?;
+        request.record("post_team", returned_team_entity, || post_team(body.clone(), store.as_ref()))?;
+        Ok(ApiResponse::json("201 Created", json!({ "message": "Team added" })))
+    });
 
-            stream.write(response.as_bytes()).unwrap();
-            stream.flush().unwrap();
+    // Ruta para recibir los cambios de las escuderías a medida que ocurren (Server-Sent Events)
+    let events_clone = Arc::clone(&events);
+    routes.stream_dataset_route(
+        "GET",
+        "/escuderias/events",
+        "500 Internal Server Error",
+        move |stream: &mut TcpStream, request: &ApiRequest| {
+            request.store()?;
+            let subscriber = stream.try_clone()?;
+            let response = ApiResponse::content("200 OK", "text/event-stream", String::new())
+                .header("Cache-Control", "no-cache")
+                .header("Connection", "keep-alive");
+            // La conexión queda abierta y desde aquí le escribe el hilo de eventos;
+            // este hilo del pool vuelve a quedar libre
+            if stream.write_all(response.to_http(&request.cookie_value).as_bytes()).and_then(|_| stream.flush()).is_ok() {
+                let last_event_id = request.header("Last-Event-ID").and_then(|id| id.trim().parse().ok());
+                events_clone.subscribe(subscriber, &request.dataset(), last_event_id);
+            }
+            Ok(())
         },
     );
 
+    // Ruta para agregar una nueva escudería
+    routes.dataset_route("POST", "/escuderias", "400 Bad Request", |request: &ApiRequest| {
+        let body = request.body()?;
+        let store = request.store()?;
+        let team = request.record("post_team", returned_team_entity, || post_team(body.clone(), store.as_ref()))?;
+        Ok(ApiResponse::created(
+            &format!("{}/{}", request.path(), percent_encode(&team.id)),
+            json!({ "message": "Team added", "id": team.id }),
+        ))
+    });
+
     // Ruta para actualizar una escudería (PUT)
-    let data_shared_clone = Arc::clone(&data_shared);
-    let cookies_clone = Arc::clone(&cookies);
-    let cookie_counter_clone = Arc::clone(&cookie_counter);
-    server.add_route(
-        "PUT",
-        "/api/escuderias/:name",
-        move |stream: &mut TcpStream, request: HashMap<String, Value>| {
-            clean_expired_cookies(&cookies_clone, 60); // Limpiar cookies expiradas
-            let mut data = data_shared_clone.write().unwrap();
-            let cookie_value = handle_cookie(&request, &cookies_clone, &cookie_counter_clone);
-
-            let pathAux = request.get("path").and_then(Value::as_str).unwrap_or("");
-            let path = &pathAux.replace("%20", " ");
-            let path_parts: Vec<&str> = path.split("/").collect();
-            let name = path_parts[3];
-
-            let mut response = String::new();
-            if let Some(body) = request.get("body") {
-                match put_team(name, body.clone(), &mut data) {
-                    Ok(_) => {
-                        response = format!(
-                            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nSet-Cookie: session={}; Max-Age=60; HttpOnly\r\n\r\n{{\"message\": \"Team updated\"}}",
-                            cookie_value
-                        );
-                    }
-                    Err(e) => {
-                        response = format!("HTTP/1.1 400 Bad Request\r\nContent-Type: application/json\r\nSet-Cookie: session={}; Max-Age=60; HttpOnly\r\n\r\n{{\"error\": \"{}\"}}", cookie_value, e);
-                    }
-                }
-            } else {
-                response = format!("HTTP/1.1 400 Bad Request\r\nContent-Type: application/json\r\nSet-Cookie: session={}; Max-Age=60; HttpOnly\r\n\r\n{{\"error\": \"Invalid request body\"}}", cookie_value);
-            }
+    routes.team_route("PUT", "/escuderias/:name", "400 Bad Request", |request: &ApiRequest| {
+        let name = request.param("name");
+        let body = request.body()?;
+        let store = request.store()?;
+        request.record(
+            "put_team",
+            |_| team_entity(store.as_ref(), &name),
+            || put_team(&name, body.clone(), request.header("If-Match"), store.as_ref()),
+        )?;
+        Ok(ApiResponse::message("Team updated"))
+    });
 
-            stream.write(response.as_bytes()).unwrap();
-            stream.flush().unwrap();
-        },
-    );
+    // Ruta para modificar parte de una escudería (JSON Merge Patch o JSON Patch)
+    routes.team_route("PATCH", "/escuderias/:name", "400 Bad Request", |request: &ApiRequest| {
+        let name = request.param("name");
+        let content_type = request.header("Content-Type").unwrap_or_default();
+        let body = request.body()?;
+        let store = request.store()?;
+        request.record(
+            "patch_team",
+            |_| team_entity(store.as_ref(), &name),
+            || patch_team(&name, content_type, body.clone(), request.header("If-Match"), store.as_ref()),
+        )?;
+        Ok(ApiResponse::message("Team updated"))
+    });
 
     // Ruta para eliminar una escudería
-    let data_shared_clone = Arc::clone(&data_shared);
-    let cookies_clone = Arc::clone(&cookies);
-    let cookie_counter_clone = Arc::clone(&cookie_counter);
-    server.add_route(
-        "DELETE",
-        "/api/escuderias/:name",
-        move |stream: &mut TcpStream, request: HashMap<String, Value>| {
-            clean_expired_cookies(&cookies_clone, 60); // Limpiar cookies expiradas
-            let mut data = data_shared_clone.write().unwrap();
-            let cookie_value = handle_cookie(&request, &cookies_clone, &cookie_counter_clone);
-
-            let pathAux = request.get("path").and_then(Value::as_str).unwrap_or("");
-            let path = &pathAux.replace("%20", " ");
-            let path_parts: Vec<&str> = path.split("/").collect();
-            let team_name = path_parts[3];
-            
-            let mut response = String::new();
-            match delete_team(team_name, &mut data) {
-                Ok(_) => {
-                    response = format!(
-                        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nSet-Cookie: session={}; Max-Age=60; HttpOnly\r\n\r\n{{\"message\": \"Team deleted\"}}",
-                        cookie_value
-                    );
-                }
-                Err(e) => {
-                    response = format!("HTTP/1.1 400 Bad Request\r\nContent-Type: application/json\r\nSet-Cookie: session={}; Max-Age=60; HttpOnly\r\n\r\n{{\"error\": \"{}\"}}", cookie_value, e);
-                }
-            }
+    routes.team_route("DELETE", "/escuderias/:name", "400 Bad Request", |request: &ApiRequest| {
+        let team_name = request.param("name");
+        let store = request.store()?;
+        request.record(
+            "delete_team",
+            |_| team_entity(store.as_ref(), &team_name),
+            || delete_team(&team_name, request.header("If-Match"), store.as_ref()),
+        )?;
+        Ok(ApiResponse::message("Team deleted"))
+    });
 
-            stream.write(response.as_bytes()).unwrap();
-            stream.flush().unwrap();
-        },
-    );
+    // Ruta para restaurar una escudería eliminada
+    routes.dataset_route("POST", "/escuderias/:name/restore", "400 Bad Request", |request: &ApiRequest| {
+        let team_name = request.param("name");
+        let store = request.store()?;
+        let team = request.record("restore_team", returned_team_entity, || restore_team(&team_name, store.as_ref()))?;
+        Ok(ApiResponse::ok(json!({ "message": "Team restored", "team": team })))
+    });
 
     // Ruta para actualizar un conductor (PATCH)
-    let data_shared_clone = Arc::clone(&data_shared);
-    let cookies_clone = Arc::clone(&cookies);
-    let cookie_counter_clone = Arc::clone(&cookie_counter);
-    server.add_route(
-        "PATCH",
-        "/api/escuderias/:team_name/pilotos/:driver_name",
-        move |stream: &mut TcpStream, request: HashMap<String, Value>| {
-            clean_expired_cookies(&cookies_clone, 60); // Limpiar cookies expiradas
-            let mut data = data_shared_clone.write().unwrap();
-            let cookie_value = handle_cookie(&request, &cookies_clone, &cookie_counter_clone);
-
-            let pathAux = request.get("path").and_then(Value::as_str).unwrap_or("");
-            let path = &pathAux.replace("%20", " ");
-            let path_parts: Vec<&str> = path.split("/").collect();
-            let team_name = path_parts[3];
-            let driver_name = path_parts[5];
-            
-
-            let mut response = String::new();
-            if let Some(body) = request.get("body") {
-                println!("body {}", body);
-                match patch_driver(team_name, driver_name, body.clone(), &mut data) {
-                    Ok(_) => {
-                        response = format!(
-                            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nSet-Cookie: session={}; Max-Age=60; HttpOnly\r\n\r\n{{\"message\": \"Driver updated\"}}",
-                            cookie_value
-                        );
-                    }
-                    Err(e) => {
-                        response = format!("HTTP/1.1 404 Not Found\r\nContent-Type: application/json\r\nSet-Cookie: session={}; Max-Age=60; HttpOnly\r\n\r\n{{\"error\": \"{}\"}}", cookie_value, e);
-                    }
-                }
-            } else {
-                response = format!("HTTP/1.1 400 Bad Request\r\nContent-Type: application/json\r\nSet-Cookie: session={}; Max-Age=60; HttpOnly\r\n\r\n{{\"error\": \"Invalid request body\"}}", cookie_value);
-            }
+    routes.team_route("PATCH", "/escuderias/:team_name/pilotos/:driver_name", "404 Not Found", |request: &ApiRequest| {
+        let team_name = request.param("team_name");
+        let driver_name = request.param("driver_name");
+        let body = request.body()?;
+        println!("body {}", body);
+        let store = request.store()?;
+        request.record(
+            "patch_driver",
+            |_| driver_entity(store.as_ref(), &team_name, &driver_name),
+            || patch_driver(&team_name, &driver_name, body.clone(), store.as_ref()),
+        )?;
+        Ok(ApiResponse::message("Driver updated"))
+    });
 
-            stream.write(response.as_bytes()).unwrap();
-            stream.flush().unwrap();
-        },
-    );
+    // Ruta para obtener los pilotos de una escudería
+    routes.team_route("GET", "/escuderias/:team_name/pilotos", "404 Not Found", |request: &ApiRequest| {
+        let drivers = get_drivers(&request.param("team_name"), request.store()?.as_ref())?;
+        Ok(ApiResponse::ok(json!({ "drivers": drivers })))
+    });
+
+    // Ruta para agregar un piloto a una escudería
+    routes.team_route("POST", "/escuderias/:team_name/pilotos", "400 Bad Request", |request: &ApiRequest| {
+        let team_name = request.param("team_name");
+        let body = request.body()?;
+        let store = request.store()?;
+        let driver = request.record(
+            "post_driver",
+            |driver: Option<&Driver>| match driver {
+                Some(driver) => driver_entity(store.as_ref(), &team_name, &driver.id),
+                None => Ok(None),
+            },
+            || post_driver(&team_name, body.clone(), store.as_ref()),
+        )?;
+        Ok(ApiResponse::created(
+            &format!("{}/{}", request.path(), percent_encode(&driver.id)),
+            json!({ "message": "Driver added", "id": driver.id }),
+        ))
+    });
+
+    // Ruta para obtener un piloto
+    routes.team_route("GET", "/escuderias/:team_name/pilotos/:driver_name", "404 Not Found", |request: &ApiRequest| {
+        let driver = get_driver(&request.param("team_name"), &request.param("driver_name"), request.store()?.as_ref())?;
+        Ok(ApiResponse::ok(json!({ "driver": driver })))
+    });
+
+    // Ruta para reemplazar un piloto (PUT)
+    routes.team_route("PUT", "/escuderias/:team_name/pilotos/:driver_name", "400 Bad Request", |request: &ApiRequest| {
+        let team_name = request.param("team_name");
+        let driver_name = request.param("driver_name");
+        let body = request.body()?;
+        let store = request.store()?;
+        request.record(
+            "put_driver",
+            |_| driver_entity(store.as_ref(), &team_name, &driver_name),
+            || put_driver(&team_name, &driver_name, body.clone(), store.as_ref()),
+        )?;
+        Ok(ApiResponse::message("Driver updated"))
+    });
+
+    // Ruta para eliminar un piloto
+    routes.team_route("DELETE", "/escuderias/:team_name/pilotos/:driver_name", "400 Bad Request", |request: &ApiRequest| {
+        let team_name = request.param("team_name");
+        let driver_name = request.param("driver_name");
+        let store = request.store()?;
+        request.record(
+            "delete_driver",
+            |_| driver_entity(store.as_ref(), &team_name, &driver_name),
+            || delete_driver(&team_name, &driver_name, store.as_ref()),
+        )?;
+        Ok(ApiResponse::message("Driver deleted"))
+    });
+
+    // Ruta para traspasar un piloto a otra escudería ({"to": "...", "from": "..."})
+    routes.dataset_route("POST", "/pilotos/:driver_name/transfer", "400 Bad Request", |request: &ApiRequest| {
+        let driver_name = request.param("driver_name");
+        let body = request.body()?;
+        let store = request.store()?;
+        // Se registra el cambio de las dos escuderías: la que deja y a la que llega
+        let transfer = request.record_all(|| transfer_driver(&driver_name, body.clone(), store.as_ref()))?;
+        Ok(ApiResponse::ok(json!({ "message": "Driver transferred", "transfer": transfer })))
+    });
+
+    // Ruta para aplicar varias operaciones de una vez: se aplican todas o ninguna
+    routes.dataset_route("POST", "/batch", "400 Bad Request", |request: &ApiRequest| {
+        let body = request.body()?;
+        let store = request.store()?;
+        let operation_count = body.get("operations").and_then(Value::as_array).map_or(0, Vec::len);
+        // Cada operación aplicada queda registrada en la auditoría
+        match request.record_all(|| apply_batch(body.clone(), store.as_ref())) {
+            Ok(results) => Ok(ApiResponse::ok(json!({ "results": results }))),
+            Err(e) => match e.downcast_ref::<BatchError>() {
+                Some(batch_error) => Ok(ApiResponse::batch_error(batch_error, operation_count)),
+                None => Err(e),
+            },
+        }
+    });
+
+    // Ruta para buscar escuderías y pilotos por nombre o nacionalidad (?q=hamil&limit=20)
+    routes.dataset_route("GET", "/search", "400 Bad Request", |request: &ApiRequest| {
+        let query = request.query("q").unwrap_or("");
+        let limit = request.query("limit").and_then(|limit| limit.parse().ok()).unwrap_or(20);
+        let store = request.datasets().indexed(&request.dataset())?;
+        let results = search(query, limit, &store)?;
+        Ok(ApiResponse::ok(json!({ "query": query, "results": results })))
+    });
+
+    // Ruta para consultar la auditoría de los cambios (?entity=escuderias/ferrari&since=2024-10-19)
+    routes.dataset_route("GET", "/audit", "400 Bad Request", |request: &ApiRequest| {
+        request.store()?;
+        let query = AuditQuery::parse(&request.query_map())?;
+        let entries = request.audit().query(&AuditQuery { dataset: Some(request.dataset()), ..query })?;
+        Ok(ApiResponse::ok(json!({ "entries": entries })))
+    });
+
+    // Ruta para exportar las escuderías (?format=json|csv|ndjson|yaml)
+    routes.raw_dataset_route("GET", "/export", "400 Bad Request", |request: &ApiRequest| {
+        let format = DataFormat::parse(request.query("format").unwrap_or("json"))?;
+        let content = export_teams(format, request.store()?.as_ref())?;
+        let disposition = format!("attachment; filename=\"{}.{}\"", request.dataset(), format.name());
+        Ok(ApiResponse::content("200 OK", format.content_type(), content).header("Content-Disposition", &disposition))
+    });
+
+    // Ruta para importar escuderías en cualquiera de los formatos de exportación
+    // (?format=...&mode=merge|replace&dry_run=true)
+    routes.dataset_route("POST", "/import", "400 Bad Request", |request: &ApiRequest| {
+        let text = request.text()?;
+        let (format, mode, dry_run) = import_options(request)?;
+        let store = request.store()?;
+        // Cada escudería que cambió queda registrada en la auditoría
+        let report = request.record_all(|| import_teams(&text, format, mode, dry_run, store.as_ref()))?;
+        Ok(ApiResponse::ok(json!(report)))
+    });
+
+    // Ruta para listar las temporadas con sus carreras y resultados
+    routes.dataset_route("GET", "/seasons", "400 Bad Request", |request: &ApiRequest| {
+        Ok(ApiResponse::ok(json!({ "seasons": get_seasons(request.store()?.as_ref())? })))
+    });
+
+    // Ruta para obtener una temporada
+    routes.dataset_route("GET", "/seasons/:year", "400 Bad Request", |request: &ApiRequest| {
+        let season = get_season(request.number("year")?, request.store()?.as_ref())?;
+        Ok(ApiResponse::ok(json!(season)))
+    });
+
+    // Ruta para crear o reemplazar una temporada completa
+    routes.dataset_route("PUT", "/seasons/:year", "400 Bad Request", |request: &ApiRequest| {
+        let body = request.body()?;
+        let (season, created) = put_season(request.number("year")?, body.clone(), request.store()?.as_ref())?;
+        let status = if created { "201 Created" } else { "200 OK" };
+        Ok(ApiResponse::json(status, json!({ "message": "Season saved", "season": season })))
+    });
+
+    // Ruta para eliminar una temporada
+    routes.dataset_route("DELETE", "/seasons/:year", "400 Bad Request", |request: &ApiRequest| {
+        delete_season(request.number("year")?, request.store()?.as_ref())?;
+        Ok(ApiResponse::message("Season deleted"))
+    });
+
+    // Ruta para obtener una carrera de una temporada
+    routes.dataset_route("GET", "/seasons/:year/races/:round", "400 Bad Request", |request: &ApiRequest| {
+        let race = get_race(request.number("year")?, request.number("round")?, request.store()?.as_ref())?;
+        Ok(ApiResponse::ok(json!(race)))
+    });
+
+    // Ruta para crear o reemplazar una carrera (con sus resultados) de una temporada existente
+    routes.dataset_route("PUT", "/seasons/:year/races/:round", "400 Bad Request", |request: &ApiRequest| {
+        let body = request.body()?;
+        let (year, round) = (request.number("year")?, request.number("round")?);
+        let (race, created) = put_race(year, round, body.clone(), request.store()?.as_ref())?;
+        let status = if created { "201 Created" } else { "200 OK" };
+        Ok(ApiResponse::json(status, json!({ "message": "Race saved", "race": race })))
+    });
+
+    // Ruta para eliminar una carrera
+    routes.dataset_route("DELETE", "/seasons/:year/races/:round", "400 Bad Request", |request: &ApiRequest| {
+        delete_race(request.number("year")?, request.number("round")?, request.store()?.as_ref())?;
+        Ok(ApiResponse::message("Race deleted"))
+    });
+
+    // Ruta para el campeonato de pilotos de una temporada, calculado con los resultados
+    routes.dataset_route("GET", "/seasons/:year/standings/drivers", "400 Bad Request", |request: &ApiRequest| {
+        let standings = get_driver_standings(request.number("year")?, request.store()?.as_ref())?;
+        Ok(ApiResponse::ok(json!({ "standings": standings })))
+    });
+
+    // Ruta para el campeonato de constructores de una temporada
+    routes.dataset_route("GET", "/seasons/:year/standings/constructors", "400 Bad Request", |request: &ApiRequest| {
+        let standings = get_constructor_standings(request.number("year")?, request.store()?.as_ref())?;
+        Ok(ApiResponse::ok(json!({ "standings": standings })))
+    });
+
+    // Ruta para registrar un webhook que recibe los cambios de las escuderías
+    let webhooks_clone = Arc::clone(&webhooks);
+    routes.dataset_route("POST", "/webhooks", "400 Bad Request", move |request: &ApiRequest| {
+        let body = request.body()?;
+        request.store()?;
+        let webhook = webhooks_clone.register(&request.dataset(), body)?;
+        // La clave solo se muestra aquí
+        Ok(ApiResponse::created(
+            &format!("{}/{}", request.path(), webhook.id),
+            json!({ "message": "Webhook registered", "webhook": webhook }),
+        ))
+    });
+
+    // Ruta para listar los webhooks (sin sus claves)
+    let webhooks_clone = Arc::clone(&webhooks);
+    routes.dataset_route("GET", "/webhooks", "400 Bad Request", move |request: &ApiRequest| {
+        request.store()?;
+        Ok(ApiResponse::ok(json!({ "webhooks": webhooks_clone.list(&request.dataset()) })))
+    });
+
+    // Ruta para eliminar un webhook
+    let webhooks_clone = Arc::clone(&webhooks);
+    routes.dataset_route("DELETE", "/webhooks/:id", "400 Bad Request", move |request: &ApiRequest| {
+        webhooks_clone.delete(&request.dataset(), &request.param("id"))?;
+        Ok(ApiResponse::message("Webhook deleted"))
+    });
+
+    // Ruta para listar las entregas que agotaron sus intentos
+    let webhooks_clone = Arc::clone(&webhooks);
+    routes.dataset_route("GET", "/webhooks/dead-letters", "400 Bad Request", move |request: &ApiRequest| {
+        request.store()?;
+        Ok(ApiResponse::ok(json!({ "dead_letters": webhooks_clone.dead_letters(&request.dataset()) })))
+    });
+
+    // Ruta para reintentar una entrega fallida
+    let webhooks_clone = Arc::clone(&webhooks);
+    routes.dataset_route("POST", "/webhooks/dead-letters/:id/retry", "400 Bad Request", move |request: &ApiRequest| {
+        let id = request.param("id").parse().map_err(|_| "Invalid delivery id")?;
+        let delivery = webhooks_clone.retry(&request.dataset(), id)?;
+        Ok(ApiResponse::ok(json!({ "message": "Delivery queued", "delivery": delivery })))
+    });
+
+    // Ruta para listar las copias de los datos
+    let snapshots_clone = Arc::clone(&snapshots);
+    routes.route("GET", "/api/admin/snapshots", "500 Internal Server Error", move |_: &ApiRequest| {
+        Ok(ApiResponse::ok(json!({ "snapshots": snapshots_clone.list()? })))
+    });
+
+    // Ruta para guardar una copia de los datos en este momento
+    let snapshots_clone = Arc::clone(&snapshots);
+    routes.route("POST", "/api/admin/snapshots", "500 Internal Server Error", move |request: &ApiRequest| {
+        let snapshot = snapshots_clone.take(request.store()?.as_ref())?;
+        Ok(ApiResponse::json("201 Created", json!({ "snapshot": snapshot })))
+    });
+
+    // Ruta para restaurar una copia mientras el servidor está en ejecución
+    let snapshots_clone = Arc::clone(&snapshots);
+    routes.route("POST", "/api/admin/snapshots/:id/restore", "500 Internal Server Error", move |request: &ApiRequest| {
+        let id = request.param("id");
+        let backup = snapshots_clone.restore(&id, request.store()?.as_ref())?;
+        Ok(ApiResponse::ok(json!({ "message": "Snapshot restored", "restored": id, "backup": backup })))
+    });
 
     // Hilo para imprimir cookies cada cierto tiempo
     let cookies_clone = Arc::clone(&cookies);
@@ -331,6 +599,23 @@ mod tests {
     use std::sync::{Arc, RwLock};
     use std::sync::atomic::AtomicUsize;
     use std::time::{SystemTime, UNIX_EPOCH};
+    use http_server_proyecto1_so::json_hashmaps::audit::Actor;
+    use http_server_proyecto1_so::json_hashmaps::datasets::DEFAULT_DATASET;
+    use http_server_proyecto1_so::json_hashmaps::f1_data_hashmap::read_f1_data;
+    use http_server_proyecto1_so::json_hashmaps::models::{Team, ValidationError, MAX_DRIVERS_PER_TEAM};
+    use http_server_proyecto1_so::json_hashmaps::patch::UnsupportedMediaType;
+    use http_server_proyecto1_so::json_hashmaps::seasons::SeasonNotFound;
+    use http_server_proyecto1_so::json_hashmaps::snapshots::SnapshotNotFound;
+    use http_server_proyecto1_so::json_hashmaps::team_store::{ConflictError, MemoryStore, Mutation, PreconditionFailed, StorageError};
+    use http_server_proyecto1_so::json_hashmaps::webhooks::WebhookNotFound;
+    use http_server_proyecto1_so::http_functions::handlers::header;
+    use http_server_proyecto1_so::http_functions::query::Projection;
+    use http_server_proyecto1_so::server_http::negotiation::{render, Representation};
+    use http_server_proyecto1_so::utils::percent_decode;
+    use http_server_proyecto1_so::json_hashmaps::search_index::IndexedStore;
+    use http_server_proyecto1_so::json_hashmaps::patch::TeamPatch;
+    use http_server_proyecto1_so::json_hashmaps::snapshots::SnapshotManager;
+    use http_server_proyecto1_so::json_hashmaps::migrations::{migrate, schema_version, CURRENT_SCHEMA_VERSION};
 
     // Test para verificar si se genera una nueva cookie cuando no hay cookies en la solicitud
     #[test]
@@ -398,14 +683,27 @@ mod tests {
 
     #[test]
     fn test_get() {
-        let f1_data = get_f1_data().unwrap();
+        let f1_data = read_f1_data(&Config::load(&[]).unwrap().data_path).unwrap();
         assert!(!f1_data.is_empty(), "Los datos de F1 no deberían estar vacíos");
     }
 
+    // Escuderías de data/f1_data.json leídas desde una copia temporal: abrir el archivo con
+    // JsonFileStore puede reescribirlo (identificadores, diario) y las pruebas no deben modificarlo
+    fn data_file_teams(test: &str) -> Vec<Team> {
+        let dir = std::env::temp_dir().join(format!("f1_{}_test_{}", test, std::process::id()));
+        let path = dir.join("f1_data.json");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::copy(Config::load(&[]).unwrap().data_path, &path).unwrap();
+
+        let teams = JsonFileStore::open(&path).unwrap().list().unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        teams
+    }
+
     #[test]
     fn test_crud_operations_in_order() {
-        // Inicializar los datos compartidos
-        let data_shared = Arc::new(RwLock::new(get_f1_data().unwrap()));
+        // Inicializar los datos compartidos en memoria para no modificar data/f1_data.json
+        let store = MemoryStore::new(data_file_teams("crud"));
 
         // 1. Test Post: Añadir un equipo
         {
@@ -415,18 +713,17 @@ mod tests {
                     {
                         "name": "Nombre Apellido",
                         "age": 23,
-                        "nacionality": "British"
+                        "nationality": "British"
                     },
                     {
                         "name": "Nombre Apellido2",
                         "age": 22,
-                        "nacionality": "Australian"
+                        "nationality": "Australian"
                     }
                 ]
             });
 
-            let mut data = data_shared.write().unwrap();
-            let result = post_team(new_team, &mut data);
+            let result = post_team(new_team, &store);
             assert!(result.is_ok(), "El equipo debería añadirse correctamente");
         }
 
@@ -438,18 +735,17 @@ mod tests {
                     {
                         "name": "Max Verstappen5",
                         "age": 26,
-                        "nacionality": "Dutch5"
+                        "nationality": "Dutch5"
                     },
                     {
                         "name": "Sergio Perez5",
                         "age": 34,
-                        "nacionality": "Mexican5"
+                        "nationality": "Mexican5"
                     }
                 ]
             });
 
-            let mut data = data_shared.write().unwrap();
-            let result = put_team("Example Team", updated_team, &mut data);
+            let result = put_team("Example Team", updated_team, None, &store);
             assert!(result.is_ok(), "El equipo debería actualizarse correctamente");
         }
 
@@ -457,19 +753,1495 @@ mod tests {
         {
             let updated_driver = json!({
                 "age": 35,
-                "nacionality": "British"
+                "nationality": "British"
             });
 
-            let mut data = data_shared.write().unwrap();
-            let result = patch_driver("Mercedes", "Lewis Hamilton", updated_driver, &mut data);
+            let result = patch_driver("Mercedes", "Lewis Hamilton", updated_driver, &store);
             assert!(result.is_ok(), "Los datos del conductor deberían actualizarse correctamente");
         }
 
         // 4. Test Delete: Eliminar el equipo añadido
         {
-            let mut data = data_shared.write().unwrap();
-            let result = delete_team("Example Team", &mut data);
+            let result = delete_team("Example Team", None, &store);
             assert!(result.is_ok(), "El equipo debería eliminarse correctamente");
         }
     }
-}
\ No newline at end of file
+    #[test]
+    fn test_driver_crud_operations_in_order() {
+        // Inicializar los datos compartidos en memoria para no modificar data/f1_data.json
+        let store = MemoryStore::new(data_file_teams("driver_crud"));
+        let team_name = store.list().unwrap()[0].name.clone();
+        let drivers_before = get_drivers(&team_name, &store).unwrap().len();
+
+        // 1. Test Post: Añadir un piloto
+        {
+            let new_driver = json!({
+                "name": "Nombre Apellido",
+                "age": 23,
+                "nationality": "British"
+            });
+
+            let result = post_driver(&team_name, new_driver.clone(), &store);
+            assert!(result.is_ok(), "El piloto debería añadirse correctamente");
+
+            let result = post_driver(&team_name, new_driver, &store);
+            assert!(result.is_err(), "El piloto ya existe en la escudería");
+        }
+
+        // 2. Test Get: Obtener los pilotos y el piloto añadido
+        {
+            let drivers = get_drivers(&team_name, &store).unwrap();
+            assert_eq!(drivers.len(), drivers_before + 1);
+            assert_eq!(drivers.last().unwrap().name, "Nombre Apellido");
+
+            let driver = get_driver(&team_name, "Nombre Apellido", &store).unwrap();
+            assert_eq!(driver.age, 23);
+        }
+
+        // 3. Test Put: Reemplazar el piloto añadido
+        {
+            let updated_driver = json!({
+                "name": "Nombre Apellido2",
+                "age": 24,
+                "nationality": "Australian"
+            });
+
+            let result = put_driver(&team_name, "Nombre Apellido", updated_driver, &store);
+            assert!(result.is_ok(), "El piloto debería actualizarse correctamente");
+            assert!(get_driver(&team_name, "Nombre Apellido", &store).is_err());
+            assert_eq!(get_driver(&team_name, "Nombre Apellido2", &store).unwrap().nationality, "Australian");
+        }
+
+        // 4. Test Delete: Eliminar el piloto añadido
+        {
+            let result = delete_driver(&team_name, "Nombre Apellido2", &store);
+            assert!(result.is_ok(), "El piloto debería eliminarse correctamente");
+            assert_eq!(get_drivers(&team_name, &store).unwrap().len(), drivers_before);
+
+            let result = delete_driver(&team_name, "Nombre Apellido2", &store);
+            assert!(result.is_err(), "El piloto ya no existe");
+        }
+
+        // Una escudería no puede superar el máximo de pilotos
+        {
+            let mut result = Ok(());
+            for i in 0..=MAX_DRIVERS_PER_TEAM {
+                let driver = json!({ "name": format!("Piloto {}", i), "age": 25, "nationality": "Italian" });
+                result = post_driver(&team_name, driver, &store).map(|_| ());
+                if result.is_err() {
+                    break;
+                }
+            }
+            let error = result.unwrap_err();
+            let validation = error.downcast_ref::<ValidationError>().expect("Debería ser un error de validación");
+            assert_eq!(validation.errors[0].field, "drivers");
+        }
+    }
+    // Test para verificar que las reglas del modelo reportan cada campo inválido
+    #[test]
+    fn test_team_validation_errors() {
+        let invalid_team = json!({
+            "name": " ",
+            "drivers": [
+                { "name": "Piloto Joven", "age": 12, "nationality": "British" },
+                { "name": "", "age": 30, "nationality": "" }
+            ]
+        });
+
+        let error = Team::from_value(invalid_team).unwrap_err();
+        let fields: Vec<&str> = error.errors.iter().map(|e| e.field.as_str()).collect();
+        assert_eq!(fields, vec!["name", "drivers[0].age", "drivers[1].name", "drivers[1].nationality"]);
+
+        // Tipos incorrectos se reportan con la ruta del campo
+        let error = Team::from_value(json!({ "name": "Equipo", "drivers": "Max" })).unwrap_err();
+        assert_eq!(error.errors[0].field, "drivers");
+
+        let error = Team::from_value(json!({ "drivers": [] })).unwrap_err();
+        assert_eq!(error.errors[0].field, "body");
+    }
+
+    // Test para verificar que post_team y patch_driver rechazan datos inválidos sin modificar nada
+    #[test]
+    fn test_invalid_mutations_are_rejected() {
+        let team = Team::from_value(json!({
+            "name": "Equipo",
+            "drivers": [{ "name": "Piloto", "age": 30, "nationality": "British" }]
+        })).unwrap();
+        let store = MemoryStore::new(vec![team]);
+
+        let too_many_drivers: Vec<Value> = (0..=MAX_DRIVERS_PER_TEAM)
+            .map(|i| json!({ "name": format!("Piloto {}", i), "age": 25, "nationality": "Italian" }))
+            .collect();
+        let result = post_team(json!({ "name": "Otro", "drivers": too_many_drivers }), &store);
+        let error = result.unwrap_err();
+        let validation = error.downcast_ref::<ValidationError>().expect("Debería ser un error de validación");
+        assert_eq!(validation.errors[0].field, "drivers");
+
+        let result = patch_driver("Equipo", "Piloto", json!({ "age": "treinta" }), &store);
+        let error = result.unwrap_err();
+        let validation = error.downcast_ref::<ValidationError>().expect("Debería ser un error de validación");
+        assert_eq!(validation.errors[0].field, "drivers[0].age");
+
+        let teams = store.list().unwrap();
+        assert_eq!(teams.len(), 1);
+        assert_eq!(teams[0].drivers[0].age, 30);
+    }
+    // Test para verificar que un documento sin versión se migra al esquema actual
+    #[test]
+    fn test_migrate_old_document() {
+        let mut data: HashMap<String, Value> = HashMap::new();
+        data.insert("teams".to_string(), json!([
+            {
+                "name": "Equipo",
+                "drivers": [
+                    { "name": "Piloto 1", "age": 30, "nacionality": "British" },
+                    { "name": "Piloto 2", "age": 25, "nationality": "Italian" }
+                ]
+            }
+        ]));
+
+        assert_eq!(schema_version(&data), 0);
+        assert!(migrate(&mut data).unwrap(), "El documento debería migrarse");
+        assert_eq!(schema_version(&data), CURRENT_SCHEMA_VERSION);
+
+        let drivers = data["teams"][0]["drivers"].as_array().unwrap();
+        assert_eq!(drivers[0]["nationality"], "British");
+        assert!(drivers[0].get("nacionality").is_none());
+        assert_eq!(drivers[1]["nationality"], "Italian");
+
+        // Un documento ya migrado no vuelve a cambiar
+        assert!(!migrate(&mut data).unwrap());
+
+        // Un documento de una versión futura no se puede cargar
+        data.insert("schema_version".to_string(), json!(CURRENT_SCHEMA_VERSION + 1));
+        assert!(migrate(&mut data).is_err());
+    }
+    // Test para verificar que JsonFileStore guarda los cambios y los conserva al reabrir el archivo
+    #[test]
+    fn test_json_file_store_persists_changes() {
+        let dir = std::env::temp_dir().join(format!("f1_store_test_{}", std::process::id()));
+        let path = dir.join("f1_data.json");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(&path, r#"{ "teams": [] }"#).unwrap();
+
+        let store = JsonFileStore::open(&path).unwrap();
+        post_team(json!({
+            "name": "Equipo",
+            "drivers": [{ "name": "Piloto", "age": 30, "nationality": "British" }]
+        }), &store).unwrap();
+        patch_driver("Equipo", "Piloto", json!({ "age": 31 }), &store).unwrap();
+
+        // Un cambio inválido no debe llegar al archivo
+        assert!(patch_driver("Equipo", "Piloto", json!({ "age": 5 }), &store).is_err());
+
+        let reopened = JsonFileStore::open(&path).unwrap();
+        let team = reopened.get("Equipo").unwrap().expect("El equipo debería estar guardado");
+        assert_eq!(team.drivers[0].age, 31);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+    // Test para verificar la importación desde JSON y los cambios por fila en SQLite
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_sqlite_store_imports_json() {
+        use http_server_proyecto1_so::json_hashmaps::sqlite_store::SqliteStore;
+
+        let dir = std::env::temp_dir().join(format!("f1_sqlite_test_{}", std::process::id()));
+        let json_path = dir.join("f1_data.json");
+        let db_path = dir.join("f1_data.db");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(&json_path, r#"{ "teams": [
+            { "name": "Mercedes", "drivers": [
+                { "name": "Lewis Hamilton", "age": 38, "nacionality": "British" },
+                { "name": "George Russell", "age": 25, "nationality": "British" }
+            ] }
+        ] }"#).unwrap();
+
+        let store = SqliteStore::open(&db_path).unwrap();
+        assert!(store.import_json_once(&json_path).unwrap());
+        assert!(!store.import_json_once(&json_path).unwrap(), "La importación solo ocurre una vez");
+
+        patch_driver("Mercedes", "Lewis Hamilton", json!({ "age": 39 }), &store).unwrap();
+        post_team(json!({
+            "name": "Ferrari",
+            "drivers": [{ "name": "Charles Leclerc", "age": 25, "nationality": "Monacan" }]
+        }), &store).unwrap();
+        assert!(post_team(json!({ "name": "Ferrari", "drivers": [] }), &store).is_err());
+        delete_team("Ferrari", None, &store).unwrap();
+
+        // Los pilotos se agregan y eliminan por fila, conservando el orden
+        post_driver("Mercedes", json!({ "name": "Kimi Antonelli", "age": 18, "nationality": "Italian" }), &store).unwrap();
+        delete_driver("Mercedes", "Lewis Hamilton", &store).unwrap();
+        put_driver("Mercedes", "George Russell", json!({ "name": "George Russell", "age": 26, "nationality": "British" }), &store).unwrap();
+        let operations = json!([{ "op": "test", "path": "/drivers/0/age", "value": 26 }, { "op": "add", "path": "/base", "value": "Brackley" }]);
+        patch_team("Mercedes", "application/json-patch+json", operations, None, &store).unwrap();
+        // Un lote que falla se deshace completo con la transacción (George Russell sigue abajo)
+        let batch = json!({ "operations": [
+            { "method": "DELETE", "path": "/escuderias/Mercedes/pilotos/George Russell" },
+            { "method": "DELETE", "path": "/escuderias/Ferrari" }
+        ]});
+        assert!(apply_batch(batch, &store).unwrap_err().is::<BatchError>());
+        // El cambio de cada operación se lee dentro de la transacción
+        let batch = json!({ "operations": [{ "method": "PATCH", "path": "/escuderias/Mercedes/pilotos/George Russell", "body": { "age": 26 } }] });
+        let (_, changes) = apply_batch(batch, &store).unwrap();
+        assert_eq!(changes[0].before.as_ref().unwrap().0, "escuderias/mercedes/pilotos/george-russell");
+        // Los traspasos se guardan con el piloto
+        post_team(json!({ "name": "Ferrari", "drivers": [] }), &store).unwrap();
+        transfer_driver("Kimi Antonelli", json!({ "to": "Ferrari" }), &store).unwrap();
+        transfer_driver("Kimi Antonelli", json!({ "to": "Mercedes" }), &store).unwrap();
+        delete_team("Ferrari", None, &store).unwrap();
+        drop(store);
+
+        let reopened = SqliteStore::open(&db_path).unwrap();
+        let teams = reopened.list().unwrap();
+        assert_eq!(teams.len(), 1);
+        let drivers: Vec<(&str, u32)> = teams[0].drivers.iter().map(|d| (d.name.as_str(), d.age)).collect();
+        assert_eq!(drivers, vec![("George Russell", 26), ("Kimi Antonelli", 18)]);
+        assert_eq!(teams[0].extra["base"], "Brackley");
+        let transfers: Vec<&str> = teams[0].drivers[1].transfers.iter().map(|t| t.to.as_str()).collect();
+        assert_eq!(transfers, vec!["Ferrari", "Mercedes"]);
+        // Los identificadores se guardan y se pueden usar en lugar de los nombres
+        assert_eq!((teams[0].id.as_str(), teams[0].drivers[1].id.as_str()), ("mercedes", "kimi-antonelli"));
+        assert_eq!(get_driver("mercedes", "kimi-antonelli", &reopened).unwrap().age, 18);
+        put_team("mercedes", json!({ "name": "Mercedes-AMG", "drivers": [] }), None, &reopened).unwrap();
+        assert_eq!(get_team("mercedes", &reopened).unwrap().name, "Mercedes-AMG");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+    // Test para verificar que al abrir el archivo se reaplican los cambios pendientes del journal
+    #[test]
+    fn test_journal_replay_on_open() {
+        let dir = std::env::temp_dir().join(format!("f1_journal_test_{}", std::process::id()));
+        let path = dir.join("f1_data.json");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(&path, r#"{ "schema_version": 1, "journal_seq": 1, "teams": [] }"#).unwrap();
+
+        // La entrada 1 ya está en el archivo, la 2 no llegó a guardarse y la última línea quedó a medias
+        let team = json!({ "name": "Equipo", "drivers": [{ "name": "Piloto", "age": 30, "nationality": "British" }] });
+        let journal = format!(
+            "{}\n{}\n{}",
+            json!({ "seq": 1, "mutation": { "op": "insert", "team": team } }),
+            json!({ "seq": 2, "mutation": { "op": "insert", "team": team } }),
+            r#"{"seq": 3, "mutation": {"op": "dele"#
+        );
+        std::fs::write(dir.join("f1_data.journal"), journal).unwrap();
+
+        let store = JsonFileStore::open(&path).unwrap();
+        assert_eq!(store.list().unwrap().len(), 1, "Solo la entrada 2 debería reaplicarse");
+        assert_eq!(std::fs::read_to_string(dir.join("f1_data.journal")).unwrap(), "");
+
+        let saved: Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(saved["journal_seq"], 2);
+        assert_eq!(saved["teams"][0]["name"], "Equipo");
+
+        // Una entrada que no se puede reaplicar impide abrir y deja los dos archivos como estaban
+        let data = std::fs::read_to_string(&path).unwrap();
+        let journal = format!("{}\n", json!({ "seq": 3, "mutation": { "op": "delete", "team_name": "Otro" } }));
+        std::fs::write(dir.join("f1_data.journal"), &journal).unwrap();
+        let error = JsonFileStore::open(&path).err().unwrap();
+        assert!(error.to_string().contains("entrada 3 del journal"), "{}", error);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), data);
+        assert_eq!(std::fs::read_to_string(dir.join("f1_data.journal")).unwrap(), journal);
+
+        // Los cambios de temporadas también pasan por el journal y se reaplican
+        let seasons = json!([{ "year": 2024, "races": [] }]);
+        let journal = format!("{}\n", json!({ "seq": 3, "mutation": { "op": "replace_seasons", "seasons": seasons } }));
+        std::fs::write(dir.join("f1_data.journal"), &journal).unwrap();
+        let store = JsonFileStore::open(&path).unwrap();
+        assert_eq!(store.seasons().unwrap()[0].year, 2024);
+        assert_eq!(store.list().unwrap().len(), 1);
+        store.update_seasons(&mut |seasons| {
+            seasons[0].year = 2025;
+            Ok(())
+        }).unwrap();
+        let saved: Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!((&saved["journal_seq"], &saved["seasons"][0]["year"]), (&json!(4), &json!(2025)));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    // Test para verificar que un fallo al escribir se reporta como error de almacenamiento
+    #[test]
+    fn test_storage_failure_is_reported() {
+        let dir = std::env::temp_dir().join(format!("f1_failure_test_{}", std::process::id()));
+        let path = dir.join("f1_data.json");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(&path, r#"{ "teams": [] }"#).unwrap();
+        let store = JsonFileStore::open(&path).unwrap();
+
+        // Reemplazar el archivo de datos por una carpeta hace que el rename falle
+        std::fs::remove_file(&path).unwrap();
+        std::fs::create_dir(&path).unwrap();
+
+        let result = post_team(json!({ "name": "Equipo", "drivers": [] }), &store);
+        let error = result.unwrap_err();
+        assert!(error.is::<StorageError>(), "Debería ser un error de almacenamiento: {}", error);
+        assert!(store.list().unwrap().is_empty(), "Los datos en memoria no deberían cambiar");
+        assert_eq!(std::fs::read_to_string(dir.join("f1_data.journal")).unwrap(), "");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+    // Test para verificar la retención de copias y la restauración de una copia anterior
+    #[test]
+    fn test_snapshots_retention_and_restore() {
+        let dir = std::env::temp_dir().join(format!("f1_snapshots_test_{}", std::process::id()));
+        let snapshots = SnapshotManager::new(&dir, 2);
+        let store = MemoryStore::default();
+
+        post_team(json!({ "name": "Equipo 1", "drivers": [] }), &store).unwrap();
+        let first = snapshots.take(&store).unwrap();
+        assert!(snapshots.take_if_changed(&store).unwrap().is_none(), "Sin cambios no se guarda otra copia");
+
+        post_team(json!({ "name": "Equipo 2", "drivers": [] }), &store).unwrap();
+        snapshots.take(&store).unwrap();
+        post_team(json!({ "name": "Equipo 3", "drivers": [] }), &store).unwrap();
+        let latest = snapshots.take(&store).unwrap();
+
+        // Solo se conservan las dos copias más recientes
+        let list = snapshots.list().unwrap();
+        assert_eq!(list.len(), 2);
+        assert_eq!(list[0].id, latest.id);
+        assert!(snapshots.load(&first.id).unwrap_err().is::<SnapshotNotFound>());
+        assert!(snapshots.load("../f1_data").unwrap_err().is::<SnapshotNotFound>());
+
+        let previous = list[1].id.clone();
+        let backup = snapshots.restore(&previous, &store).unwrap();
+        let names: Vec<String> = store.list().unwrap().into_iter().map(|t| t.name).collect();
+        assert_eq!(names, vec!["Equipo 1", "Equipo 2"]);
+        assert_eq!(snapshots.load(&backup.id).unwrap().teams.len(), 3, "La copia de respaldo tiene el estado anterior");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    // Test para verificar que los cambios hechos al archivo fuera del servidor se recargan
+    // y que una escritura sobre un archivo modificado se reporta como conflicto
+    #[test]
+    fn test_external_changes_are_reloaded() {
+        let dir = std::env::temp_dir().join(format!("f1_reload_test_{}", std::process::id()));
+        let path = dir.join("f1_data.json");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(&path, r#"{ "teams": [] }"#).unwrap();
+        let store = Arc::new(JsonFileStore::open(&path).unwrap());
+
+        // Una escritura con cambios sin recargar se rechaza, pero deja los datos al día
+        std::fs::write(&path, r#"{ "teams": [{ "name": "Externo", "drivers": [] }] }"#).unwrap();
+        let error = post_team(json!({ "name": "Equipo", "drivers": [] }), store.as_ref()).unwrap_err();
+        assert!(error.is::<ConflictError>(), "Debería ser un conflicto: {}", error);
+        post_team(json!({ "name": "Equipo", "drivers": [] }), store.as_ref()).unwrap();
+        let names: Vec<String> = store.list().unwrap().into_iter().map(|t| t.name).collect();
+        assert_eq!(names, vec!["Externo", "Equipo"]);
+
+        // Un archivo inválido no reemplaza los datos actuales ni se sobrescribe
+        std::fs::write(&path, r#"{ "teams": [{ "name": "", "drivers": [] }] }"#).unwrap();
+        assert!(store.reload().is_err());
+        let error = delete_team("Equipo", None, store.as_ref()).unwrap_err();
+        assert!(error.is::<ConflictError>(), "Debería ser un conflicto: {}", error);
+        assert_eq!(store.list().unwrap().len(), 2);
+
+        // El watcher recarga el archivo editado por otro programa
+        Arc::clone(&store).watch().unwrap();
+        std::fs::write(&path, r#"{ "teams": [{ "name": "Externo 2", "drivers": [] }] }"#).unwrap();
+        let mut reloaded = false;
+        for _ in 0..50 {
+            if store.get("Externo 2").unwrap().is_some() {
+                reloaded = true;
+                break;
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+        assert!(reloaded, "El watcher debería recargar el archivo");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    // Test para verificar el orden de prioridad de la configuración: línea de comandos,
+    // variables de entorno y archivo de configuración
+    #[test]
+    fn test_config_sources() {
+        let dir = std::env::temp_dir().join(format!("f1_config_test_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let config_path = dir.join("config.json");
+        std::fs::write(&config_path, r#"{
+            "data_path": "f1_data.json",
+            "datasets": { "2023": "f1_2023.json", "2024": "f1_2024.json" }
+        }"#).unwrap();
+
+        let args: Vec<String> = vec!["--config".into(), config_path.display().to_string()];
+        let config = Config::from_sources(&args, |_| None).unwrap();
+        assert_eq!(config.data_path, dir.join("f1_data.json"), "Las rutas parten de la carpeta del archivo");
+        assert_eq!(config.datasets["2023"], dir.join("f1_2023.json"));
+
+        let env = |name: &str| match name {
+            "F1_DATA_PATH" => Some("/tmp/env.json".to_string()),
+            "F1_DATASETS" => Some("2024=/tmp/env_2024.json".to_string()),
+            _ => None,
+        };
+        let args: Vec<String> = ["--config", &config_path.display().to_string(), "--dataset", "2023=/tmp/cli_2023.json"]
+            .iter().map(|a| a.to_string()).collect();
+        let config = Config::from_sources(&args, env).unwrap();
+        assert_eq!(config.data_path, Path::new("/tmp/env.json"));
+        assert_eq!(config.datasets["2023"], Path::new("/tmp/cli_2023.json"));
+        assert_eq!(config.datasets["2024"], Path::new("/tmp/env_2024.json"));
+
+        let args: Vec<String> = vec!["--dataset".into(), "../2023=f1_2023.json".into()];
+        assert!(Config::from_sources(&args, |_| None).is_err(), "El nombre del conjunto forma parte de la ruta");
+        assert!(Config::from_sources(&["--puerto".to_string()], |_| None).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    // Test para verificar la consulta de una escudería y la selección de campos con ?fields=
+    #[test]
+    fn test_get_team_and_projection() {
+        let team = Team::from_value(json!({
+            "name": "Equipo",
+            "color": "Rojo",
+            "drivers": [{ "name": "Piloto", "age": 30, "nationality": "British" }]
+        })).unwrap();
+        let store = MemoryStore::new(vec![team]);
+
+        let team = get_team("Equipo", &store).unwrap();
+        assert_eq!(team.extra["color"], "Rojo");
+        assert!(get_team("Otro", &store).is_err());
+
+        let projection = Projection::parse("name, drivers.name, drivers.edad").unwrap();
+        assert_eq!(
+            projection.apply(json!([team])),
+            json!([{ "name": "Equipo", "drivers": [{ "name": "Piloto" }] }])
+        );
+        assert_eq!(Projection::parse("drivers").unwrap().apply(json!({ "name": "Equipo", "drivers": [] })), json!({ "drivers": [] }));
+
+        let error = Projection::parse("name,,drivers.").unwrap_err();
+        assert!(error.is::<ValidationError>());
+
+        // Los parámetros de consulta se decodifican
+        let query = http_server_proyecto1_so::utils::parse_query_string("fields=name%2Cdrivers.name&q=Red+Bull&vacio");
+        assert_eq!(query["fields"], "name,drivers.name");
+        assert_eq!(query["q"], "Red Bull");
+        assert_eq!(query["vacio"], "");
+        assert_eq!(percent_decode("Se%C3%B1or%20Piloto%2", false), "Señor Piloto%2");
+    }
+
+    // Test para verificar los filtros, el orden y la paginación de la lista de escuderías
+    #[test]
+    fn test_list_filters_sort_and_pagination() {
+        let teams: Vec<Team> = [("Ferrari", "Monegasque"), ("McLaren", "British"), ("Mercedes", "British"), ("Williams", "Thai")]
+            .iter()
+            .map(|(name, nationality)| Team::from_value(json!({
+                "name": name,
+                "drivers": [{ "name": format!("Piloto {}", name), "age": 25, "nationality": nationality }]
+            })).unwrap())
+            .collect();
+        let store = MemoryStore::new(teams);
+        let query = |params: Value| ListQuery::parse(params.as_object().unwrap());
+
+        let page = list_teams(&store, &query(json!({ "driver.nationality": "british", "sort": "-name" })).unwrap()).unwrap();
+        let names: Vec<&str> = page.items.iter().map(|t| t["name"].as_str().unwrap()).collect();
+        assert_eq!(names, vec!["Mercedes", "McLaren"]);
+        assert_eq!(page.total, 2);
+        assert_eq!(page.next_cursor, None);
+
+        // Páginas de dos elementos; el cursor es opaco
+        let page = list_teams(&store, &query(json!({ "sort": "name", "limit": "2" })).unwrap()).unwrap();
+        assert_eq!(page.items[0]["name"], "Ferrari");
+        assert_eq!(page.total, 4);
+        assert_eq!(page.prev_cursor, None);
+        let next = page.next_cursor.expect("Debería haber página siguiente");
+
+        let page = list_teams(&store, &query(json!({ "sort": "name", "limit": "2", "cursor": next })).unwrap()).unwrap();
+        let names: Vec<&str> = page.items.iter().map(|t| t["name"].as_str().unwrap()).collect();
+        assert_eq!(names, vec!["Mercedes", "Williams"]);
+        assert_eq!(page.next_cursor, None);
+        let prev = page.prev_cursor.expect("Debería haber página anterior");
+
+        let page = list_teams(&store, &query(json!({ "sort": "name", "limit": "2", "cursor": prev })).unwrap()).unwrap();
+        let names: Vec<&str> = page.items.iter().map(|t| t["name"].as_str().unwrap()).collect();
+        assert_eq!(names, vec!["Ferrari", "McLaren"]);
+
+        // Las escuderías agregadas o eliminadas entre páginas no hacen saltar ni repetir elementos
+        store.insert(Team::from_value(json!({ "name": "Alpine", "drivers": [] })).unwrap()).unwrap();
+        store.delete("Ferrari").unwrap();
+        let page = list_teams(&store, &query(json!({ "sort": "name", "limit": "2", "cursor": next })).unwrap()).unwrap();
+        let names: Vec<&str> = page.items.iter().map(|t| t["name"].as_str().unwrap()).collect();
+        assert_eq!(names, vec!["Mercedes", "Williams"]);
+
+        // Un cursor de otro orden no se acepta
+        let error = query(json!({ "sort": "name,-id", "limit": "2", "cursor": next })).unwrap_err();
+        assert!(error.is::<ValidationError>());
+
+        // Filtrar por un campo que no existe es un error, no una lista vacía
+        let error = list_teams(&store, &query(json!({ "foo": "bar" })).unwrap()).unwrap_err();
+        let validation = error.downcast_ref::<ValidationError>().expect("Debería ser un error de validación");
+        assert_eq!(validation.errors[0].field, "foo");
+        assert!(list_teams(&store, &query(json!({ "driver.age": "25" })).unwrap()).is_ok());
+
+        // Parámetros inválidos se reportan con 422
+        let error = query(json!({ "limit": "0", "cursor": "abc", "sort": "-" })).unwrap_err();
+        let validation = error.downcast_ref::<ValidationError>().expect("Debería ser un error de validación");
+        let fields: Vec<&str> = validation.errors.iter().map(|e| e.field.as_str()).collect();
+        assert_eq!(fields, vec!["cursor", "limit", "sort"]);
+    }
+
+    #[test]
+    fn test_search_index() {
+        let team = |name: &str, drivers: &[(&str, &str)]| Team::from_value(json!({
+            "name": name,
+            "drivers": drivers.iter().map(|(driver, nationality)| json!({ "name": driver, "age": 30, "nationality": nationality })).collect::<Vec<_>>()
+        })).unwrap();
+        let inner = Arc::new(MemoryStore::new(vec![
+            team("Mercedes", &[("Lewis Hamilton", "British"), ("George Russell", "British")]),
+            team("Ferrari", &[("Charles Leclerc", "Monegasque")]),
+        ]));
+        let store = IndexedStore::new(inner.clone()).unwrap();
+        let found = |query: &str| -> Vec<(String, Option<String>)> {
+            search(query, 10, &store).unwrap().into_iter().map(|r| (r.team, r.driver)).collect()
+        };
+        let hamilton = ("Mercedes".to_string(), Some("Lewis Hamilton".to_string()));
+
+        // Prefijo, errores de escritura y nombres completos
+        assert_eq!(found("hamil"), vec![hamilton.clone()]);
+        assert_eq!(found("Hamiltn"), vec![hamilton.clone()]);
+        assert_eq!(found("lewis hamilton"), vec![hamilton.clone()]);
+        assert!(search(" ", 10, &store).unwrap_err().downcast_ref::<ValidationError>().is_some());
+
+        // El nombre pesa más que la nacionalidad y la coincidencia exacta más que el prefijo
+        let results = search("brit", 10, &store).unwrap();
+        assert_eq!(results.len(), 2);
+        let results = search("mercedes", 10, &store).unwrap();
+        assert_eq!((results[0].kind, results[0].team.as_str()), ("team", "Mercedes"));
+
+        // Cada cambio actualiza el índice
+        post_team(serde_json::to_value(team("Alpine", &[("Pierre Gasly", "French")])).unwrap(), &store).unwrap();
+        assert_eq!(found("gasly"), vec![("Alpine".to_string(), Some("Pierre Gasly".to_string()))]);
+
+        patch_driver("Mercedes", "Lewis Hamilton", json!({ "name": "Kimi Antonelli" }), &store).unwrap();
+        assert!(found("hamil").is_empty());
+        assert_eq!(found("antonelli"), vec![("Mercedes".to_string(), Some("Kimi Antonelli".to_string()))]);
+
+        put_team("Ferrari", serde_json::to_value(team("Ferrari", &[("Lewis Hamilton", "British")])).unwrap(), None, &store).unwrap();
+        assert_eq!(found("hamil"), vec![("Ferrari".to_string(), Some("Lewis Hamilton".to_string()))]);
+        assert!(found("leclerc").is_empty());
+
+        delete_team("Alpine", None, &store).unwrap();
+        assert!(found("gasly").is_empty());
+
+        // Un cambio hecho sin pasar por el índice se detecta por la revisión
+        inner.delete("Ferrari").unwrap();
+        assert!(found("hamil").is_empty());
+    }
+
+    #[test]
+    fn test_patch_team() {
+        let store = MemoryStore::new(vec![Team::from_value(json!({
+            "name": "Ferrari",
+            "base": "Maranello",
+            "drivers": [
+                { "name": "Charles Leclerc", "age": 26, "nationality": "Monegasque" },
+                { "name": "Carlos Sainz", "age": 29, "nationality": "Spanish" }
+            ]
+        })).unwrap()]);
+        let merge = "application/merge-patch+json";
+        let json_patch = "application/json-patch+json; charset=utf-8";
+
+        // Merge Patch: null elimina el campo y los arreglos se reemplazan completos
+        patch_team("Ferrari", merge, json!({ "base": null, "principal": "Fred Vasseur" }), None, &store).unwrap();
+        let team = serde_json::to_value(get_team("Ferrari", &store).unwrap()).unwrap();
+        assert_eq!(team.get("base"), None);
+        assert_eq!(team["principal"], "Fred Vasseur");
+
+        // JSON Patch con todas las operaciones
+        let operations = json!([
+            { "op": "test", "path": "/drivers/1/name", "value": "Carlos Sainz" },
+            { "op": "replace", "path": "/drivers/1/name", "value": "Lewis Hamilton" },
+            { "op": "add", "path": "/drivers/-", "value": { "name": "Oliver Bearman", "age": 19, "nationality": "British" } },
+            { "op": "copy", "from": "/drivers/2/nationality", "path": "/drivers/1/nationality" },
+            { "op": "move", "from": "/principal", "path": "/team_principal" },
+            { "op": "remove", "path": "/drivers/0" }
+        ]);
+        patch_team("Ferrari", json_patch, operations, None, &store).unwrap();
+        let team = serde_json::to_value(get_team("Ferrari", &store).unwrap()).unwrap();
+        // El piloto renombrado conserva su identificador
+        assert_eq!(team["drivers"][0], json!({ "id": "carlos-sainz", "name": "Lewis Hamilton", "age": 29, "nationality": "British" }));
+        assert_eq!(team["drivers"][1]["name"], "Oliver Bearman");
+        assert_eq!(team["team_principal"], "Fred Vasseur");
+        assert_eq!(team.get("principal"), None);
+
+        // Un test fallido es un conflicto y no se aplica ninguna operación
+        let before = get_team("Ferrari", &store).unwrap();
+        let operations = json!([
+            { "op": "replace", "path": "/name", "value": "Scuderia Ferrari" },
+            { "op": "test", "path": "/drivers/0/age", "value": 30 }
+        ]);
+        let error = patch_team("Ferrari", json_patch, operations, None, &store).unwrap_err();
+        assert!(error.is::<ConflictError>(), "Debería ser un conflicto: {}", error);
+        assert_eq!(get_team("Ferrari", &store).unwrap(), before);
+
+        // Rutas inexistentes, resultados inválidos y tipos no soportados
+        let error = patch_team("Ferrari", json_patch, json!([{ "op": "remove", "path": "/drivers/5" }]), None, &store).unwrap_err();
+        assert!(error.is::<ValidationError>());
+        let error = patch_team("Ferrari", merge, json!({ "drivers": [{ "name": "Sin edad" }] }), None, &store).unwrap_err();
+        assert!(error.is::<ValidationError>());
+        let error = patch_team("Ferrari", json_patch, json!([{ "op": "jump", "path": "/name" }]), None, &store).unwrap_err();
+        assert!(error.is::<ValidationError>());
+        let error = patch_team("Ferrari", "application/json", json!({}), None, &store).unwrap_err();
+        assert!(error.is::<UnsupportedMediaType>());
+
+        // Los identificadores no se pueden cambiar; enviarlos sin cambios sí se acepta
+        let id_errors = |error: Box<dyn Error>| -> Vec<String> {
+            let validation = error.downcast_ref::<ValidationError>().expect("Debería ser un error de validación");
+            validation.errors.iter().map(|e| e.field.clone()).collect()
+        };
+        let error = patch_team("Ferrari", json_patch, json!([{ "op": "replace", "path": "/id", "value": "otra" }]), None, &store).unwrap_err();
+        assert_eq!(id_errors(error), vec!["id"]);
+        let error = patch_team("Ferrari", merge, json!({ "id": "otra" }), None, &store).unwrap_err();
+        assert_eq!(id_errors(error), vec!["id"]);
+        let error = patch_team("Ferrari", json_patch, json!([{ "op": "replace", "path": "/drivers/0/id", "value": "otro" }]), None, &store).unwrap_err();
+        assert_eq!(id_errors(error), vec!["drivers[0].id"]);
+        let error = patch_driver("Ferrari", "Lewis Hamilton", json!({ "id": "otro" }), &store).unwrap_err();
+        assert_eq!(id_errors(error), vec!["id"]);
+        assert_eq!(get_team("Ferrari", &store).unwrap(), before);
+        patch_team("Ferrari", merge, json!({ "id": before.id }), None, &store).unwrap();
+        patch_driver("Ferrari", "Lewis Hamilton", json!({ "id": "carlos-sainz" }), &store).unwrap();
+        assert_eq!(get_team("Ferrari", &store).unwrap(), before);
+
+        // El patch se guarda en el journal y se puede volver a aplicar
+        let patch = TeamPatch::from_body(merge, json!({ "name": "Scuderia Ferrari" })).unwrap();
+        let mutation = Mutation::PatchTeam {
+            team_name: "Ferrari".to_string(),
+            patch,
+        };
+        let encoded = serde_json::to_string(&mutation).unwrap();
+        assert_eq!(serde_json::from_str::<Mutation>(&encoded).unwrap(), mutation);
+    }
+
+    #[test]
+    fn test_if_match_versions() {
+        let team = |age: u32| json!({
+            "name": "Ferrari",
+            "drivers": [{ "name": "Charles Leclerc", "age": age, "nationality": "Monegasque" }]
+        });
+        let store = IndexedStore::new(Arc::new(MemoryStore::new(vec![Team::from_value(team(26)).unwrap()]))).unwrap();
+        let version = get_team("Ferrari", &store).unwrap().version();
+        assert_eq!(version, get_team("Ferrari", &store).unwrap().version(), "La versión depende solo del contenido");
+
+        // Con la versión actual el cambio se aplica y la versión cambia
+        put_team("Ferrari", team(27), Some(&format!("\"{}\"", version)), &store).unwrap();
+        let new_version = get_team("Ferrari", &store).unwrap().version();
+        assert_ne!(new_version, version);
+
+        // Con una versión anterior (o débil) no se aplica nada
+        for if_match in [format!("\"{}\"", version), format!("W/\"{}\"", new_version)] {
+            let error = put_team("Ferrari", team(30), Some(&if_match), &store).unwrap_err();
+            assert!(error.is::<PreconditionFailed>(), "Debería fallar la precondición: {}", error);
+            let error = delete_team("Ferrari", Some(&if_match), &store).unwrap_err();
+            assert!(error.is::<PreconditionFailed>());
+        }
+        let error = patch_team("Ferrari", "application/merge-patch+json", json!({ "base": "Maranello" }), Some("otra"), &store).unwrap_err();
+        assert!(error.is::<PreconditionFailed>());
+        assert_eq!(get_team("Ferrari", &store).unwrap().drivers[0].age, 27);
+
+        // Una lista de versiones o * aceptan la actual; el índice de búsqueda se actualiza igual
+        let if_match = format!("\"{}\", \"{}\"", version, new_version);
+        patch_team("Ferrari", "application/merge-patch+json", json!({ "name": "Scuderia Ferrari" }), Some(&if_match), &store).unwrap();
+        assert_eq!(search("scuderia", 10, &store).unwrap()[0].team, "Scuderia Ferrari");
+        delete_team("Scuderia Ferrari", Some("*"), &store).unwrap();
+        let error = delete_team("Scuderia Ferrari", Some("*"), &store).unwrap_err();
+        assert!(error.is::<PreconditionFailed>(), "* no acepta una escudería que no existe");
+        assert!(search("ferrari", 10, &store).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_batch_is_all_or_nothing() {
+        let store = MemoryStore::new(vec![
+            Team::from_value(json!({ "name": "Ferrari", "drivers": [{ "name": "Carlos Sainz", "age": 29, "nationality": "Spanish" }] })).unwrap(),
+            Team::from_value(json!({ "name": "Williams", "drivers": [] })).unwrap(),
+        ]);
+        let sainz = json!({ "name": "Carlos Sainz", "age": 29, "nationality": "Spanish" });
+
+        // Mover un piloto de una escudería a otra en un solo cambio
+        let (results, changes) = apply_batch(json!({ "operations": [
+            { "method": "DELETE", "path": "/escuderias/Ferrari/pilotos/Carlos%20Sainz" },
+            { "method": "POST", "path": "/escuderias/Williams/pilotos", "body": sainz },
+            { "method": "PATCH", "path": "/escuderias/Williams", "body": { "base": "Grove" } }
+        ]}), &store).unwrap();
+        let statuses: Vec<u16> = results.iter().map(|r| r.status).collect();
+        assert_eq!(statuses, vec![200, 201, 200]);
+        // El cambio de cada operación, para la auditoría
+        let audited: Vec<(&str, bool, &str)> = changes
+            .iter()
+            .map(|c| (c.operation.as_str(), c.before.is_some(), c.after.as_ref().or(c.before.as_ref()).unwrap().0.as_str()))
+            .collect();
+        assert_eq!(audited, vec![
+            ("delete_driver", true, "escuderias/ferrari/pilotos/carlos-sainz"),
+            ("post_driver", false, "escuderias/williams/pilotos/carlos-sainz"),
+            ("patch_team", true, "escuderias/williams"),
+        ]);
+        assert_eq!(changes[2].after.as_ref().unwrap().1["base"], "Grove");
+        assert!(get_team("Ferrari", &store).unwrap().drivers.is_empty());
+        assert_eq!(get_team("Williams", &store).unwrap().drivers[0].name, "Carlos Sainz");
+        assert_eq!(store.revision(), 1, "El lote es un único cambio");
+
+        // Si una operación falla no se aplica ninguna y el error indica cuál fue
+        let before = store.list().unwrap();
+        let error = apply_batch(json!({ "operations": [
+            { "method": "DELETE", "path": "/escuderias/Williams/pilotos/Carlos Sainz" },
+            { "method": "POST", "path": "/escuderias/Ferrari/pilotos", "body": sainz },
+            { "method": "DELETE", "path": "/escuderias/Mercedes" }
+        ]}), &store).unwrap_err();
+        let batch_error = error.downcast_ref::<BatchError>().expect("Debería indicar la operación que falló");
+        assert_eq!(batch_error.index, 2);
+        assert_eq!(store.list().unwrap(), before);
+
+        // Los errores de validación y de If-Match conservan su tipo y su posición
+        let error = apply_batch(json!({ "operations": [
+            { "method": "DELETE", "path": "/escuderias/Williams", "if_match": "\"vieja\"" }
+        ]}), &store).unwrap_err();
+        let batch_error = error.downcast_ref::<BatchError>().unwrap();
+        assert!(batch_error.error.is::<PreconditionFailed>(), "{}", batch_error);
+        let error = apply_batch(json!({ "operations": [
+            { "method": "GET", "path": "/escuderias" },
+            { "method": "POST", "path": "/escuderias", "body": { "name": "Sin pilotos" } }
+        ]}), &store).unwrap_err();
+        let batch_error = error.downcast_ref::<BatchError>().unwrap();
+        assert_eq!(batch_error.index, 0);
+        assert!(batch_error.error.is::<ValidationError>());
+        assert!(apply_batch(json!({ "operations": [] }), &store).unwrap_err().is::<ValidationError>());
+        assert_eq!(store.list().unwrap(), before);
+
+        // El observador recibe las escuderías antes y después de cada paso del lote
+        let haas = Team::from_value(json!({ "name": "Haas", "drivers": [] })).unwrap();
+        let mut steps = Vec::new();
+        let mutations = vec![Mutation::Insert { team: haas }, Mutation::Delete { team_name: "haas".to_string() }];
+        store
+            .apply_observed(Mutation::Batch { mutations }, &mut |index, before, after| steps.push((index, before.len(), after.len())))
+            .unwrap();
+        assert_eq!(steps, vec![(0, 2, 3), (1, 3, 2)]);
+    }
+
+    // Test para verificar los traspasos de pilotos y la regla de un piloto por escudería
+    #[test]
+    fn test_transfer_driver_and_unique_drivers() {
+        use http_server_proyecto1_so::utils::format_utc_timestamp;
+
+        assert_eq!(format_utc_timestamp(0), "1970-01-01T00:00:00Z");
+        assert_eq!(format_utc_timestamp(1_700_000_000), "2023-11-14T22:13:20Z");
+
+        let teams = vec![
+            Team::from_value(json!({ "name": "Ferrari", "drivers": [{ "name": "Carlos Sainz", "age": 29, "nationality": "Spanish" }] })).unwrap(),
+            Team::from_value(json!({ "name": "Williams", "drivers": [] })).unwrap(),
+            // Duplicado que ya existe en los datos
+            Team::from_value(json!({ "name": "Mercedes", "drivers": [{ "name": "Lewis Hamilton", "age": 38, "nationality": "British" }] })).unwrap(),
+            Team::from_value(json!({ "name": "Ferrari HP", "drivers": [{ "name": "Lewis Hamilton", "age": 38, "nationality": "British" }] })).unwrap(),
+        ];
+        let store = MemoryStore::new(teams).with_unique_drivers(true);
+
+        let (transfer, changes) = transfer_driver("Carlos Sainz", json!({ "to": "Williams" }), &store).unwrap();
+        assert_eq!(transfer.from, "Ferrari");
+        assert!(get_team("Ferrari", &store).unwrap().drivers.is_empty());
+        let sainz = &get_team("Williams", &store).unwrap().drivers[0];
+        assert_eq!(sainz.transfers, vec![transfer.clone()], "El traspaso queda registrado en el piloto");
+        assert!(transfer.date.ends_with('Z'));
+        assert_eq!(store.revision(), 1, "El traspaso es un único cambio");
+        let audited: Vec<&str> = changes.iter().map(|c| c.after.as_ref().unwrap().0.as_str()).collect();
+        assert_eq!(audited, vec!["escuderias/ferrari", "escuderias/williams"], "Se registran las dos escuderías");
+        assert_eq!(changes[1].before.as_ref().unwrap().1["drivers"], json!([]));
+
+        // Con el piloto en varias escuderías hay que indicar desde cuál
+        let error = transfer_driver("Lewis Hamilton", json!({ "to": "Ferrari" }), &store).unwrap_err();
+        assert!(error.is::<ValidationError>());
+        assert!(transfer_driver("Nadie", json!({ "to": "Ferrari" }), &store).is_err());
+        assert!(transfer_driver("Carlos Sainz", json!({ "to": "Williams" }), &store).is_err(), "No se traspasa a la misma escudería");
+        assert!(transfer_driver("Carlos Sainz", json!({ "destino": "Ferrari" }), &store).unwrap_err().is::<ValidationError>());
+
+        // La regla rechaza un piloto nuevo en una segunda escudería, pero no bloquea los duplicados previos
+        let error = post_driver("Ferrari", json!({ "name": " carlos sainz ", "age": 29, "nationality": "Spanish" }), &store).unwrap_err();
+        assert!(error.is::<ConflictError>(), "{}", error);
+        patch_driver("Mercedes", "Lewis Hamilton", json!({ "age": 39 }), &store).unwrap();
+        let store = MemoryStore::new(store.list().unwrap());
+        post_driver("Ferrari", json!({ "name": "Carlos Sainz", "age": 29, "nationality": "Spanish" }), &store).unwrap();
+
+        let args: Vec<String> = vec!["--unique-drivers".into()];
+        assert!(Config::from_sources(&args, |_| None).unwrap().unique_drivers);
+        assert!(!Config::from_sources(&[], |_| None).unwrap().unique_drivers);
+        let env = |value: &'static str| move |name: &str| (name == "F1_UNIQUE_DRIVERS").then(|| value.to_string());
+        assert!(Config::from_sources(&[], env("true")).unwrap().unique_drivers);
+        assert!(Config::from_sources(&[], env("quizás")).is_err());
+    }
+
+    // Test para verificar los identificadores de escuderías y pilotos
+    #[test]
+    fn test_stable_ids() {
+        use http_server_proyecto1_so::utils::slugify;
+
+        assert_eq!(slugify("Sergio Pérez"), "sergio-perez");
+        assert_eq!(slugify("  Alpine / BWT "), "alpine-bwt");
+        assert_eq!(slugify("レッドブル"), "");
+
+        // Los datos sin identificadores los reciben al cargarse, sin repetirse
+        let store = MemoryStore::new(vec![
+            Team::from_value(json!({ "name": "Mercedes", "drivers": [{ "name": "Lewis Hamilton", "age": 38, "nationality": "British" }] })).unwrap(),
+            Team::from_value(json!({ "name": "Ferrari", "drivers": [{ "name": "Lewis Hamilton", "age": 39, "nationality": "British" }] })).unwrap(),
+        ]);
+        let ids: Vec<String> = store.list().unwrap().iter().map(|t| t.drivers[0].id.clone()).collect();
+        assert_eq!(ids, vec!["lewis-hamilton", "lewis-hamilton-2"]);
+
+        // El servidor asigna el identificador e ignora el que envía el cliente
+        let team = post_team(json!({ "id": "mercedes", "name": "Scuderia Ferrari", "drivers": [] }), &store).unwrap();
+        assert_eq!(team.id, "scuderia-ferrari");
+        let driver = post_driver("scuderia-ferrari", json!({ "id": "x", "name": "Kimi Antonelli", "age": 18, "nationality": "Italian" }), &store).unwrap();
+        assert_eq!(driver.id, "kimi-antonelli");
+        let team = post_team(json!({ "name": "ミ", "drivers": [] }), &store).unwrap();
+        assert_eq!(team.id, "escuderia");
+
+        // Renombrar con PUT o PATCH no cambia los identificadores
+        put_team("ferrari", json!({ "name": "Ferrari HP", "drivers": [{ "name": "Lewis Hamilton", "age": 40, "nationality": "British" }] }), None, &store).unwrap();
+        let team = get_team("ferrari", &store).unwrap();
+        assert_eq!((team.name.as_str(), team.drivers[0].id.as_str()), ("Ferrari HP", "lewis-hamilton-2"));
+        patch_driver("ferrari", "lewis-hamilton-2", json!({ "id": "lewis-hamilton-2", "name": "Sir Lewis Hamilton" }), &store).unwrap();
+        assert_eq!(get_driver("ferrari", "lewis-hamilton-2", &store).unwrap().name, "Sir Lewis Hamilton");
+        assert!(patch_driver("ferrari", "lewis-hamilton-2", json!({ "id": "otro" }), &store).unwrap_err().is::<ValidationError>());
+        assert!(patch_team("ferrari", "application/merge-patch+json", json!({ "id": "otro" }), None, &store).unwrap_err().is::<ValidationError>());
+        assert_eq!(get_team("Ferrari HP", &store).unwrap().id, "ferrari");
+
+        // El traspaso conserva el identificador del piloto y se busca primero por identificador
+        transfer_driver("kimi-antonelli", json!({ "to": "mercedes" }), &store).unwrap();
+        assert_eq!(get_driver("Mercedes", "kimi-antonelli", &store).unwrap().transfers[0].from, "Scuderia Ferrari");
+        delete_team("scuderia-ferrari", None, &store).unwrap();
+        assert!(get_team("Scuderia Ferrari", &store).is_err());
+
+        // La migración asigna identificadores a los documentos anteriores
+        let mut old_document: HashMap<String, Value> = serde_json::from_value(json!({
+            "schema_version": 1,
+            "teams": [
+                { "name": "Red Bull", "base": "Milton Keynes", "drivers": [{ "name": "Max Verstappen", "age": 26, "nationality": "Dutch" }] },
+                { "id": "red-bull", "name": "RB", "drivers": [] }
+            ]
+        })).unwrap();
+        assert!(migrate(&mut old_document).unwrap());
+        assert_eq!(old_document["teams"][0]["id"], "red-bull-2", "No repite los identificadores existentes");
+        assert_eq!(old_document["teams"][0]["drivers"][0]["id"], "max-verstappen");
+        assert_eq!(old_document["teams"][0]["base"], "Milton Keynes", "Conserva los campos adicionales");
+        let mut invalid: HashMap<String, Value> =
+            serde_json::from_value(json!({ "schema_version": 1, "teams": [{ "name": "Sin pilotos" }] })).unwrap();
+        assert!(migrate(&mut invalid).is_err());
+    }
+
+    // Test para verificar el registro de auditoría y su consulta
+    #[test]
+    fn test_audit_log() {
+        use http_server_proyecto1_so::json_hashmaps::patch::{diff, PatchOperation};
+
+        let dir = std::env::temp_dir().join(format!("f1_audit_test_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("audit.jsonl");
+        let _ = std::fs::remove_file(&path);
+
+        let store = MemoryStore::new(Vec::new());
+        let audit = AuditLog::open(&path).unwrap();
+        let actor = Actor { session: "session_1".to_string(), client_ip: "127.0.0.1".to_string() };
+        let created = |team: Option<&Team>| Ok(team.map(|team| (format!("escuderias/{}", team.id), json!(team))));
+
+        audit.record(&actor, DEFAULT_DATASET, "post_team", created, || {
+            post_team(json!({ "name": "Equipo", "drivers": [{ "name": "Piloto", "age": 30, "nationality": "British" }] }), &store)
+        }).unwrap();
+        audit.record(&actor, DEFAULT_DATASET, "patch_driver", |_| driver_entity(&store, "equipo", "piloto"), || {
+            patch_driver("equipo", "piloto", json!({ "age": 31 }), &store)
+        }).unwrap();
+        // Un cambio que falla no se registra
+        assert!(audit.record(&actor, DEFAULT_DATASET, "patch_driver", |_| driver_entity(&store, "equipo", "piloto"), || {
+            patch_driver("equipo", "piloto", json!({ "age": 5 }), &store)
+        }).is_err());
+        audit.record(&actor, "2024", "delete_team", |_| team_entity(&store, "equipo"), || delete_team("equipo", None, &store)).unwrap();
+
+        // Las entradas se conservan al reabrir el registro y siguen numerándose
+        let audit = AuditLog::open(&path).unwrap();
+        let entries = audit.query(&AuditQuery::default()).unwrap();
+        let summary: Vec<(u64, &str, &str)> = entries.iter().map(|e| (e.seq, e.operation.as_str(), e.entity.as_str())).collect();
+        assert_eq!(summary, vec![
+            (1, "post_team", "escuderias/equipo"),
+            (2, "patch_driver", "escuderias/equipo/pilotos/piloto"),
+            (3, "delete_team", "escuderias/equipo"),
+        ]);
+        assert_eq!(entries[0].before, Value::Null);
+        assert_eq!(entries[1].diff, vec![PatchOperation::Replace { path: "/age".to_string(), value: json!(31) }]);
+        assert_eq!((entries[1].session.as_str(), entries[1].client_ip.as_str()), ("session_1", "127.0.0.1"));
+        assert_eq!(entries[2].after, Value::Null);
+
+        // Filtros por entidad (incluye sus pilotos), conjunto de datos y fecha
+        let query = |params: Value| AuditQuery::parse(params.as_object().unwrap());
+        let driver_changes = audit.query(&query(json!({ "entity": "escuderias/equipo/pilotos" })).unwrap()).unwrap();
+        assert_eq!(driver_changes.len(), 1);
+        let default_changes = audit.query(&AuditQuery { dataset: Some(DEFAULT_DATASET.to_string()), ..query(json!({ "entity": "/escuderias/equipo/" })).unwrap() }).unwrap();
+        assert_eq!(default_changes.len(), 2);
+        assert_eq!(audit.query(&query(json!({ "since": "2000-01-01" })).unwrap()).unwrap().len(), 3);
+        assert!(audit.query(&query(json!({ "since": "9999" })).unwrap()).unwrap().is_empty());
+        assert!(query(json!({ "since": "ayer" })).is_err());
+
+        // Cada conjunto de datos tiene su propio lock: un cambio de otro conjunto no espera al que está en curso
+        let other = MemoryStore::new(Vec::new());
+        audit.record_all(&actor, "2024", || {
+            let team = audit.record(&actor, DEFAULT_DATASET, "post_team", created, || post_team(json!({ "name": "Otro", "drivers": [] }), &other))?;
+            Ok((team, Vec::new()))
+        }).unwrap();
+        assert_eq!(audit.query(&AuditQuery::default()).unwrap().last().unwrap().seq, 4);
+
+        // Aplicar el diff como JSON Patch al estado anterior da el estado posterior
+        let before = Team::from_value(json!({ "name": "A", "drivers": [
+            { "name": "Uno", "age": 20, "nationality": "British" },
+            { "name": "Dos", "age": 21, "nationality": "British" }
+        ] })).unwrap();
+        let after = Team::from_value(json!({ "name": "A/B", "drivers": [{ "name": "Uno", "age": 22, "nationality": "Dutch" }] })).unwrap();
+        let operations = diff(&json!(before), &json!(after));
+        assert_eq!(TeamPatch::Json(operations.clone()).apply(&before).unwrap(), after);
+        assert_eq!(TeamPatch::Json(diff(&json!(after), &json!(before))).apply(&after).unwrap(), before);
+        assert!(operations.contains(&PatchOperation::Remove { path: "/drivers/1".to_string() }));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_soft_delete_and_restore() {
+        use http_server_proyecto1_so::json_hashmaps::team_store::purge_deleted;
+
+        fn check(store: &dyn TeamStore) {
+            let team = |name: &str| json!({ "name": name, "drivers": [{ "name": "Piloto", "age": 30, "nationality": "British" }] });
+            post_team(team("Equipo"), store).unwrap();
+            post_team(team("Otro"), store).unwrap();
+            delete_team("equipo", None, store).unwrap();
+
+            // La escudería eliminada no aparece ni se puede modificar, salvo con ?include_deleted=true
+            assert!(store.get("equipo").unwrap().is_none());
+            assert!(patch_driver("equipo", "piloto", json!({ "age": 31 }), store).is_err());
+            let names = |include_deleted: &str| {
+                let query = ListQuery::parse(json!({ "include_deleted": include_deleted }).as_object().unwrap()).unwrap();
+                list_teams(store, &query).unwrap().items.into_iter().map(|team| team["name"].clone()).collect::<Vec<_>>()
+            };
+            assert_eq!(names("false"), vec![json!("Otro")]);
+            assert_eq!(names("true"), vec![json!("Equipo"), json!("Otro")]);
+            assert!(ListQuery::parse(json!({ "include_deleted": "si" }).as_object().unwrap()).is_err());
+
+            // El nombre queda libre, pero entonces no se puede restaurar la anterior
+            post_team(team("Equipo"), store).unwrap();
+            assert!(restore_team("equipo", store).unwrap_err().is::<ConflictError>());
+            delete_team("Equipo", None, store).unwrap();
+            let restored = restore_team("equipo", store).unwrap();
+            assert_eq!((restored.id.as_str(), restored.deleted_at), ("equipo", None));
+            assert!(restore_team("equipo", store).is_err(), "Solo se restauran escuderías eliminadas");
+
+            // La purga borra las eliminadas antes del período de retención
+            store.apply_mutation(Mutation::SoftDelete { team_name: "Otro".to_string(), date: "2000-01-01T00:00:00Z".to_string() }).unwrap();
+            assert_eq!(purge_deleted(store, Duration::from_secs(86_400)).unwrap(), 1);
+            let remaining: Vec<String> = store.list_all().unwrap().into_iter().map(|team| team.id).collect();
+            assert_eq!(remaining, vec!["equipo", "equipo-2"]);
+            assert_eq!(purge_deleted(store, Duration::from_secs(86_400)).unwrap(), 0);
+        }
+
+        check(&MemoryStore::new(Vec::new()));
+
+        #[cfg(feature = "sqlite")]
+        {
+            let db_path = std::env::temp_dir().join(format!("f1_soft_delete_test_{}.db", std::process::id()));
+            let _ = std::fs::remove_file(&db_path);
+            check(&SqliteStore::open(&db_path).unwrap());
+            std::fs::remove_file(&db_path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_change_events() {
+        use std::io::{BufRead, BufReader};
+        use std::net::TcpListener;
+
+        let events = Arc::new(EventStream::new(4));
+        Arc::clone(&events).start(Duration::from_millis(50));
+        let mut datasets = Datasets::with_events(Arc::new(MemoryStore::new(Vec::new())), Arc::clone(&events)).unwrap();
+        datasets.insert("2024", Arc::new(MemoryStore::new(Vec::new()))).unwrap();
+
+        // Suscribir una conexión local y leer sus eventos (líneas "event:" e "id:")
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let subscribe = |topic: &str, last_event_id: Option<u64>| {
+            let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+            client.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+            events.subscribe(listener.accept().unwrap().0, topic, last_event_id);
+            BufReader::new(client)
+        };
+        let read_event = |reader: &mut BufReader<TcpStream>| -> (String, String, Value) {
+            let (mut id, mut event, mut data) = (String::new(), String::new(), Value::Null);
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                let line = line.trim_end();
+                if let Some(value) = line.strip_prefix("id: ") { id = value.to_string(); }
+                if let Some(value) = line.strip_prefix("event: ") { event = value.to_string(); }
+                if let Some(value) = line.strip_prefix("data: ") { data = serde_json::from_str(value).unwrap(); }
+                if line.is_empty() && !event.is_empty() { break; }
+            }
+            (id, event, data)
+        };
+
+        let mut reader = subscribe(DEFAULT_DATASET, None);
+        let store = datasets.get(DEFAULT_DATASET).unwrap();
+        post_team(json!({ "name": "Equipo", "drivers": [{ "name": "Piloto", "age": 30, "nationality": "British" }] }), store.as_ref()).unwrap();
+        patch_driver("equipo", "piloto", json!({ "age": 31 }), store.as_ref()).unwrap();
+        post_team(json!({ "name": "Otro", "drivers": [] }), datasets.get("2024").unwrap().as_ref()).unwrap();
+        delete_team("equipo", None, store.as_ref()).unwrap();
+
+        let (_, event, data) = read_event(&mut reader);
+        assert_eq!((event.as_str(), data["id"].as_str()), ("created", Some("equipo")));
+        let (id, event, data) = read_event(&mut reader);
+        assert_eq!((event.as_str(), &data["team"]["drivers"][0]["age"]), ("updated", &json!(31)));
+        // Los cambios de otro conjunto de datos no se envían
+        let (_, event, data) = read_event(&mut reader);
+        assert_eq!((event.as_str(), &data["team"], &data["dataset"]), ("deleted", &Value::Null, &json!(DEFAULT_DATASET)));
+
+        // Retomar desde Last-Event-ID con los eventos guardados; si ya no están, se pide recargar
+        let mut resumed = subscribe(DEFAULT_DATASET, Some(id.parse().unwrap()));
+        assert_eq!(read_event(&mut resumed).1, "deleted");
+        restore_team("equipo", store.as_ref()).unwrap();
+        let mut reset = subscribe(DEFAULT_DATASET, Some(0));
+        assert_eq!(read_event(&mut reset).1, "reset");
+        let mut restarted = subscribe(DEFAULT_DATASET, Some(1000));
+        assert_eq!(read_event(&mut restarted).1, "reset");
+
+        // Un suscriptor que no lee no retrasa a los demás: lo pendiente queda en su búfer
+        let slow_events = Arc::new(EventStream::new(1000));
+        Arc::clone(&slow_events).start(Duration::from_secs(60));
+        let connect = || {
+            let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+            client.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+            slow_events.subscribe(listener.accept().unwrap().0, DEFAULT_DATASET, None);
+            client
+        };
+        let _stalled = connect();
+        let mut reader = BufReader::new(connect());
+        let started = std::time::Instant::now();
+        let data = json!({ "relleno": "x".repeat(64 * 1024) });
+        for _ in 0..100 {
+            slow_events.publish(DEFAULT_DATASET, "updated", &data);
+        }
+        let last_id = slow_events.publish(DEFAULT_DATASET, "done", &json!({}));
+        let (id, _, _) = std::iter::repeat_with(|| read_event(&mut reader)).find(|(_, event, _)| event == "done").unwrap();
+        assert_eq!(id, last_id.to_string());
+        assert!(started.elapsed() < Duration::from_secs(3), "Tardó {:?}", started.elapsed());
+    }
+
+    #[test]
+    fn test_websocket() {
+        use std::io::{BufReader, Read};
+        use std::net::TcpListener;
+        use http_server_proyecto1_so::server_http::routes::Router;
+        use http_server_proyecto1_so::server_http::websocket::{accept_key, CloseFrame, Message, WebSocket, CLOSE_NORMAL, CLOSE_PROTOCOL_ERROR};
+        use http_server_proyecto1_so::utils::parse_request_into_hashmap;
+
+        // Ejemplo de la RFC 6455
+        assert_eq!(accept_key("dGhlIHNhbXBsZSBub25jZQ=="), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+
+        // Servidor de eco en /eco/:nombre
+        let mut router = Router::new();
+        router.websocket("/eco/:nombre", |mut socket: WebSocket, request: HashMap<String, Value>| {
+            let name = request["params"]["nombre"].as_str().unwrap_or_default().to_string();
+            while let Ok(message) = socket.read_message() {
+                match message {
+                    Message::Text(text) => socket.send_text(&format!("{}: {}", name, text)).unwrap(),
+                    Message::Binary(data) => socket.send(&Message::Binary(data)).unwrap(),
+                    Message::Close(_) => break,
+                    _ => {}
+                }
+            }
+        });
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let request = parse_request_into_hashmap(BufReader::new(&stream));
+                router.handle_request(request, &mut stream);
+            }
+        });
+
+        let mut client = WebSocket::connect(&address, "/eco/uno").unwrap();
+        client.send_text("hola").unwrap();
+        assert_eq!(client.read_message().unwrap(), Message::Text("uno: hola".to_string()));
+        // Los mensajes de más de 125 y de más de 65535 bytes usan longitudes extendidas
+        for len in [200, 70_000] {
+            let data = vec![7u8; len];
+            client.send(&Message::Binary(data.clone())).unwrap();
+            assert_eq!(client.read_message().unwrap(), Message::Binary(data));
+        }
+        client.send(&Message::Ping(b"latido".to_vec())).unwrap();
+        assert_eq!(client.read_message().unwrap(), Message::Pong(b"latido".to_vec()));
+        client.close(CLOSE_NORMAL, "adiós").unwrap();
+        let closed = Message::Close(Some(CloseFrame { code: CLOSE_NORMAL, reason: String::new() }));
+        assert_eq!(client.read_message().unwrap(), closed);
+        assert!(client.send_text("tarde").is_err());
+
+        // Cuadros armados a mano: un texto en dos fragmentos y luego un cuadro sin máscara
+        let handshake = |version: &str| {
+            let mut raw = TcpStream::connect(&address).unwrap();
+            let request = format!(
+                "GET /eco/dos HTTP/1.1\r\nHost: local\r\nUpgrade: websocket\r\nConnection: keep-alive, Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: {}\r\n\r\n",
+                version
+            );
+            raw.write_all(request.as_bytes()).unwrap();
+            let mut response = Vec::new();
+            let mut byte = [0u8; 1];
+            while !response.ends_with(b"\r\n\r\n") && raw.read(&mut byte).unwrap() == 1 {
+                response.push(byte[0]);
+            }
+            (raw, String::from_utf8(response).unwrap())
+        };
+        let masked = |first_byte: u8, data: &[u8]| {
+            let mask = [1u8, 2, 3, 4];
+            let mut frame = vec![first_byte, 0x80 | data.len() as u8];
+            frame.extend(mask);
+            frame.extend(data.iter().enumerate().map(|(i, byte)| byte ^ mask[i % 4]));
+            frame
+        };
+        let (mut raw, response) = handshake("13");
+        assert!(response.starts_with("HTTP/1.1 101") && response.contains("s3pPLMBiTxaQ9kYGzzhZRbK+xOo="));
+        raw.write_all(&[masked(0x01, b"ho"), masked(0x80, b"la")].concat()).unwrap();
+        let mut frame = [0u8; 11];
+        raw.read_exact(&mut frame).unwrap();
+        assert_eq!(&frame[..2], &[0x81, 9]);
+        assert_eq!(&frame[2..], b"dos: hola");
+        raw.write_all(&[0x81, 0x02, b'n', b'o']).unwrap();
+        let mut close = [0u8; 4];
+        raw.read_exact(&mut close).unwrap();
+        assert_eq!(close[0], 0x88);
+        assert_eq!(u16::from_be_bytes([close[2], close[3]]), CLOSE_PROTOCOL_ERROR);
+
+        // Solicitudes que no piden un WebSocket válido
+        assert!(handshake("8").1.starts_with("HTTP/1.1 426"));
+        let mut plain = TcpStream::connect(&address).unwrap();
+        plain.write_all(b"GET /eco/tres HTTP/1.1\r\nHost: local\r\n\r\n").unwrap();
+        let mut response = String::new();
+        plain.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 400"));
+    }
+
+    #[test]
+    fn test_webhooks() {
+        use std::io::BufReader;
+        use std::net::TcpListener;
+        use http_server_proyecto1_so::json_hashmaps::webhooks::sign;
+        use http_server_proyecto1_so::utils::parse_request_into_hashmap;
+
+        // Receptor local: /inestable falla la primera vez y /caido siempre
+        let received: Arc<std::sync::Mutex<Vec<HashMap<String, Value>>>> = Arc::default();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let receiver_url = format!("http://{}", listener.local_addr().unwrap());
+        let received_clone = Arc::clone(&received);
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let request = parse_request_into_hashmap(BufReader::new(&stream));
+                let mut received = received_clone.lock().unwrap();
+                let path = request["path"].as_str().unwrap_or_default().to_string();
+                let fails = path == "/caido" || (path == "/inestable" && !received.iter().any(|r| r["path"] == "/inestable"));
+                received.push(request);
+                let status = if fails { "500 Internal Server Error" } else { "200 OK" };
+                let _ = stream.write_all(format!("HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status).as_bytes());
+            }
+        });
+        let wait_until = |done: &dyn Fn() -> bool| {
+            for _ in 0..250 {
+                if done() {
+                    return;
+                }
+                thread::sleep(Duration::from_millis(20));
+            }
+            panic!("El webhook no recibió lo esperado a tiempo");
+        };
+
+        let path = std::env::temp_dir().join(format!("f1_webhooks_test_{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let events = Arc::new(EventStream::new(100));
+        let datasets = Datasets::with_events(Arc::new(MemoryStore::new(Vec::new())), Arc::clone(&events)).unwrap();
+        let webhooks = Arc::new(Webhooks::open(&path, 3, Duration::from_millis(10)).unwrap());
+        Arc::clone(&webhooks).start(Arc::clone(&events));
+
+        assert!(webhooks.register(DEFAULT_DATASET, &json!({ "url": "ftp://x", "events": ["borrado"] })).unwrap_err().is::<ValidationError>());
+        let flaky = webhooks
+            .register(DEFAULT_DATASET, &json!({ "url": format!("{}/inestable", receiver_url), "events": ["created"], "secret": "clave-de-prueba-123" }))
+            .unwrap();
+        let down = webhooks.register(DEFAULT_DATASET, &json!({ "url": format!("{}/caido", receiver_url) })).unwrap();
+        assert_eq!(down.secret.len(), 64);
+        assert!(webhooks.list(DEFAULT_DATASET).iter().all(|webhook| webhook.secret.is_empty()));
+
+        let store = datasets.get(DEFAULT_DATASET).unwrap();
+        post_team(json!({ "name": "Equipo", "drivers": [] }), store.as_ref()).unwrap();
+        patch_team("equipo", "application/merge-patch+json", json!({ "base": "Maranello" }), None, store.as_ref()).unwrap();
+
+        // La primera entrega falla y se reintenta; llega firmada con la clave del webhook
+        let delivered = |path: &str| received.lock().unwrap().iter().filter(|r| r["path"] == path).cloned().collect::<Vec<_>>();
+        wait_until(&|| delivered("/inestable").len() == 2);
+        let request = &delivered("/inestable")[1];
+        let body = request["body"].clone();
+        assert_eq!((&body["event"], &body["team_id"], &body["team"]["name"]), (&json!("created"), &json!("equipo"), &json!("Equipo")));
+        let signature = format!("sha256={}", sign("clave-de-prueba-123", &body.to_string()));
+        assert_eq!(header(request, "X-Webhook-Signature"), Some(signature.as_str()));
+
+        // Después de 3 intentos las entregas quedan entre las fallidas, y se conservan al reabrir
+        wait_until(&|| webhooks.dead_letters(DEFAULT_DATASET).len() == 2);
+        assert_eq!(delivered("/caido").len(), 6);
+        let dead_letters = Webhooks::open(&path, 3, Duration::from_millis(10)).unwrap().dead_letters(DEFAULT_DATASET);
+        let events: Vec<(&str, u32)> = dead_letters.iter().map(|d| (d.event.as_str(), d.attempts)).collect();
+        assert_eq!(events, vec![("created", 3), ("updated", 3)]);
+        assert!(dead_letters[0].last_error.as_ref().unwrap().contains("500"));
+
+        // Reintentar una fallida la vuelve a enviar con todos sus intentos
+        webhooks.retry(DEFAULT_DATASET, dead_letters[0].id).unwrap();
+        wait_until(&|| delivered("/caido").len() == 9);
+        webhooks.delete(DEFAULT_DATASET, &down.id).unwrap();
+        assert!(webhooks.retry(DEFAULT_DATASET, dead_letters[1].id).unwrap_err().is::<WebhookNotFound>());
+        assert!(webhooks.delete(DEFAULT_DATASET, &down.id).is_err());
+        let remaining: Vec<String> = webhooks.list(DEFAULT_DATASET).into_iter().map(|webhook| webhook.id).collect();
+        assert_eq!(remaining, vec![flaky.id]);
+        assert!(webhooks.list("2024").is_empty());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_seasons_and_standings() {
+        use http_server_proyecto1_so::json_hashmaps::seasons::{result_points, Season};
+
+        fn check(store: &dyn TeamStore) {
+            let driver = |name: &str| json!({ "name": name, "age": 30, "nationality": "British" });
+            post_team(json!({ "name": "Equipo A", "drivers": [driver("Uno"), driver("Dos")] }), store).unwrap();
+            post_team(json!({ "name": "Equipo B", "drivers": [driver("Tres"), driver("Cuatro")] }), store).unwrap();
+            let result = |position: u32, driver: &str, team: &str| json!({ "position": position, "driver": driver, "team": team });
+            let race = |name: &str, date: &str, results: Vec<Value>| {
+                json!({ "name": name, "circuit": "Circuito", "date": date, "results": results })
+            };
+
+            // Los resultados aceptan nombres y se guardan con identificadores y con los puntos calculados
+            let mut fastest = result(2, "Tres", "Equipo B");
+            fastest["fastest_lap"] = json!(true);
+            fastest["points"] = json!(100);
+            let first = race("Bahrein", "2024-03-02", vec![result(1, "uno", "equipo-a"), fastest, result(3, "Dos", "Equipo A"), result(4, "Cuatro", "Equipo B")]);
+            let (season, created) = put_season(2024, json!({ "races": [first] }), store).unwrap();
+            assert!(created);
+            assert_eq!(season.races[0].results[1].driver, "tres");
+            assert_eq!(season.races[0].results[1].points, 19);
+
+            let mut fastest = result(1, "tres", "equipo-b");
+            fastest["fastest_lap"] = json!(true);
+            let second = race("Arabia Saudita", "2024-03-09", vec![result(11, "dos", "equipo-a"), fastest, result(2, "uno", "equipo-a"), result(3, "cuatro", "equipo-b")]);
+            let (saved, created) = put_race(2024, 2, second.clone(), store).unwrap();
+            assert!(created);
+            assert_eq!(saved.results.iter().map(|r| r.position).collect::<Vec<_>>(), vec![1, 2, 3, 11]);
+            assert!(!put_race(2024, 2, second, store).unwrap().1);
+
+            let drivers = get_driver_standings(2024, store).unwrap();
+            let summary: Vec<(&str, u32, u32)> = drivers.iter().map(|d| (d.driver.as_str(), d.points, d.wins)).collect();
+            assert_eq!(summary, vec![("tres", 45, 1), ("uno", 43, 1), ("cuatro", 27, 0), ("dos", 15, 0)]);
+            assert_eq!((drivers[0].name.as_str(), drivers[0].team.as_str(), drivers[0].podiums), ("Tres", "equipo-b", 2));
+            let constructors = get_constructor_standings(2024, store).unwrap();
+            let summary: Vec<(&str, &str, u32)> = constructors.iter().map(|c| (c.team.as_str(), c.name.as_str(), c.points)).collect();
+            assert_eq!(summary, vec![("equipo-b", "Equipo B", 72), ("equipo-a", "Equipo A", 58)]);
+
+            // Con los mismos puntos gana quien tenga más victorias
+            let (_, created) = put_season(2023, json!({ "year": 2023, "races": [
+                race("Uno", "2023-03-05", vec![result(1, "uno", "equipo-a"), result(3, "dos", "equipo-a")]),
+                race("Dos", "2023-03-19", vec![result(5, "dos", "equipo-a")]),
+            ] }), store).unwrap();
+            assert!(created);
+            let drivers = get_driver_standings(2023, store).unwrap();
+            assert_eq!((drivers[0].points, drivers[1].points), (25, 25));
+            assert_eq!((drivers[0].driver.as_str(), drivers[0].position), ("uno", 1));
+            assert_eq!(get_seasons(store).unwrap().iter().map(|s| s.year).collect::<Vec<_>>(), vec![2023, 2024]);
+
+            // Datos inválidos: año distinto al de la ruta, fecha de otro año, piloto inexistente, posición repetida
+            let invalid = |result: Result<(Season, bool), Box<dyn Error>>| {
+                let error = result.unwrap_err();
+                error.downcast_ref::<ValidationError>().unwrap().errors.iter().map(|e| e.field.clone()).collect::<Vec<_>>()
+            };
+            assert_eq!(invalid(put_season(2024, json!({ "year": 2025 }), store)), vec!["year"]);
+            let bad_race = race("Mala", "2023-02-30", vec![result(1, "nadie", "equipo-a"), result(1, "uno", "equipo-a")]);
+            assert_eq!(
+                invalid(put_season(2022, json!({ "races": [bad_race] }), store)),
+                vec!["races[0].date", "races[0].results[0].driver", "races[0].results[1].position"]
+            );
+            assert!(put_race(2021, 1, race("X", "2021-05-01", vec![]), store).unwrap_err().is::<SeasonNotFound>());
+            assert_eq!(get_seasons(store).unwrap().len(), 2, "Los cambios inválidos no se guardan");
+
+            delete_race(2024, 2, store).unwrap();
+            assert!(get_race(2024, 2, store).unwrap_err().is::<SeasonNotFound>());
+            assert_eq!(get_driver_standings(2024, store).unwrap()[0].driver, "uno");
+            delete_season(2023, store).unwrap();
+            assert!(delete_season(2023, store).unwrap_err().is::<SeasonNotFound>());
+            assert!(get_constructor_standings(2023, store).unwrap_err().is::<SeasonNotFound>());
+        }
+
+        assert_eq!((result_points(1, false), result_points(10, true), result_points(11, true)), (25, 2, 0));
+        check(&MemoryStore::new(Vec::new()));
+
+        // En el archivo las temporadas se guardan junto a las escuderías
+        let dir = std::env::temp_dir().join(format!("f1_seasons_test_{}", std::process::id()));
+        let path = dir.join("f1_data.json");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(&path, r#"{ "teams": [] }"#).unwrap();
+        check(&JsonFileStore::open(&path).unwrap());
+        let reopened = JsonFileStore::open(&path).unwrap();
+        assert_eq!(get_season(2024, &reopened).unwrap().races.len(), 1);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        #[cfg(feature = "sqlite")]
+        {
+            let db_path = std::env::temp_dir().join(format!("f1_seasons_test_{}.db", std::process::id()));
+            let _ = std::fs::remove_file(&db_path);
+            check(&SqliteStore::open(&db_path).unwrap());
+            std::fs::remove_file(&db_path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_import_export_formats() {
+        use http_server_proyecto1_so::json_hashmaps::formats::DataFormat;
+        use http_server_proyecto1_so::json_hashmaps::team_store::ChangeKind;
+
+        let store = MemoryStore::new(Vec::new());
+        post_team(json!({ "name": "Red Bull, Racing", "base": "Milton Keynes", "titles": 6, "drivers": [
+            { "name": "Max \"Mad Max\" Verstappen", "age": 27, "nationality": "Dutch", "number": 1 },
+            { "name": "Sergio Pérez", "age": 34, "nationality": "Mexican" }
+        ] }), &store).unwrap();
+        post_team(json!({ "name": "Sin pilotos", "drivers": [] }), &store).unwrap();
+        store.apply_mutation(Mutation::TransferDriver {
+            driver_name: "sergio-perez".to_string(),
+            from: "red-bull-racing".to_string(),
+            to: "sin-pilotos".to_string(),
+            date: "2024-12-18T10:00:00Z".to_string(),
+        }).unwrap();
+        let teams = store.list().unwrap();
+
+        // Cada formato se vuelve a importar igual en un almacenamiento vacío
+        for format in [DataFormat::Json, DataFormat::Csv, DataFormat::Ndjson, DataFormat::Yaml] {
+            let exported = export_teams(format, &store).unwrap();
+            let copy = MemoryStore::new(Vec::new());
+            let (report, _) = import_teams(&exported, format, ImportMode::Merge, false, &copy).unwrap();
+            assert_eq!((report.teams, report.changes.len(), report.unchanged), (2, 2, 0), "{}", format.name());
+            assert_eq!(copy.list().unwrap(), teams, "{}", format.name());
+            let (again, _) = import_teams(&exported, format, ImportMode::Merge, false, &copy).unwrap();
+            assert_eq!((again.changes.len(), again.unchanged), (0, 2));
+        }
+        let csv = export_teams(DataFormat::Csv, &store).unwrap();
+        assert!(csv.starts_with("team_id,team_name,driver_id,driver_name,driver_age,driver_nationality,driver_transfers,team.base,team.titles,driver.number\n"));
+        assert!(csv.contains("red-bull-racing,\"Red Bull, Racing\",max-mad-max-verstappen,\"Max \"\"Mad Max\"\" Verstappen\",27,Dutch,,Milton Keynes,6,1\n"));
+
+        // Los textos que se leerían como otro valor se exportan entre comillas y siguen siendo textos
+        let texts = MemoryStore::new(Vec::new());
+        post_team(json!({ "name": "Textos", "code": "007", "flag": "true", "motto": "\"Go\"", "note": " a ", "drivers": [
+            { "name": "Uno", "age": 30, "nationality": "Thai", "number": "23", "tags": "[1]" }
+        ] }), &texts).unwrap();
+        let exported = export_teams(DataFormat::Csv, &texts).unwrap();
+        assert!(exported.trim_end().ends_with(r#"textos,Textos,uno,Uno,30,Thai,,007,"""true""","""\""Go\""""",""" a ""","""23""","""[1]""""#));
+        let copy = MemoryStore::new(Vec::new());
+        import_teams(&exported, DataFormat::Csv, ImportMode::Merge, false, &copy).unwrap();
+        assert_eq!(copy.list().unwrap(), texts.list().unwrap());
+        let extra = copy.get("textos").unwrap().unwrap().extra;
+        assert_eq!((extra.get("code"), extra.get("flag")), (Some(&json!("007")), Some(&json!("true"))));
+
+        // Una hoja de cálculo con un cambio, una escudería nueva y sin la escudería sin pilotos
+        let sheet = "\u{feff}team_name,driver_name,driver_age,driver_nationality\n\
+            \"Red Bull, Racing\",\"Max \"\"Mad Max\"\" Verstappen\",28,Dutch\n\
+            Williams,Alex Albon,28,Thai\n\
+            Williams,Carlos Sainz,30,Spanish\n";
+        let (preview, audited) = import_teams(sheet, DataFormat::Csv, ImportMode::Replace, true, &store).unwrap();
+        assert!(audited.is_empty(), "El dry run no se registra en la auditoría");
+        let summary: Vec<(ChangeKind, &str)> = preview.changes.iter().map(|c| (c.change, c.id.as_str())).collect();
+        assert_eq!(summary, vec![(ChangeKind::Updated, "red-bull-racing"), (ChangeKind::Created, "williams"), (ChangeKind::Deleted, "sin-pilotos")]);
+        assert!(preview.changes[0].diff.iter().any(|op| json!(op) == json!({ "op": "replace", "path": "/drivers/0/age", "value": 28 })));
+        assert_eq!(store.list().unwrap(), teams, "El dry run no modifica los datos");
+
+        let (applied, audited) = import_teams(sheet, DataFormat::Csv, ImportMode::Replace, false, &store).unwrap();
+        let audited: Vec<(&str, bool, bool)> = audited.iter().map(|c| (c.operation.as_str(), c.before.is_some(), c.after.is_some())).collect();
+        assert_eq!(audited, vec![("import_team", true, true), ("import_team", false, true), ("import_team", true, false)]);
+        assert_eq!((applied.dry_run, applied.changes, applied.unchanged), (false, preview.changes, 0));
+        let names: Vec<String> = store.list().unwrap().into_iter().map(|team| team.name).collect();
+        assert_eq!(names, vec!["Red Bull, Racing", "Williams"]);
+        assert_eq!(store.get("red-bull-racing").unwrap().unwrap().drivers[0].extra.get("number"), None);
+
+        // Errores con la fila y la columna, columnas desconocidas y escuderías inválidas o repetidas
+        let fields = |text: &str, format: DataFormat| {
+            let error = import_teams(text, format, ImportMode::Merge, true, &store).unwrap_err();
+            error.downcast_ref::<ValidationError>().unwrap().errors.iter().map(|e| e.field.clone()).collect::<Vec<_>>()
+        };
+        assert_eq!(fields("team_name,driver_name,driver_age\nA,Uno,viejo\n,Dos,30\n", DataFormat::Csv), vec!["rows[0].driver_age", "rows[1].team_name"]);
+        assert_eq!(fields("team_name,driver_nacionality\n", DataFormat::Csv), vec!["header"]);
+        assert_eq!(fields("team_name,driver_age\nA,30\n", DataFormat::Csv), vec!["teams[0].drivers[0]"]);
+        assert_eq!(fields("{\"name\": \"A\", \"drivers\": []}\n\n{\"name\": \"A\", \"drivers\": []}\n", DataFormat::Ndjson), vec!["teams[1].name"]);
+        assert_eq!(fields("teams:\n  - name: B\n    drivers:\n      - name: Uno\n        age: 5\n        nationality: X\n", DataFormat::Yaml), vec!["teams[0].drivers[0].age"]);
+        assert_eq!(fields("{\"teams\": [{\"drivers\": []}]}", DataFormat::Json), vec!["teams[0]"]);
+        assert!(DataFormat::parse("xlsx").is_err());
+        assert_eq!(DataFormat::from_content_type("text/csv; charset=utf-8").unwrap(), DataFormat::Csv);
+        assert!(DataFormat::from_content_type("application/xml").is_err());
+    }
+
+    #[test]
+    fn test_content_negotiation() {
+        let negotiate = |accept: &str| Representation::negotiate(Some(accept)).ok();
+
+        // Sin Accept, con comodines o con la misma calidad se prefiere JSON
+        assert_eq!(Representation::negotiate(None).unwrap(), Representation::Json);
+        assert_eq!(negotiate("*/*"), Some(Representation::Json));
+        assert_eq!(negotiate("application/*"), Some(Representation::Json));
+        assert_eq!(negotiate("application/json"), Some(Representation::Json));
+        assert_eq!(negotiate("application/json; pretty=true"), Some(Representation::PrettyJson));
+        // La calidad decide; con la misma calidad, el orden de Accept
+        assert_eq!(negotiate("application/json;q=0.5, text/csv"), Some(Representation::Csv));
+        assert_eq!(negotiate("text/xml, text/csv"), Some(Representation::Xml));
+        assert_eq!(negotiate("application/json;q=0.2, application/msgpack;q=0.9, */*;q=0.1"), Some(Representation::MessagePack));
+        // El tipo más específico gana sobre el comodín, aunque tenga q=0 (y excluye también al JSON con sangría)
+        assert_eq!(negotiate("*/*, application/json;q=0"), Some(Representation::Csv));
+        assert_eq!(negotiate("text/*;q=0.3, text/csv;q=0, application/xml;q=0.1"), Some(Representation::Xml));
+        // Ningún formato aceptable (los tipos mal formados se ignoran)
+        assert!(negotiate("text/html").is_none());
+        assert!(negotiate("application/json;q=0").is_none());
+        assert!(negotiate("application/json;q=2, html").is_none());
+
+        let value = json!({
+            "teams": [
+                { "id": "red-bull", "name": "Red Bull <Racing>", "drivers": [{ "name": "Max", "age": 27 }] },
+                { "id": "williams", "name": "Williams", "drivers": [] }
+            ],
+            "total": 2,
+            "next_cursor": null
+        });
+        let text = |representation: Representation| String::from_utf8(representation.serialize(&value).unwrap()).unwrap();
+        assert_eq!(text(Representation::Json), value.to_string());
+        assert_eq!(serde_json::from_str::<Value>(&text(Representation::PrettyJson)).unwrap(), value);
+        assert!(text(Representation::PrettyJson).contains("\n  \"next_cursor\": null"));
+        // CSV: una fila por elemento de la lista, con los campos anidados aplanados
+        assert_eq!(
+            text(Representation::Csv),
+            "drivers.0.age,drivers.0.name,id,name,drivers\n27,Max,red-bull,Red Bull <Racing>,\n,,williams,Williams,\n"
+        );
+        assert_eq!(
+            String::from_utf8(Representation::Csv.serialize(&json!({ "team": { "id": "a", "tags": ["x", "y"] } })).unwrap()).unwrap(),
+            "id,tags.0,tags.1\na,x,y\n"
+        );
+        assert_eq!(
+            text(Representation::Xml),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?><response><next_cursor/><teams>\
+             <item><drivers><item><age>27</age><name>Max</name></item></drivers><id>red-bull</id><name>Red Bull &lt;Racing&gt;</name></item>\
+             <item><drivers></drivers><id>williams</id><name>Williams</name></item></teams><total>2</total></response>"
+        );
+        assert!(String::from_utf8(Representation::Xml.serialize(&json!({ "2024": 1, "xml": 2 })).unwrap())
+            .unwrap()
+            .ends_with("<response><entry key=\"2024\">1</entry><entry key=\"xml\">2</entry></response>"));
+        let packed: Value = rmp_serde::from_slice(&Representation::MessagePack.serialize(&value).unwrap()).unwrap();
+        assert_eq!(packed, value);
+
+        // Solo se convierten las respuestas JSON
+        let response = "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nSet-Cookie: session=s; Max-Age=60; HttpOnly\r\n\r\n{\"message\":\"ok\"}";
+        assert_eq!(
+            String::from_utf8(render(response, Representation::Xml)).unwrap(),
+            "HTTP/1.1 200 OK\r\nContent-Type: application/xml; charset=utf-8\r\nSet-Cookie: session=s; Max-Age=60; HttpOnly\r\nVary: Accept\r\n\r\n\
+             <?xml version=\"1.0\" encoding=\"UTF-8\"?><response><message>ok</message></response>"
+        );
+        assert!(String::from_utf8(render(response, Representation::Json)).unwrap().ends_with("Vary: Accept\r\n\r\n{\"message\":\"ok\"}"));
+        let events = "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\n\r\n";
+        assert_eq!(render(events, Representation::Csv), events.as_bytes());
+    }
+}
@@ -1,3 +1,6 @@
 pub mod server;
 pub mod thread_pool;
-pub mod routes;
\ No newline at end of file
+pub mod routes;
+pub mod sse;
+pub mod websocket;
+pub mod negotiation;
@@ -0,0 +1,325 @@
+use serde_json::Value;
+use std::error::Error;
+use std::fmt;
+
+// Formatos en los que se puede responder el mismo JSON de la API, en orden de preferencia
+// del servidor (se usa para desempatar cuando Accept los acepta con la misma calidad)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Representation {
+    Json,
+    // JSON con sangría: Accept: application/json; pretty=true
+    PrettyJson,
+    Csv,
+    Xml,
+    MessagePack,
+}
+
+// Ningún formato disponible es aceptable según el encabezado Accept
+#[derive(Debug)]
+pub struct NotAcceptable {
+    pub accept: String,
+}
+
+impl fmt::Display for NotAcceptable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Ningún formato disponible es aceptable para Accept: {}", self.accept)
+    }
+}
+
+impl Error for NotAcceptable {}
+
+// Tipo de medio de Accept (p. ej. "text/*;q=0.5")
+struct MediaRange {
+    kind: String,
+    subtype: String,
+    params: Vec<(String, String)>,
+    quality: f32,
+}
+
+impl Representation {
+    pub const ALL: [Representation; 5] = [
+        Representation::Json,
+        Representation::PrettyJson,
+        Representation::Csv,
+        Representation::Xml,
+        Representation::MessagePack,
+    ];
+
+    // Elegir el formato según Accept; sin el encabezado se responde JSON
+    pub fn negotiate(accept: Option<&str>) -> Result<Representation, NotAcceptable> {
+        let Some(accept) = accept.filter(|accept| !accept.trim().is_empty()) else {
+            return Ok(Representation::Json);
+        };
+        let ranges = parse_accept(accept);
+
+        // Calidad de cada formato: la del tipo más específico de Accept que lo incluye
+        // (y su posición en Accept, para desempatar)
+        let mut best: Option<(Representation, f32, usize)> = None;
+        for representation in Representation::ALL {
+            let matched = representation
+                .media_types()
+                .iter()
+                .flat_map(|media_type| {
+                    ranges
+                        .iter()
+                        .enumerate()
+                        .filter_map(move |(i, range)| range.specificity(media_type).map(|specificity| (specificity, i, range.quality)))
+                })
+                .min_by_key(|&(specificity, i, _)| (std::cmp::Reverse(specificity), i));
+            let Some((_, index, quality)) = matched else {
+                continue;
+            };
+            let better = match best {
+                None => true,
+                Some((_, best_quality, best_index)) => quality > best_quality || (quality == best_quality && index < best_index),
+            };
+            if quality > 0.0 && better {
+                best = Some((representation, quality, index));
+            }
+        }
+
+        best.map(|(representation, _, _)| representation)
+            .ok_or_else(|| NotAcceptable { accept: accept.to_string() })
+    }
+
+    // Nombre corto, usado en el ETag de las representaciones que no son JSON
+    pub fn name(self) -> &'static str {
+        match self {
+            Representation::Json => "json",
+            Representation::PrettyJson => "pretty",
+            Representation::Csv => "csv",
+            Representation::Xml => "xml",
+            Representation::MessagePack => "msgpack",
+        }
+    }
+
+    // Tipos de medio de Accept que corresponden a este formato
+    pub fn media_types(self) -> &'static [&'static str] {
+        match self {
+            Representation::Json => &["application/json"],
+            Representation::PrettyJson => &["application/json; pretty=true"],
+            Representation::Csv => &["text/csv"],
+            Representation::Xml => &["application/xml", "text/xml"],
+            Representation::MessagePack => &["application/msgpack", "application/x-msgpack", "application/vnd.msgpack"],
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Representation::Json | Representation::PrettyJson => "application/json",
+            Representation::Csv => "text/csv; charset=utf-8",
+            Representation::Xml => "application/xml; charset=utf-8",
+            Representation::MessagePack => "application/msgpack",
+        }
+    }
+
+    pub fn serialize(self, value: &Value) -> Result<Vec<u8>, Box<dyn Error>> {
+        match self {
+            Representation::Json => Ok(serde_json::to_vec(value)?),
+            Representation::PrettyJson => Ok(serde_json::to_vec_pretty(value)?),
+            Representation::Csv => write_csv(value),
+            Representation::Xml => Ok(write_xml(value).into_bytes()),
+            Representation::MessagePack => Ok(rmp_serde::to_vec_named(value)?),
+        }
+    }
+}
+
+// Respuesta HTTP completa en el formato elegido. Solo se convierten las respuestas JSON
+// (las demás, como las de eventos o exportaciones, ya tienen su propio formato).
+pub fn render(response: &str, representation: Representation) -> Vec<u8> {
+    let Some((head, body)) = response.split_once("\r\n\r\n") else {
+        return response.as_bytes().to_vec();
+    };
+    let is_content_type = |line: &str| {
+        line.split_once(':')
+            .is_some_and(|(name, _)| name.trim().eq_ignore_ascii_case("Content-Type"))
+    };
+    let is_json = head.split("\r\n").any(|line| {
+        is_content_type(line) && line.split_once(':').is_some_and(|(_, value)| value.trim() == "application/json")
+    });
+    if !is_json {
+        return response.as_bytes().to_vec();
+    }
+
+    let body = match representation {
+        Representation::Json => body.as_bytes().to_vec(),
+        _ => match serde_json::from_str::<Value>(body).map_err(Box::from).and_then(|value| representation.serialize(&value)) {
+            Ok(body) => body,
+            // Un cuerpo que no es JSON válido se envía como está
+            Err(_) => return response.as_bytes().to_vec(),
+        },
+    };
+    let head: Vec<String> = head
+        .split("\r\n")
+        .map(|line| match is_content_type(line) {
+            true => format!("Content-Type: {}", representation.content_type()),
+            false => line.to_string(),
+        })
+        .collect();
+
+    let mut rendered = format!("{}\r\nVary: Accept\r\n\r\n", head.join("\r\n")).into_bytes();
+    rendered.extend(body);
+    rendered
+}
+
+// Tipos de medio de Accept separados por comas, con sus parámetros; los mal formados se ignoran
+fn parse_accept(accept: &str) -> Vec<MediaRange> {
+    accept
+        .split(',')
+        .filter_map(|part| {
+            let mut parts = part.split(';');
+            let (kind, subtype) = parts.next()?.trim().split_once('/')?;
+            let mut range = MediaRange {
+                kind: kind.trim().to_ascii_lowercase(),
+                subtype: subtype.trim().to_ascii_lowercase(),
+                params: Vec::new(),
+                quality: 1.0,
+            };
+            for param in parts {
+                let (name, value) = param.split_once('=')?;
+                let (name, value) = (name.trim().to_ascii_lowercase(), value.trim().trim_matches('"'));
+                if name == "q" {
+                    range.quality = value.parse::<f32>().ok().filter(|q| (0.0..=1.0).contains(q))?;
+                } else {
+                    range.params.push((name, value.to_ascii_lowercase()));
+                }
+            }
+            Some(range)
+        })
+        .collect()
+}
+
+impl MediaRange {
+    // Qué tan específicamente incluye al tipo de medio ("tipo/subtipo; parámetros"):
+    // */* < tipo/* < tipo/subtipo < tipo/subtipo con parámetros. None si no lo incluye.
+    fn specificity(&self, media_type: &str) -> Option<usize> {
+        let mut parts = media_type.split(';');
+        let (kind, subtype) = parts.next()?.split_once('/')?;
+        let params: Vec<(&str, &str)> = parts.filter_map(|param| param.trim().split_once('=')).collect();
+
+        match (self.kind.as_str(), self.subtype.as_str()) {
+            ("*", "*") => Some(0),
+            (range_kind, "*") if range_kind == kind => Some(1),
+            (range_kind, range_subtype) if range_kind == kind && range_subtype == subtype => {
+                // Los parámetros pedidos tienen que estar en el tipo de medio
+                let included = self.params.iter().all(|(name, value)| params.contains(&(name.as_str(), value.as_str())));
+                included.then_some(2 + self.params.len())
+            }
+            _ => None,
+        }
+    }
+}
+
+// CSV con una fila por elemento: la lista de la respuesta ({"teams": [...], "total": ...}),
+// el único objeto ({"team": {...}}) o la respuesta completa. Los objetos y listas anidados
+// se aplanan en columnas con puntos ("drivers.0.name").
+fn write_csv(value: &Value) -> Result<Vec<u8>, Box<dyn Error>> {
+    let rows: Vec<&Value> = match value {
+        Value::Array(items) => items.iter().collect(),
+        Value::Object(fields) => match fields.values().find_map(Value::as_array) {
+            Some(items) => items.iter().collect(),
+            None if fields.len() == 1 && fields.values().all(Value::is_object) => fields.values().collect(),
+            None => vec![value],
+        },
+        _ => vec![value],
+    };
+
+    let rows: Vec<Vec<(String, String)>> = rows
+        .into_iter()
+        .map(|row| {
+            let mut cells = Vec::new();
+            flatten("", row, &mut cells);
+            cells
+        })
+        .collect();
+    let mut columns: Vec<&str> = Vec::new();
+    for (column, _) in rows.iter().flatten() {
+        if !columns.contains(&column.as_str()) {
+            columns.push(column);
+        }
+    }
+
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(&columns)?;
+    for row in &rows {
+        writer.write_record(columns.iter().map(|column| {
+            row.iter().find(|(name, _)| name == column).map_or("", |(_, cell)| cell.as_str())
+        }))?;
+    }
+    Ok(writer.into_inner()?)
+}
+
+fn flatten(prefix: &str, value: &Value, cells: &mut Vec<(String, String)>) {
+    let column = |key: &str| if prefix.is_empty() { key.to_string() } else { format!("{}.{}", prefix, key) };
+    match value {
+        Value::Object(fields) if !fields.is_empty() => {
+            for (key, value) in fields {
+                flatten(&column(key), value, cells);
+            }
+        }
+        Value::Array(items) if !items.is_empty() => {
+            for (i, value) in items.iter().enumerate() {
+                flatten(&column(&i.to_string()), value, cells);
+            }
+        }
+        Value::Object(_) | Value::Array(_) | Value::Null => cells.push((column_name(prefix), String::new())),
+        Value::String(text) => cells.push((column_name(prefix), text.clone())),
+        _ => cells.push((column_name(prefix), value.to_string())),
+    }
+}
+
+// Un valor suelto (no es un objeto) queda en la columna "value"
+fn column_name(prefix: &str) -> String {
+    if prefix.is_empty() { "value".to_string() } else { prefix.to_string() }
+}
+
+// XML con la respuesta dentro de <response>: cada campo es un elemento y cada elemento
+// de una lista es un <item>. Las claves que no son nombres XML válidos quedan como <entry key="...">.
+fn write_xml(value: &Value) -> String {
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>");
+    write_element("response", value, &mut xml);
+    xml
+}
+
+fn write_element(name: &str, value: &Value, xml: &mut String) {
+    let (open, close) = if is_xml_name(name) {
+        (name.to_string(), name)
+    } else {
+        (format!("entry key=\"{}\"", escape_xml(name)), "entry")
+    };
+    match value {
+        Value::Null => xml.push_str(&format!("<{}/>", open)),
+        Value::Object(fields) => {
+            xml.push_str(&format!("<{}>", open));
+            for (key, value) in fields {
+                write_element(key, value, xml);
+            }
+            xml.push_str(&format!("</{}>", close));
+        }
+        Value::Array(items) => {
+            xml.push_str(&format!("<{}>", open));
+            for value in items {
+                write_element("item", value, xml);
+            }
+            xml.push_str(&format!("</{}>", close));
+        }
+        Value::String(text) => xml.push_str(&format!("<{}>{}</{}>", open, escape_xml(text), close)),
+        _ => xml.push_str(&format!("<{}>{}</{}>", open, value, close)),
+    }
+}
+
+fn is_xml_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    let starts_well = chars.next().is_some_and(|c| c.is_alphabetic() || c == '_');
+    starts_well
+        && chars.all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.'))
+        && !name.to_ascii_lowercase().starts_with("xml")
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
@@ -1,12 +1,13 @@
-use core::hash;
 use std::sync::Arc;
 use std::collections::HashMap;
-use std::io::{Write};
+use std::io::Write;
 use std::net::TcpStream;
+use std::thread;
 
 use serde_json::Value;
 
-use crate::utils::{print_hashmap, clean_string};
+use crate::server_http::websocket::{self, WebSocket};
+use crate::utils::clean_string;
 
 pub type Handler = Arc<Box<dyn Fn(&mut TcpStream, HashMap<String, Value>) + Send + Sync>>;
 
@@ -15,6 +16,12 @@ pub struct Router {
     routes: HashMap<String, Handler>, // No es necesario bloquear aquí
 }
 
+impl Default for Router {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Router {
     pub fn new() -> Self {
         Router {
@@ -37,6 +44,22 @@ impl Router {
         self.routes.insert(path.to_string(), Arc::new(Box::new(handler)));
     }
 
+    // Ruta GET que acepta `Upgrade: websocket`. Después del handshake cada conexión se atiende
+    // en su propio hilo, así una conexión abierta no ocupa un hilo del pool.
+    pub fn websocket<F>(&mut self, path: &str, handler: F)
+    where
+        F: Fn(WebSocket, HashMap<String, Value>) + Send + Sync + 'static,
+    {
+        let handler = Arc::new(handler);
+        self.add_route(&format!("GET {}", path), move |stream: &mut TcpStream, request: HashMap<String, Value>| {
+            // Si el handshake falla ya se respondió con el error
+            if let Ok(socket) = websocket::accept(stream, &request) {
+                let handler = Arc::clone(&handler);
+                thread::spawn(move || handler(socket, request));
+            }
+        });
+    }
+
     pub fn handle_request(&self, request: HashMap<String, Value>, stream: &mut TcpStream) {
         let path = request.get("path").unwrap().as_str().unwrap();
         let method = request.get("method").unwrap().as_str().unwrap();
@@ -45,23 +68,32 @@ impl Router {
         if let Some(handler) = self.routes.get(key.as_str()) {
             handler(stream, request);
         } else {
-            // Verificar rutas con parámetros
-            for (route_key, handler) in &self.routes {
-                if let Some(captures) = self.match_route(route_key, &key) {
-                    let mut request_with_params = request.clone();
-                    
-                    // Convert HashMap to serde_json::Map
-                    let params_map: serde_json::Map<String, Value> = captures.into_iter().collect();
-                    
-                    request_with_params.insert("params".to_string(), Value::Object(params_map));
-                    handler(stream, request_with_params);
-                    return;
-                }
+            // Verificar rutas con parámetros. Si varias coinciden se usa la más específica:
+            // la que tiene un segmento fijo donde las otras tienen un parámetro.
+            let matched = self
+                .routes
+                .iter()
+                .filter_map(|(route_key, handler)| {
+                    self.match_route(route_key, &key).map(|captures| (route_key, handler, captures))
+                })
+                .min_by_key(|(route_key, _, _)| {
+                    route_key.split('/').map(|part| part.starts_with(':')).collect::<Vec<bool>>()
+                });
+
+            if let Some((_, handler, captures)) = matched {
+                let mut request_with_params = request.clone();
+
+                // Convert HashMap to serde_json::Map
+                let params_map: serde_json::Map<String, Value> = captures.into_iter().collect();
+
+                request_with_params.insert("params".to_string(), Value::Object(params_map));
+                handler(stream, request_with_params);
+                return;
             }
     
-            let response = format!("HTTP/1.1 404 NOT FOUND\r\n\r\n");
+            let response = "HTTP/1.1 404 NOT FOUND\r\n\r\n";
             println!("Ruta no encontrada");
-            stream.write(response.as_bytes()).unwrap();
+            stream.write_all(response.as_bytes()).unwrap();
         }
     }
     
@@ -80,9 +112,8 @@ impl Router {
         let mut params = HashMap::new();
     
         for (route_part, path_part) in route_parts.iter().zip(path_parts.iter()) {
-            if route_part.starts_with(':') {
+            if let Some(param_name) = route_part.strip_prefix(':') {
                 // Capture the parameter
-                let param_name = &route_part[1..]; // remove ':'
                 params.insert(param_name.to_string(), Value::String(path_part.to_string()));
             } else if *route_part != *path_part {
                 return None; // No match
@@ -1,14 +1,13 @@
 use serde_json::Value;
 
-use crate::utils::{parse_request_into_hashmap, print_hashmap};
-use crate::json_hashmaps::f1_data_hashmap::get_f1_data;
+use crate::utils::parse_request_into_hashmap;
 use crate::server_http::thread_pool::ThreadPool;
-use crate::server_http::routes::{Router, Handler}; 
+use crate::server_http::routes::Router;
+use crate::server_http::websocket::WebSocket;
 
 use std::collections::HashMap;
 use std::net::{TcpListener, TcpStream};
-use std::io::{BufReader, Write};
-use std::sync::{Arc, Mutex};
+use std::io::BufReader;
 
 pub struct Server {
     router: Router,
@@ -31,6 +30,13 @@ impl Server {
         self.router.add_route(&pair, handler);
     }
 
+    pub fn websocket<F>(&mut self, path: &str, handler: F)
+    where
+        F: Fn(WebSocket, HashMap<String, Value>) + Send + Sync + 'static,
+    {
+        self.router.websocket(path, handler);
+    }
+
     pub fn start(&self, host: &str, port: u16) {
         let listener = TcpListener::bind(format!("{}:{}", host, port)).unwrap();
         println!("Server listening on {}:{}", host, port);
@@ -52,10 +58,10 @@ impl Server {
 }
 
 fn handle_connection(mut stream: TcpStream, router: &Router) {
-    let mut reader = BufReader::new(&stream);
-    
+    let reader = BufReader::new(&stream);
+
     let request_parts = parse_request_into_hashmap(reader);
-    
+
 
     // Enviar el request parseado al router
     router.handle_request(request_parts, &mut stream);
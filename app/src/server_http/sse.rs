@@ -0,0 +1,224 @@
+use std::collections::VecDeque;
+use std::io::{ErrorKind, Write};
+use std::net::TcpStream;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde_json::Value;
+
+// Bytes pendientes que se guardan para un suscriptor; los eventos siguientes esperan a que lea
+const MAX_BUFFERED: usize = 1024 * 1024;
+
+// Tiempo sin que el socket de un suscriptor acepte nada antes de desconectarlo
+const STALL_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Cada cuánto se reintenta enviar lo pendiente a los suscriptores con el socket lleno
+const RETRY_INTERVAL: Duration = Duration::from_millis(50);
+
+// Evento de text/event-stream. `topic` elige a qué suscriptores se envía (p. ej. el conjunto de datos)
+#[derive(Debug, Clone, PartialEq)]
+pub struct SseEvent {
+    pub id: u64,
+    pub topic: String,
+    pub event: String,
+    pub data: String,
+}
+
+impl SseEvent {
+    fn format(&self) -> String {
+        let mut message = format!("id: {}\nevent: {}\n", self.id, self.event);
+        for line in self.data.lines() {
+            message.push_str(&format!("data: {}\n", line));
+        }
+        message.push('\n');
+        message
+    }
+}
+
+struct Subscriber {
+    // Socket no bloqueante: escribir nunca detiene al hilo de envío
+    stream: TcpStream,
+    topic: String,
+    // Último evento que ya está en el búfer o enviado (o que el cliente ya tenía, según Last-Event-ID)
+    last_id: u64,
+    // Lo que todavía no entró en el socket
+    buffer: Vec<u8>,
+    // Desde cuándo el socket no acepta nada de lo pendiente
+    stalled_since: Option<Instant>,
+}
+
+impl Subscriber {
+    // Enviar lo pendiente hasta que el socket no acepte más. False si hay que desconectarlo:
+    // la conexión se cerró o lleva STALL_TIMEOUT sin aceptar nada.
+    fn flush(&mut self) -> bool {
+        let pending = self.buffer.len();
+        while !self.buffer.is_empty() {
+            match self.stream.write(&self.buffer) {
+                Ok(0) => return false,
+                Ok(written) => {
+                    self.buffer.drain(..written);
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(_) => return false,
+            }
+        }
+        if self.buffer.len() < pending || self.buffer.is_empty() {
+            self.stalled_since = None;
+            return true;
+        }
+        self.stalled_since.get_or_insert_with(Instant::now).elapsed() < STALL_TIMEOUT
+    }
+}
+
+#[derive(Default)]
+struct StreamState {
+    last_id: u64,
+    // Eventos recientes, para retomar desde Last-Event-ID
+    events: VecDeque<SseEvent>,
+    // Suscriptores que el hilo de envío todavía no tomó
+    new_subscribers: Vec<Subscriber>,
+}
+
+// Eventos enviados a conexiones abiertas (Server-Sent Events). Un solo hilo escribe a todos
+// los suscriptores, así cada conexión abierta no ocupa un hilo del pool. Las escrituras no
+// bloquean: lo que no entra en el socket de un suscriptor lento queda en su búfer y los demás
+// reciben los eventos sin esperarlo.
+pub struct EventStream {
+    capacity: usize,
+    state: Mutex<StreamState>,
+    changed: Condvar,
+}
+
+impl EventStream {
+    // `capacity` es la cantidad de eventos que se conservan para retomar una conexión
+    pub fn new(capacity: usize) -> Self {
+        EventStream {
+            capacity: capacity.max(1),
+            state: Mutex::new(StreamState::default()),
+            changed: Condvar::new(),
+        }
+    }
+
+    pub fn publish(&self, topic: &str, event: &str, data: &Value) -> u64 {
+        let mut state = self.state.lock().unwrap();
+        state.last_id += 1;
+        let event = SseEvent {
+            id: state.last_id,
+            topic: topic.to_string(),
+            event: event.to_string(),
+            data: data.to_string(),
+        };
+        state.events.push_back(event);
+        if state.events.len() > self.capacity {
+            state.events.pop_front();
+        }
+        self.changed.notify_all();
+        state.last_id
+    }
+
+    pub fn last_id(&self) -> u64 {
+        self.state.lock().unwrap().last_id
+    }
+
+    // Eventos posteriores a `after` que siguen guardados, esperando hasta `timeout` si todavía
+    // no hay ninguno. Para consumir los eventos dentro del proceso (p. ej. los webhooks).
+    pub fn wait_after(&self, after: u64, timeout: Duration) -> Vec<SseEvent> {
+        let state = self.state.lock().unwrap();
+        let (state, _) = self
+            .changed
+            .wait_timeout_while(state, timeout, |state| state.last_id <= after)
+            .unwrap();
+        state.events.iter().filter(|event| event.id > after).cloned().collect()
+    }
+
+    // Agregar una conexión que ya recibió los encabezados de la respuesta. Con `last_event_id`
+    // se le envían los eventos posteriores que siguen guardados; sin él, solo los nuevos.
+    pub fn subscribe(&self, stream: TcpStream, topic: &str, last_event_id: Option<u64>) {
+        if stream.set_nonblocking(true).is_err() {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        let last_id = last_event_id.unwrap_or(state.last_id);
+        state.new_subscribers.push(Subscriber {
+            stream,
+            topic: topic.to_string(),
+            last_id,
+            buffer: Vec::new(),
+            stalled_since: None,
+        });
+        self.changed.notify_all();
+    }
+
+    // Hilo que envía los eventos a los suscriptores. Cada `keep_alive` sin eventos se envía un
+    // comentario, para que los proxies no cierren la conexión y para detectar las cerradas.
+    pub fn start(self: Arc<Self>, keep_alive: Duration) {
+        thread::spawn(move || {
+            let mut subscribers: Vec<Subscriber> = Vec::new();
+            let mut sent_id = 0;
+            let mut last_keep_alive = Instant::now();
+            loop {
+                // Con envíos pendientes se despierta antes para reintentarlos
+                let pending = subscribers.iter().any(|subscriber| !subscriber.buffer.is_empty());
+                let wait = match pending {
+                    true => RETRY_INTERVAL,
+                    false => keep_alive.saturating_sub(last_keep_alive.elapsed()),
+                };
+                let (events, latest_id) = {
+                    let state = self.state.lock().unwrap();
+                    let (mut state, _) = self
+                        .changed
+                        .wait_timeout_while(state, wait, |state| {
+                            state.last_id == sent_id && state.new_subscribers.is_empty()
+                        })
+                        .unwrap();
+                    subscribers.append(&mut state.new_subscribers);
+                    let first_needed = subscribers.iter().map(|s| s.last_id).min().unwrap_or(state.last_id);
+                    let events: Vec<SseEvent> = state.events.iter().filter(|e| e.id > first_needed).cloned().collect();
+                    (events, state.last_id)
+                };
+                sent_id = latest_id;
+                let keep_alive_due = last_keep_alive.elapsed() >= keep_alive;
+                if keep_alive_due {
+                    last_keep_alive = Instant::now();
+                }
+
+                let oldest_id = events.first().map_or(latest_id + 1, |event| event.id);
+                subscribers.retain_mut(|subscriber| {
+                    let queued = queue_events(subscriber, &events, oldest_id, latest_id);
+                    if !queued && keep_alive_due && subscriber.buffer.is_empty() {
+                        subscriber.buffer.extend_from_slice(b": keep-alive\n\n");
+                    }
+                    subscriber.flush()
+                });
+            }
+        });
+    }
+}
+
+// Agregar al búfer del suscriptor los eventos que le faltan, hasta MAX_BUFFERED; el resto se
+// agrega cuando lea. Si se perdieron eventos (ya no están guardados, o el Last-Event-ID es de
+// antes de reiniciar el servidor) se envía `reset` para que vuelva a cargar todo.
+// Devuelve true si se agregó algo.
+fn queue_events(subscriber: &mut Subscriber, events: &[SseEvent], oldest_id: u64, latest_id: u64) -> bool {
+    if subscriber.last_id > latest_id || subscriber.last_id + 1 < oldest_id {
+        subscriber.buffer.extend_from_slice(format!("id: {}\nevent: reset\ndata: {{}}\n\n", latest_id).as_bytes());
+        subscriber.last_id = latest_id;
+        return true;
+    }
+
+    let mut queued = false;
+    let already_queued = subscriber.last_id;
+    for event in events.iter().filter(|event| event.id > already_queued) {
+        if subscriber.buffer.len() >= MAX_BUFFERED {
+            break;
+        }
+        if event.topic == subscriber.topic {
+            subscriber.buffer.extend_from_slice(event.format().as_bytes());
+            queued = true;
+        }
+        subscriber.last_id = event.id;
+    }
+    queued
+}
@@ -0,0 +1,432 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::io::{Read, Write};
+use std::net::{Shutdown, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde_json::Value;
+use sha1::{Digest, Sha1};
+
+// Valor fijo que se combina con Sec-WebSocket-Key (RFC 6455, sección 1.3)
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+// Tamaño máximo de un mensaje, sumando sus fragmentos
+pub const MAX_MESSAGE_SIZE: usize = 16 * 1024 * 1024;
+
+// Códigos de cierre (RFC 6455, sección 7.4.1)
+pub const CLOSE_NORMAL: u16 = 1000;
+pub const CLOSE_PROTOCOL_ERROR: u16 = 1002;
+pub const CLOSE_INVALID_DATA: u16 = 1007;
+pub const CLOSE_TOO_BIG: u16 = 1009;
+
+const OPCODE_CONTINUATION: u8 = 0x0;
+const OPCODE_TEXT: u8 = 0x1;
+const OPCODE_BINARY: u8 = 0x2;
+const OPCODE_CLOSE: u8 = 0x8;
+const OPCODE_PING: u8 = 0x9;
+const OPCODE_PONG: u8 = 0xA;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CloseFrame {
+    pub code: u16,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Message {
+    Text(String),
+    Binary(Vec<u8>),
+    Ping(Vec<u8>),
+    Pong(Vec<u8>),
+    // Sin código si el otro lado no lo envió
+    Close(Option<CloseFrame>),
+}
+
+// El otro lado no respetó el protocolo; la conexión se cierra con `code`
+#[derive(Debug)]
+pub struct ProtocolError {
+    pub code: u16,
+    pub message: String,
+}
+
+impl ProtocolError {
+    fn new(code: u16, message: &str) -> Self {
+        ProtocolError { code, message: message.to_string() }
+    }
+}
+
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Error de WebSocket ({}): {}", self.code, self.message)
+    }
+}
+
+impl Error for ProtocolError {}
+
+// El servidor no enmascara lo que envía y exige que el cliente lo haga; el cliente al revés
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    Server,
+    Client,
+}
+
+struct Frame {
+    fin: bool,
+    opcode: u8,
+    payload: Vec<u8>,
+}
+
+// Valor de Sec-WebSocket-Accept para una Sec-WebSocket-Key
+pub fn accept_key(key: &str) -> String {
+    let mut hasher = Sha1::new();
+    hasher.update(key.trim().as_bytes());
+    hasher.update(WEBSOCKET_GUID.as_bytes());
+    BASE64.encode(hasher.finalize())
+}
+
+// Responder el handshake de una solicitud `Upgrade: websocket`. Si la solicitud no es válida
+// se responde 400 (o 426 si pide otra versión del protocolo) y se devuelve el error.
+pub fn accept(stream: &mut TcpStream, request: &HashMap<String, Value>) -> Result<WebSocket, Box<dyn Error>> {
+    let header = |name: &str| {
+        request
+            .get("headers")
+            .and_then(Value::as_object)
+            .and_then(|headers| headers.iter().find(|(key, _)| key.eq_ignore_ascii_case(name)))
+            .and_then(|(_, value)| value.as_str())
+            .unwrap_or_default()
+    };
+    let has_token = |value: &str, token: &str| value.split(',').any(|part| part.trim().eq_ignore_ascii_case(token));
+    let key_is_valid = BASE64.decode(header("Sec-WebSocket-Key").trim()).is_ok_and(|key| key.len() == 16);
+
+    let rejection = if !has_token(header("Upgrade"), "websocket") || !has_token(header("Connection"), "upgrade") {
+        Some(("400 Bad Request", "", "Se esperaba Upgrade: websocket"))
+    } else if header("Sec-WebSocket-Version").trim() != "13" {
+        Some(("426 Upgrade Required", "Sec-WebSocket-Version: 13\r\n", "Solo se admite la versión 13"))
+    } else if !key_is_valid {
+        Some(("400 Bad Request", "", "Sec-WebSocket-Key inválida"))
+    } else {
+        None
+    };
+    if let Some((status, headers, error)) = rejection {
+        let body = serde_json::json!({ "error": error }).to_string();
+        let response = format!(
+            "HTTP/1.1 {}\r\n{}Content-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status, headers, body.len(), body
+        );
+        stream.write_all(response.as_bytes())?;
+        stream.flush()?;
+        return Err(error.into());
+    }
+
+    let response = format!(
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+        accept_key(header("Sec-WebSocket-Key"))
+    );
+    stream.write_all(response.as_bytes())?;
+    stream.flush()?;
+    WebSocket::new(stream.try_clone()?, Role::Server)
+}
+
+// Lado que escribe de una conexión; se puede clonar para enviar mensajes desde otros hilos
+#[derive(Clone)]
+pub struct WebSocketSender {
+    stream: Arc<Mutex<TcpStream>>,
+    role: Role,
+    // Ya se envió el cierre: no se puede enviar nada más
+    closed: Arc<AtomicBool>,
+}
+
+impl WebSocketSender {
+    pub fn send(&self, message: &Message) -> Result<(), Box<dyn Error>> {
+        let (opcode, payload) = match message {
+            Message::Text(text) => (OPCODE_TEXT, text.as_bytes().to_vec()),
+            Message::Binary(data) => (OPCODE_BINARY, data.clone()),
+            Message::Ping(data) => (OPCODE_PING, data.clone()),
+            Message::Pong(data) => (OPCODE_PONG, data.clone()),
+            Message::Close(frame) => {
+                let frame = frame.clone().unwrap_or(CloseFrame { code: CLOSE_NORMAL, reason: String::new() });
+                return self.close(frame.code, &frame.reason);
+            }
+        };
+        if opcode >= OPCODE_CLOSE && payload.len() > 125 {
+            return Err("Los mensajes de control tienen como máximo 125 bytes".into());
+        }
+        self.write_frame(opcode, &payload)
+    }
+
+    pub fn send_text(&self, text: &str) -> Result<(), Box<dyn Error>> {
+        self.send(&Message::Text(text.to_string()))
+    }
+
+    // Enviar el cierre; solo se envía una vez. El motivo se recorta a 123 bytes sin partir caracteres.
+    pub fn close(&self, code: u16, reason: &str) -> Result<(), Box<dyn Error>> {
+        let mut end = reason.len().min(123);
+        while !reason.is_char_boundary(end) {
+            end -= 1;
+        }
+        let mut payload = code.to_be_bytes().to_vec();
+        payload.extend(&reason.as_bytes()[..end]);
+        self.write_frame(OPCODE_CLOSE, &payload)
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::SeqCst)
+    }
+
+    // El lock hace que los mensajes enviados desde varios hilos no se mezclen
+    fn write_frame(&self, opcode: u8, payload: &[u8]) -> Result<(), Box<dyn Error>> {
+        let mut stream = self.stream.lock().unwrap();
+        if self.is_closed() {
+            return Err("La conexión WebSocket está cerrada".into());
+        }
+        if opcode == OPCODE_CLOSE {
+            self.closed.store(true, Ordering::SeqCst);
+        }
+        let mask = (self.role == Role::Client).then(random_mask);
+        stream.write_all(&encode_frame(opcode, payload, mask))?;
+        stream.flush()?;
+        Ok(())
+    }
+}
+
+// Conexión WebSocket (RFC 6455): un hilo lee con `read_message` y cualquiera envía con el `sender`
+pub struct WebSocket {
+    reader: TcpStream,
+    sender: WebSocketSender,
+    role: Role,
+    // Tipo y contenido de un mensaje fragmentado que todavía no termina
+    fragments: Option<(u8, Vec<u8>)>,
+}
+
+impl WebSocket {
+    pub fn new(stream: TcpStream, role: Role) -> Result<Self, Box<dyn Error>> {
+        Ok(WebSocket {
+            sender: WebSocketSender {
+                stream: Arc::new(Mutex::new(stream.try_clone()?)),
+                role,
+                closed: Arc::new(AtomicBool::new(false)),
+            },
+            reader: stream,
+            role,
+            fragments: None,
+        })
+    }
+
+    // Conectarse como cliente a ws://`address``path` (p. ej. "127.0.0.1:7000", "/api/live")
+    pub fn connect(address: &str, path: &str) -> Result<Self, Box<dyn Error>> {
+        let mut stream = TcpStream::connect(address)?;
+        let key = BASE64.encode(random_mask().repeat(4));
+        let request = format!(
+            "GET {} HTTP/1.1\r\nHost: {}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: {}\r\nSec-WebSocket-Version: 13\r\n\r\n",
+            path, address, key
+        );
+        stream.write_all(request.as_bytes())?;
+
+        // Leer la respuesta byte a byte para no consumir los primeros mensajes
+        let mut response = Vec::new();
+        let mut byte = [0u8; 1];
+        while !response.ends_with(b"\r\n\r\n") {
+            if stream.read(&mut byte)? == 0 {
+                return Err("El servidor cerró la conexión durante el handshake".into());
+            }
+            response.push(byte[0]);
+        }
+        let response = String::from_utf8_lossy(&response);
+        let accepted = response
+            .lines()
+            .find_map(|line| line.split_once(':').filter(|(name, _)| name.eq_ignore_ascii_case("Sec-WebSocket-Accept")))
+            .is_some_and(|(_, value)| value.trim() == accept_key(&key));
+        if !response.starts_with("HTTP/1.1 101") || !accepted {
+            return Err(format!("El servidor rechazó el WebSocket: {}", response.lines().next().unwrap_or_default()).into());
+        }
+        WebSocket::new(stream, Role::Client)
+    }
+
+    pub fn sender(&self) -> WebSocketSender {
+        self.sender.clone()
+    }
+
+    pub fn send(&self, message: &Message) -> Result<(), Box<dyn Error>> {
+        self.sender.send(message)
+    }
+
+    pub fn send_text(&self, text: &str) -> Result<(), Box<dyn Error>> {
+        self.sender.send_text(text)
+    }
+
+    pub fn close(&self, code: u16, reason: &str) -> Result<(), Box<dyn Error>> {
+        self.sender.close(code, reason)
+    }
+
+    // Siguiente mensaje. Los ping se responden con pong antes de devolverlos. Al recibir el
+    // cierre se responde (si no se había enviado) y se cierra la conexión TCP. Si el otro lado
+    // no respeta el protocolo se cierra con el código correspondiente y se devuelve el error.
+    pub fn read_message(&mut self) -> Result<Message, Box<dyn Error>> {
+        match self.next_message() {
+            Err(e) => {
+                if let Some(error) = e.downcast_ref::<ProtocolError>() {
+                    let _ = self.sender.close(error.code, &error.message);
+                    let _ = self.reader.shutdown(Shutdown::Both);
+                }
+                Err(e)
+            }
+            Ok(Message::Close(frame)) => {
+                if !self.sender.is_closed() {
+                    let code = frame.as_ref().map_or(CLOSE_NORMAL, |frame| frame.code);
+                    let _ = self.sender.close(code, "");
+                }
+                let _ = self.reader.shutdown(Shutdown::Both);
+                Ok(Message::Close(frame))
+            }
+            Ok(Message::Ping(data)) => {
+                // Si ya se envió el cierre no se puede responder, y no hace falta
+                let _ = self.sender.send(&Message::Pong(data.clone()));
+                Ok(Message::Ping(data))
+            }
+            message => message,
+        }
+    }
+
+    fn next_message(&mut self) -> Result<Message, Box<dyn Error>> {
+        loop {
+            let frame = read_frame(&mut self.reader, self.role)?;
+            match frame.opcode {
+                OPCODE_TEXT | OPCODE_BINARY if self.fragments.is_some() => {
+                    return Err(ProtocolError::new(CLOSE_PROTOCOL_ERROR, "Se esperaba la continuación del mensaje").into());
+                }
+                OPCODE_TEXT | OPCODE_BINARY if !frame.fin => self.fragments = Some((frame.opcode, frame.payload)),
+                OPCODE_TEXT | OPCODE_BINARY => return data_message(frame.opcode, frame.payload),
+                OPCODE_CONTINUATION => {
+                    let Some((_, data)) = self.fragments.as_mut() else {
+                        return Err(ProtocolError::new(CLOSE_PROTOCOL_ERROR, "Continuación sin mensaje").into());
+                    };
+                    if data.len() + frame.payload.len() > MAX_MESSAGE_SIZE {
+                        return Err(ProtocolError::new(CLOSE_TOO_BIG, "Mensaje demasiado grande").into());
+                    }
+                    data.extend(frame.payload);
+                    if frame.fin {
+                        let (opcode, data) = self.fragments.take().unwrap();
+                        return data_message(opcode, data);
+                    }
+                }
+                OPCODE_CLOSE => return Ok(Message::Close(close_frame(&frame.payload)?)),
+                OPCODE_PING => return Ok(Message::Ping(frame.payload)),
+                OPCODE_PONG => return Ok(Message::Pong(frame.payload)),
+                _ => return Err(ProtocolError::new(CLOSE_PROTOCOL_ERROR, "Tipo de mensaje desconocido").into()),
+            }
+        }
+    }
+}
+
+fn data_message(opcode: u8, data: Vec<u8>) -> Result<Message, Box<dyn Error>> {
+    if opcode == OPCODE_BINARY {
+        return Ok(Message::Binary(data));
+    }
+    String::from_utf8(data)
+        .map(Message::Text)
+        .map_err(|_| ProtocolError::new(CLOSE_INVALID_DATA, "El texto no es UTF-8 válido").into())
+}
+
+fn close_frame(payload: &[u8]) -> Result<Option<CloseFrame>, Box<dyn Error>> {
+    match payload {
+        [] => Ok(None),
+        [_] => Err(ProtocolError::new(CLOSE_PROTOCOL_ERROR, "Código de cierre incompleto").into()),
+        [high, low, reason @ ..] => {
+            let reason = std::str::from_utf8(reason)
+                .map_err(|_| ProtocolError::new(CLOSE_INVALID_DATA, "El motivo del cierre no es UTF-8 válido"))?;
+            Ok(Some(CloseFrame { code: u16::from_be_bytes([*high, *low]), reason: reason.to_string() }))
+        }
+    }
+}
+
+fn read_frame(stream: &mut impl Read, role: Role) -> Result<Frame, Box<dyn Error>> {
+    let mut header = [0u8; 2];
+    stream.read_exact(&mut header)?;
+    let fin = header[0] & 0x80 != 0;
+    let opcode = header[0] & 0x0F;
+    let masked = header[1] & 0x80 != 0;
+
+    if header[0] & 0x70 != 0 {
+        return Err(ProtocolError::new(CLOSE_PROTOCOL_ERROR, "Bits reservados en uso").into());
+    }
+    // Lo que envía el cliente siempre va enmascarado, y lo que envía el servidor nunca
+    if masked != (role == Role::Server) {
+        return Err(ProtocolError::new(CLOSE_PROTOCOL_ERROR, "Enmascarado incorrecto").into());
+    }
+
+    let len = match header[1] & 0x7F {
+        126 => {
+            let mut len = [0u8; 2];
+            stream.read_exact(&mut len)?;
+            u16::from_be_bytes(len) as u64
+        }
+        127 => {
+            let mut len = [0u8; 8];
+            stream.read_exact(&mut len)?;
+            u64::from_be_bytes(len)
+        }
+        len => len as u64,
+    };
+    if opcode >= OPCODE_CLOSE && (!fin || len > 125) {
+        return Err(ProtocolError::new(CLOSE_PROTOCOL_ERROR, "Mensaje de control inválido").into());
+    }
+    if len > MAX_MESSAGE_SIZE as u64 {
+        return Err(ProtocolError::new(CLOSE_TOO_BIG, "Mensaje demasiado grande").into());
+    }
+
+    let mut mask = [0u8; 4];
+    if masked {
+        stream.read_exact(&mut mask)?;
+    }
+    let mut payload = vec![0u8; len as usize];
+    stream.read_exact(&mut payload)?;
+    if masked {
+        apply_mask(&mut payload, mask);
+    }
+    Ok(Frame { fin, opcode, payload })
+}
+
+fn encode_frame(opcode: u8, payload: &[u8], mask: Option<[u8; 4]>) -> Vec<u8> {
+    let mask_bit = if mask.is_some() { 0x80 } else { 0 };
+    let mut frame = vec![0x80 | opcode];
+    match payload.len() {
+        len @ 0..=125 => frame.push(mask_bit | len as u8),
+        len @ 126..=0xFFFF => {
+            frame.push(mask_bit | 126);
+            frame.extend((len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(mask_bit | 127);
+            frame.extend((len as u64).to_be_bytes());
+        }
+    }
+    let mut payload = payload.to_vec();
+    if let Some(mask) = mask {
+        frame.extend(mask);
+        apply_mask(&mut payload, mask);
+    }
+    frame.extend(payload);
+    frame
+}
+
+fn apply_mask(data: &mut [u8], mask: [u8; 4]) {
+    for (i, byte) in data.iter_mut().enumerate() {
+        *byte ^= mask[i % 4];
+    }
+}
+
+// Máscara del cliente: no tiene que ser criptográfica, solo distinta en cada mensaje
+fn random_mask() -> [u8; 4] {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos() as u64;
+    let mut x = nanos ^ COUNTER.fetch_add(0x9E37_79B9_7F4A_7C15, Ordering::Relaxed);
+    x ^= x << 13;
+    x ^= x >> 7;
+    x ^= x << 17;
+    (x as u32).to_be_bytes()
+}
@@ -23,6 +23,63 @@ pub fn print_hashmap(hashmap: &HashMap<String, Value>) {
 
 }
 
+// Fecha y hora UTC en formato RFC 3339 (2024-10-19T15:04:05Z) a partir de segundos desde 1970
+pub fn format_utc_timestamp(secs: u64) -> String {
+    let days = (secs / 86_400) as i64;
+    let seconds_of_day = secs % 86_400;
+
+    // Conversión de días a fecha civil (algoritmo de Howard Hinnant)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        seconds_of_day / 3_600,
+        seconds_of_day % 3_600 / 60,
+        seconds_of_day % 60
+    )
+}
+
+// Fecha y hora actual en formato RFC 3339
+pub fn now_utc() -> String {
+    format_utc_timestamp(std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_secs())
+}
+
+// Identificador legible a partir de un nombre: "Sergio Pérez" → "sergio-perez".
+// Se quitan los acentos y cualquier otro carácter separa palabras; puede quedar vacío.
+pub fn slugify(name: &str) -> String {
+    let mut slug = String::new();
+    for c in name.chars().flat_map(char::to_lowercase) {
+        let c = match c {
+            'á' | 'à' | 'â' | 'ä' | 'ã' | 'å' => 'a',
+            'é' | 'è' | 'ê' | 'ë' => 'e',
+            'í' | 'ì' | 'î' | 'ï' => 'i',
+            'ó' | 'ò' | 'ô' | 'ö' | 'õ' | 'ø' => 'o',
+            'ú' | 'ù' | 'û' | 'ü' => 'u',
+            'ñ' => 'n',
+            'ç' => 'c',
+            'ý' | 'ÿ' => 'y',
+            c => c,
+        };
+        if c.is_ascii_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.trim_end_matches('-').to_string()
+}
+
 // Función para limpiar las cadenas de caracteres especiales
 pub fn clean_string(string: String) -> String {
     string.replace("\"", "").replace("\\", "")
@@ -40,7 +97,11 @@ pub fn parse_request_into_hashmap(mut buf_reader: BufReader<&TcpStream>) -> Hash
     let parts: Vec<&str> = request_line.split_whitespace().collect();
     if parts.len() >= 3 {
         result.insert("method".to_string(), json!(clean_string(parts[0].to_string())));
-        result.insert("path".to_string(), json!(clean_string(parts[1].to_string())));
+        // Separar la ruta de los parámetros de consulta (?clave=valor&...)
+        let target = clean_string(parts[1].to_string());
+        let (path, query) = target.split_once('?').unwrap_or((&target, ""));
+        result.insert("path".to_string(), json!(path));
+        result.insert("query".to_string(), Value::Object(parse_query_string(query)));
         result.insert("version".to_string(), json!(clean_string(parts[2].to_string())));
     }
 
@@ -82,7 +143,8 @@ pub fn parse_request_into_hashmap(mut buf_reader: BufReader<&TcpStream>) -> Hash
                 result.insert("body".to_string(), json_body); // Insertar como objeto JSON si es válido
             }
             Err(_) => {
-                result.insert("body".to_string(), json!(clean_string(body_str))); // Si no es JSON, almacenar como cadena
+                // Si no es JSON (p. ej. CSV o YAML), almacenar el texto tal cual
+                result.insert("body".to_string(), json!(body_str));
             }
         }
     }
@@ -95,6 +157,53 @@ pub fn parse_request_into_hashmap(mut buf_reader: BufReader<&TcpStream>) -> Hash
     result
 }
 
+// Decodificar los caracteres codificados como %XX (y '+' como espacio si `plus_as_space`)
+pub fn percent_decode(value: &str, plus_as_space: bool) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+            (b'+', _) if plus_as_space => decoded.push(b' '),
+            (byte, _) => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+// Codificar un valor para usarlo en una URL; solo se dejan tal cual letras, números, '-', '_', '.', '~' y ','
+pub fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b',' => (byte as char).to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+// Convertir "a=1&b=2" en un objeto JSON con los valores decodificados
+pub fn parse_query_string(query: &str) -> serde_json::Map<String, Value> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (percent_decode(key, true), json!(percent_decode(value, true)))
+        })
+        .collect()
+}
+
 // Leer todas las líneas del BufReader y devolverlas como un String
 pub fn read_lines_to_string(buf_reader: &mut BufReader<&TcpStream>) -> Result<String, std::io::Error> {
     let mut result = String::new();
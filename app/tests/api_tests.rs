@@ -102,4 +102,863 @@ async fn test_get_team() {
         .expect("Failed to send GET request");
 
     assert_eq!(get_response.status(), 200, "Debería obtenerse el equipo correctamente");
-}
\ No newline at end of file
+}
+
+#[tokio::test]
+async fn test_post_invalid_team() {
+    let client = reqwest::Client::new();
+
+    // Un equipo sin nombre y con pilotos como texto debe rechazarse con 422
+    let invalid_team = serde_json::json!({
+        "name": "",
+        "drivers": "Nombre Apellido"
+    });
+
+    let response = client
+        .post("http://localhost:7000/api/escuderias")
+        .json(&invalid_team)
+        .send()
+        .await
+        .expect("Failed to send POST request");
+
+    assert_eq!(response.status(), 422, "El equipo inválido debería rechazarse");
+
+    let body: serde_json::Value = response.json().await.expect("La respuesta debería ser JSON");
+    assert_eq!(body["errors"][0]["field"], "drivers");
+}
+
+
+#[tokio::test]
+async fn test_admin_snapshots() {
+    let client = reqwest::Client::new();
+
+    // Guardar una copia y verificar que aparece en la lista
+    let response = client
+        .post("http://localhost:7000/api/admin/snapshots")
+        .send()
+        .await
+        .expect("Failed to send POST request");
+
+    assert_eq!(response.status(), 201, "La copia debería guardarse");
+    let body: serde_json::Value = response.json().await.expect("La respuesta debería ser JSON");
+    let id = body["snapshot"]["id"].as_str().unwrap().to_string();
+
+    let response = client
+        .get("http://localhost:7000/api/admin/snapshots")
+        .send()
+        .await
+        .expect("Failed to send GET request");
+
+    assert_eq!(response.status(), 200);
+    let body: serde_json::Value = response.json().await.expect("La respuesta debería ser JSON");
+    assert!(body["snapshots"].as_array().unwrap().iter().any(|s| s["id"] == id.as_str()));
+
+    // Restaurar una copia inexistente
+    let response = client
+        .post("http://localhost:7000/api/admin/snapshots/f1_data-0/restore")
+        .send()
+        .await
+        .expect("Failed to send POST request");
+
+    assert_eq!(response.status(), 404, "La copia no existe");
+}
+
+#[tokio::test]
+async fn test_datasets() {
+    let client = reqwest::Client::new();
+
+    // El conjunto por defecto también responde con su nombre
+    let response = client
+        .get("http://localhost:7000/api/default/escuderias")
+        .send()
+        .await
+        .expect("Failed to send GET request");
+
+    assert_eq!(response.status(), 200);
+    let body: serde_json::Value = response.json().await.expect("La respuesta debería ser JSON");
+    assert!(body["teams"].is_array());
+
+    // Un conjunto que no está configurado
+    let response = client
+        .get("http://localhost:7000/api/1950/escuderias")
+        .send()
+        .await
+        .expect("Failed to send GET request");
+
+    assert_eq!(response.status(), 404, "El conjunto de datos no existe");
+}
+
+#[tokio::test]
+async fn test_driver_crud_operations_in_order() {
+    let client = reqwest::Client::new();
+    let base = "http://localhost:7000/api/escuderias/Mercedes/pilotos";
+
+    // 1. Test POST: Añadir un piloto
+    {
+        let new_driver = serde_json::json!({
+            "name": "Nombre Apellido",
+            "age": 23,
+            "nationality": "British"
+        });
+
+        let response = client
+            .post(base)
+            .json(&new_driver)
+            .send()
+            .await
+            .expect("Failed to send POST request");
+
+        assert_eq!(response.status(), 201, "El piloto debería añadirse correctamente");
+    }
+
+    // 2. Test GET: Obtener los pilotos y el piloto añadido
+    {
+        let response = client
+            .get(base)
+            .send()
+            .await
+            .expect("Failed to send GET request");
+
+        assert_eq!(response.status(), 200);
+        let body: serde_json::Value = response.json().await.expect("La respuesta debería ser JSON");
+        assert!(body["drivers"].as_array().unwrap().iter().any(|d| d["name"] == "Nombre Apellido"));
+
+        let response = client
+            .get(format!("{}/Nombre Apellido", base))
+            .send()
+            .await
+            .expect("Failed to send GET request");
+
+        assert_eq!(response.status(), 200, "Debería obtenerse el piloto correctamente");
+        let body: serde_json::Value = response.json().await.expect("La respuesta debería ser JSON");
+        assert_eq!(body["driver"]["age"], 23);
+    }
+
+    // 3. Test PUT: Reemplazar el piloto añadido
+    {
+        let updated_driver = serde_json::json!({
+            "name": "Nombre Apellido",
+            "age": 24,
+            "nationality": "Australian"
+        });
+
+        let response = client
+            .put(format!("{}/Nombre Apellido", base))
+            .json(&updated_driver)
+            .send()
+            .await
+            .expect("Failed to send PUT request");
+
+        assert_eq!(response.status(), 200, "El piloto debería actualizarse correctamente");
+    }
+
+    // 4. Test DELETE: Eliminar el piloto añadido
+    {
+        let response = client
+            .delete(format!("{}/Nombre Apellido", base))
+            .send()
+            .await
+            .expect("Failed to send DELETE request");
+
+        assert_eq!(response.status(), 200, "El piloto debería eliminarse correctamente");
+
+        let response = client
+            .get(format!("{}/Nombre Apellido", base))
+            .send()
+            .await
+            .expect("Failed to send GET request");
+
+        assert_eq!(response.status(), 404, "El piloto ya no existe");
+    }
+}
+
+#[tokio::test]
+async fn test_get_single_team_with_fields() {
+    let client = reqwest::Client::new();
+
+    let response = client
+        .get("http://localhost:7000/api/escuderias/Mercedes?fields=name,drivers.name")
+        .send()
+        .await
+        .expect("Failed to send GET request");
+
+    assert_eq!(response.status(), 200, "Debería obtenerse la escudería");
+    let body: serde_json::Value = response.json().await.expect("La respuesta debería ser JSON");
+    assert_eq!(body["team"]["name"], "Mercedes");
+    assert!(body["team"]["drivers"][0]["name"].is_string());
+    assert!(body["team"]["drivers"][0].get("age").is_none(), "Solo se incluyen los campos pedidos");
+
+    // La selección de campos también aplica a la lista
+    let response = client
+        .get("http://localhost:7000/api/escuderias?fields=name")
+        .send()
+        .await
+        .expect("Failed to send GET request");
+
+    assert_eq!(response.status(), 200);
+    let body: serde_json::Value = response.json().await.expect("La respuesta debería ser JSON");
+    assert!(body["teams"].as_array().unwrap().iter().all(|t| t.get("drivers").is_none()));
+
+    let response = client
+        .get("http://localhost:7000/api/escuderias/No Existe")
+        .send()
+        .await
+        .expect("Failed to send GET request");
+
+    assert_eq!(response.status(), 404, "La escudería no existe");
+}
+
+#[tokio::test]
+async fn test_list_pagination() {
+    let client = reqwest::Client::new();
+
+    let response = client
+        .get("http://localhost:7000/api/escuderias?sort=-name&limit=1")
+        .send()
+        .await
+        .expect("Failed to send GET request");
+
+    assert_eq!(response.status(), 200);
+    let link = response.headers().get("link").expect("Debería incluir el encabezado Link").to_str().unwrap().to_string();
+    assert!(link.contains("rel=\"next\""), "Debería haber página siguiente: {}", link);
+
+    let body: serde_json::Value = response.json().await.expect("La respuesta debería ser JSON");
+    assert_eq!(body["teams"].as_array().unwrap().len(), 1);
+    assert!(body["total"].as_u64().unwrap() > 1);
+    let cursor = body["next_cursor"].as_str().expect("Debería incluir next_cursor").to_string();
+    assert!(link.contains(&format!("cursor={}", cursor)));
+
+    // La página siguiente empieza después de la primera escudería
+    let response = client
+        .get(format!("http://localhost:7000/api/escuderias?sort=-name&limit=1&cursor={}", cursor))
+        .send()
+        .await
+        .expect("Failed to send GET request");
+
+    assert_eq!(response.status(), 200);
+    let next: serde_json::Value = response.json().await.expect("La respuesta debería ser JSON");
+    assert_ne!(next["teams"][0]["name"], body["teams"][0]["name"]);
+
+    let response = client
+        .get("http://localhost:7000/api/escuderias?foo=bar")
+        .send()
+        .await
+        .expect("Failed to send GET request");
+
+    assert_eq!(response.status(), 422, "El filtro no es un campo de la escudería");
+
+    let response = client
+        .get("http://localhost:7000/api/escuderias?limit=1000")
+        .send()
+        .await
+        .expect("Failed to send GET request");
+
+    assert_eq!(response.status(), 422, "El límite es demasiado grande");
+}
+
+#[tokio::test]
+async fn test_search() {
+    let response = reqwest::get("http://localhost:7000/api/search?q=hamil")
+        .await
+        .expect("Failed to send GET request");
+
+    assert_eq!(response.status(), 200);
+    let body: serde_json::Value = response.json().await.expect("La respuesta debería ser JSON");
+    let results = body["results"].as_array().unwrap();
+    assert!(
+        results.iter().any(|r| r["driver"] == "Lewis Hamilton"),
+        "Debería encontrar a Lewis Hamilton: {}",
+        body
+    );
+
+    let response = reqwest::get("http://localhost:7000/api/search?q=")
+        .await
+        .expect("Failed to send GET request");
+    assert_eq!(response.status(), 422);
+}
+
+#[tokio::test]
+async fn test_patch_team() {
+    let client = reqwest::Client::new();
+    let url = "http://localhost:7000/api/escuderias/Patch Team";
+
+    let new_team = serde_json::json!({
+        "name": "Patch Team",
+        "base": "Brackley",
+        "drivers": [{ "name": "Piloto Patch", "age": 25, "nationality": "British" }]
+    });
+    let response = client
+        .post("http://localhost:7000/api/escuderias")
+        .json(&new_team)
+        .send()
+        .await
+        .expect("Failed to send POST request");
+    assert_eq!(response.status(), 201);
+
+    let response = client
+        .patch(url)
+        .header("Content-Type", "application/merge-patch+json")
+        .body(r#"{"base": null}"#)
+        .send()
+        .await
+        .expect("Failed to send PATCH request");
+    assert_eq!(response.status(), 200, "El Merge Patch debería aplicarse");
+
+    let response = client
+        .patch(url)
+        .header("Content-Type", "application/json-patch+json")
+        .body(r#"[{"op": "test", "path": "/drivers/0/age", "value": 99}, {"op": "replace", "path": "/drivers/0/age", "value": 30}]"#)
+        .send()
+        .await
+        .expect("Failed to send PATCH request");
+    assert_eq!(response.status(), 409, "Un test fallido debería responder 409");
+
+    let body: serde_json::Value = client.get(url).send().await.unwrap().json().await.unwrap();
+    assert_eq!(body["team"].get("base"), None);
+    assert_eq!(body["team"]["drivers"][0]["age"], 25);
+
+    let response = client
+        .patch(url)
+        .json(&serde_json::json!({ "base": "Enstone" }))
+        .send()
+        .await
+        .expect("Failed to send PATCH request");
+    assert_eq!(response.status(), 415);
+
+    let response = client.delete(url).send().await.expect("Failed to send DELETE request");
+    assert_eq!(response.status(), 200);
+}
+
+#[tokio::test]
+async fn test_etag_and_if_match() {
+    let client = reqwest::Client::new();
+    let url = "http://localhost:7000/api/escuderias/ETag Team";
+    let team = |age: u32| serde_json::json!({
+        "name": "ETag Team",
+        "drivers": [{ "name": "Piloto ETag", "age": age, "nationality": "British" }]
+    });
+
+    let response = client
+        .post("http://localhost:7000/api/escuderias")
+        .json(&team(25))
+        .send()
+        .await
+        .expect("Failed to send POST request");
+    assert_eq!(response.status(), 201);
+
+    let response = client.get(url).send().await.expect("Failed to send GET request");
+    assert_eq!(response.status(), 200);
+    let etag = response.headers().get("etag").expect("Debería incluir ETag").to_str().unwrap().to_string();
+    assert!(etag.starts_with('"') && etag.ends_with('"'), "El ETag debería ser fuerte: {}", etag);
+
+    // Sin cambios, If-None-Match responde 304 sin cuerpo
+    let response = client.get(url).header("If-None-Match", &etag).send().await.expect("Failed to send GET request");
+    assert_eq!(response.status(), 304);
+
+    // PUT con la versión actual y luego con la anterior
+    let response = client.put(url).header("If-Match", &etag).json(&team(26)).send().await.expect("Failed to send PUT request");
+    assert_eq!(response.status(), 200);
+    let response = client.put(url).header("If-Match", &etag).json(&team(27)).send().await.expect("Failed to send PUT request");
+    assert_eq!(response.status(), 412, "Una versión anterior debería responder 412");
+    let response = client.delete(url).header("If-Match", &etag).send().await.expect("Failed to send DELETE request");
+    assert_eq!(response.status(), 412);
+
+    let response = client.get(url).header("If-None-Match", &etag).send().await.expect("Failed to send GET request");
+    assert_eq!(response.status(), 200, "El equipo cambió, no debería responder 304");
+    let etag = response.headers().get("etag").unwrap().to_str().unwrap().to_string();
+
+    let response = client.delete(url).header("If-Match", &etag).send().await.expect("Failed to send DELETE request");
+    assert_eq!(response.status(), 200);
+}
+
+#[tokio::test]
+async fn test_batch() {
+    let client = reqwest::Client::new();
+    let driver = serde_json::json!({ "name": "Piloto Lote", "age": 24, "nationality": "French" });
+
+    // Crear dos escuderías y mover el piloto de una a otra
+    let operations = serde_json::json!({ "operations": [
+        { "method": "POST", "path": "/escuderias", "body": { "name": "Batch A", "drivers": [driver] } },
+        { "method": "POST", "path": "/escuderias", "body": { "name": "Batch B", "drivers": [] } },
+        { "method": "DELETE", "path": "/escuderias/Batch A/pilotos/Piloto Lote" },
+        { "method": "POST", "path": "/escuderias/Batch B/pilotos", "body": driver }
+    ]});
+    let response = client.post("http://localhost:7000/api/batch").json(&operations).send().await.expect("Failed to send POST request");
+    assert_eq!(response.status(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["results"].as_array().unwrap().len(), 4);
+    assert_eq!(body["results"][0]["status"], 201);
+
+    // La segunda operación falla: la primera tampoco se aplica
+    let operations = serde_json::json!({ "operations": [
+        { "method": "DELETE", "path": "/escuderias/Batch A" },
+        { "method": "PUT", "path": "/escuderias/Batch B/pilotos/No Existe", "body": driver }
+    ]});
+    let response = client.post("http://localhost:7000/api/batch").json(&operations).send().await.expect("Failed to send POST request");
+    assert_eq!(response.status(), 400);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["index"], 1);
+    assert_eq!(body["results"][0]["status"], 424);
+    assert_eq!(body["results"][1]["status"], 400);
+
+    let response = client.get("http://localhost:7000/api/escuderias/Batch B/pilotos").send().await.unwrap();
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert!(body.to_string().contains("Piloto Lote"), "El piloto debería estar en Batch B: {}", body);
+
+    let operations = serde_json::json!({ "operations": [
+        { "method": "DELETE", "path": "/escuderias/Batch A" },
+        { "method": "DELETE", "path": "/escuderias/Batch B" }
+    ]});
+    let response = client.post("http://localhost:7000/api/batch").json(&operations).send().await.expect("Failed to send POST request");
+    assert_eq!(response.status(), 200);
+}
+
+#[tokio::test]
+async fn test_transfer_driver() {
+    let client = reqwest::Client::new();
+    let driver = serde_json::json!({ "name": "Piloto Traspaso", "age": 22, "nationality": "Danish" });
+    for team in [serde_json::json!({ "name": "Traspaso A", "drivers": [driver] }), serde_json::json!({ "name": "Traspaso B", "drivers": [] })] {
+        let response = client.post("http://localhost:7000/api/escuderias").json(&team).send().await.expect("Failed to send POST request");
+        assert_eq!(response.status(), 201);
+    }
+
+    let url = "http://localhost:7000/api/pilotos/Piloto Traspaso/transfer";
+    let response = client.post(url).json(&serde_json::json!({ "to": "Traspaso B" })).send().await.expect("Failed to send POST request");
+    assert_eq!(response.status(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["transfer"]["from"], "Traspaso A");
+    assert!(body["transfer"]["date"].is_string());
+
+    let response = client.get("http://localhost:7000/api/escuderias/Traspaso B/pilotos/Piloto Traspaso").send().await.unwrap();
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["driver"]["transfers"][0]["to"], "Traspaso B", "{}", body);
+
+    // Traspasar a una escudería que no existe no cambia nada
+    let response = client.post(url).json(&serde_json::json!({ "to": "No Existe" })).send().await.unwrap();
+    assert_eq!(response.status(), 400);
+    let response = client.post(url).json(&serde_json::json!({ "hacia": "Traspaso A" })).send().await.unwrap();
+    assert_eq!(response.status(), 422);
+
+    for team in ["Traspaso A", "Traspaso B"] {
+        let response = client.delete(format!("http://localhost:7000/api/escuderias/{}", team)).send().await.unwrap();
+        assert_eq!(response.status(), 200);
+    }
+}
+
+#[tokio::test]
+async fn test_stable_ids_and_name_redirects() {
+    let client = reqwest::Client::new();
+    let team = serde_json::json!({ "name": "Equipo Identificado", "drivers": [{ "name": "Piloto Identificado", "age": 23, "nationality": "Polish" }] });
+    let response = client.post("http://localhost:7000/api/escuderias").json(&team).send().await.expect("Failed to send POST request");
+    assert_eq!(response.status(), 201);
+    assert_eq!(response.headers()["location"], "/api/escuderias/equipo-identificado");
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["id"], "equipo-identificado");
+
+    // Las rutas con nombres redirigen a las rutas con identificadores
+    let no_redirects = reqwest::Client::builder().redirect(reqwest::redirect::Policy::none()).build().unwrap();
+    let response = no_redirects
+        .get("http://localhost:7000/api/escuderias/Equipo Identificado/pilotos/Piloto Identificado")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 307);
+    assert_eq!(response.headers()["location"], "/api/escuderias/equipo-identificado/pilotos/piloto-identificado");
+
+    // Renombrar no cambia la URL
+    let renamed = serde_json::json!({ "name": "Equipo Renombrado", "drivers": [{ "name": "Piloto Identificado", "age": 24, "nationality": "Polish" }] });
+    let response = client.put("http://localhost:7000/api/escuderias/equipo-identificado").json(&renamed).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    let response = client.get("http://localhost:7000/api/escuderias/equipo-identificado").send().await.unwrap();
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["team"]["name"], "Equipo Renombrado");
+    assert_eq!(body["team"]["drivers"][0]["id"], "piloto-identificado");
+
+    // Los clientes que usan nombres siguen la redirección
+    let response = client.delete("http://localhost:7000/api/escuderias/Equipo Renombrado").send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    let response = client.get("http://localhost:7000/api/escuderias/equipo-identificado").send().await.unwrap();
+    assert_eq!(response.status(), 404);
+}
+
+#[tokio::test]
+async fn test_audit_log() {
+    let client = reqwest::Client::new();
+    let team = serde_json::json!({ "name": "Equipo Auditado", "drivers": [{ "name": "Piloto Auditado", "age": 23, "nationality": "Polish" }] });
+    let response = client.post("http://localhost:7000/api/escuderias").json(&team).send().await.expect("Failed to send POST request");
+    assert_eq!(response.status(), 201);
+
+    let response = client
+        .patch("http://localhost:7000/api/escuderias/equipo-auditado/pilotos/piloto-auditado")
+        .json(&serde_json::json!({ "age": 24 }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let response = client.delete("http://localhost:7000/api/escuderias/equipo-auditado").send().await.unwrap();
+    assert_eq!(response.status(), 200);
+
+    // Los cambios de la escudería y de sus pilotos, en orden
+    let response = client
+        .get("http://localhost:7000/api/audit?entity=escuderias/equipo-auditado&since=2024-01-01")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    let entries = body["entries"].as_array().unwrap();
+    let operations: Vec<&str> = entries.iter().rev().take(3).rev().map(|e| e["operation"].as_str().unwrap()).collect();
+    assert_eq!(operations, vec!["post_team", "patch_driver", "delete_team"]);
+    let patch = &entries[entries.len() - 2];
+    assert_eq!(patch["entity"], "escuderias/equipo-auditado/pilotos/piloto-auditado");
+    assert_eq!(patch["diff"], serde_json::json!([{ "op": "replace", "path": "/age", "value": 24 }]));
+    assert!(patch["session"].as_str().unwrap().starts_with("session"));
+    assert!(!patch["client_ip"].as_str().unwrap().is_empty());
+
+    // Las operaciones de un lote se registran una por una
+    let team = serde_json::json!({ "name": "Equipo Lote Auditado", "drivers": [] });
+    let response = client.post("http://localhost:7000/api/escuderias").json(&team).send().await.unwrap();
+    assert_eq!(response.status(), 201);
+    let response = client
+        .post("http://localhost:7000/api/batch")
+        .json(&serde_json::json!({ "operations": [
+            { "method": "PATCH", "path": "/escuderias/equipo-lote-auditado", "body": { "base": "Lote" } },
+            { "method": "DELETE", "path": "/escuderias/equipo-lote-auditado" }
+        ]}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let body: serde_json::Value = client
+        .get("http://localhost:7000/api/audit?entity=escuderias/equipo-lote-auditado")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let entries = body["entries"].as_array().unwrap();
+    let operations: Vec<&str> = entries.iter().rev().take(3).rev().map(|e| e["operation"].as_str().unwrap()).collect();
+    assert_eq!(operations, vec!["post_team", "patch_team", "delete_team"]);
+    let delete = entries.last().unwrap();
+    assert_eq!(delete["before"]["base"], "Lote");
+    assert!(delete["after"].is_null());
+
+    let response = client.get("http://localhost:7000/api/audit?since=ayer").send().await.unwrap();
+    assert_eq!(response.status(), 422);
+}
+
+#[tokio::test]
+async fn test_soft_delete_and_restore() {
+    let client = reqwest::Client::new();
+    let team = serde_json::json!({ "name": "Equipo Borrado", "drivers": [{ "name": "Piloto Borrado", "age": 25, "nationality": "Danish" }] });
+    let response = client.post("http://localhost:7000/api/escuderias").json(&team).send().await.unwrap();
+    assert_eq!(response.status(), 201);
+    let response = client.delete("http://localhost:7000/api/escuderias/equipo-borrado").send().await.unwrap();
+    assert_eq!(response.status(), 200);
+
+    // Eliminada: no se encuentra ni aparece en la lista, salvo con ?include_deleted=true
+    let response = client.get("http://localhost:7000/api/escuderias/equipo-borrado").send().await.unwrap();
+    assert_eq!(response.status(), 404);
+    let listed = |include_deleted: bool| {
+        let client = client.clone();
+        async move {
+            let url = format!("http://localhost:7000/api/escuderias?id=equipo-borrado&include_deleted={}", include_deleted);
+            let body: serde_json::Value = client.get(url).send().await.unwrap().json().await.unwrap();
+            body["teams"].as_array().unwrap().clone()
+        }
+    };
+    assert!(listed(false).await.is_empty());
+    let deleted = listed(true).await;
+    assert_eq!(deleted.len(), 1);
+    assert!(deleted[0]["deleted_at"].is_string());
+
+    let response = client.post("http://localhost:7000/api/escuderias/equipo-borrado/restore").send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["team"]["id"], "equipo-borrado");
+    assert!(body["team"].get("deleted_at").is_none());
+    let response = client.get("http://localhost:7000/api/escuderias/equipo-borrado").send().await.unwrap();
+    assert_eq!(response.status(), 200);
+
+    // Solo se restauran escuderías eliminadas
+    let response = client.post("http://localhost:7000/api/escuderias/equipo-borrado/restore").send().await.unwrap();
+    assert_eq!(response.status(), 400);
+}
+
+// Leer el stream de eventos hasta encontrar uno que cumpla `found`; devuelve su id, tipo y datos
+async fn next_event(
+    response: &mut reqwest::Response,
+    buffer: &mut String,
+    found: impl Fn(&str, &serde_json::Value) -> bool,
+) -> (u64, String, serde_json::Value) {
+    loop {
+        while let Some(end) = buffer.find("\n\n") {
+            let message: String = buffer.drain(..end + 2).collect();
+            let field = |name: &str| message.lines().find_map(|line| line.strip_prefix(name)).unwrap_or_default().to_string();
+            let (id, event) = (field("id: "), field("event: "));
+            let data = serde_json::from_str(&field("data: ")).unwrap_or_default();
+            if !event.is_empty() && found(&event, &data) {
+                return (id.parse().unwrap(), event, data);
+            }
+        }
+        let chunk = tokio::time::timeout(std::time::Duration::from_secs(10), response.chunk())
+            .await
+            .expect("No llegó el evento")
+            .unwrap()
+            .expect("Se cerró el stream de eventos");
+        buffer.push_str(&String::from_utf8_lossy(&chunk));
+    }
+}
+
+#[tokio::test]
+async fn test_change_events() {
+    let client = reqwest::Client::new();
+    let mut events = client.get("http://localhost:7000/api/escuderias/events").send().await.unwrap();
+    assert_eq!(events.status(), 200);
+    assert_eq!(events.headers()["content-type"], "text/event-stream");
+
+    let team = serde_json::json!({ "name": "Equipo SSE", "drivers": [{ "name": "Piloto SSE", "age": 21, "nationality": "Thai" }] });
+    let response = client.post("http://localhost:7000/api/escuderias").json(&team).send().await.unwrap();
+    assert_eq!(response.status(), 201);
+    let response = client.delete("http://localhost:7000/api/escuderias/equipo-sse").send().await.unwrap();
+    assert_eq!(response.status(), 200);
+
+    let mut buffer = String::new();
+    let is_test_team = |_: &str, data: &serde_json::Value| data["id"] == "equipo-sse";
+    let (created_id, event, data) = next_event(&mut events, &mut buffer, is_test_team).await;
+    assert_eq!(event, "created");
+    assert_eq!(data["team"]["drivers"][0]["name"], "Piloto SSE");
+    let (_, event, data) = next_event(&mut events, &mut buffer, is_test_team).await;
+    assert_eq!((event.as_str(), &data["team"]), ("deleted", &serde_json::Value::Null));
+
+    // Al reconectar con Last-Event-ID se reciben los eventos que siguen
+    let mut resumed = client
+        .get("http://localhost:7000/api/escuderias/events")
+        .header("Last-Event-ID", (created_id - 1).to_string())
+        .send()
+        .await
+        .unwrap();
+    let (id, event, _) = next_event(&mut resumed, &mut String::new(), |_, _| true).await;
+    assert_eq!((id, event.as_str()), (created_id, "created"));
+
+    let response = client.get("http://localhost:7000/api/no_existe/escuderias/events").send().await.unwrap();
+    assert_eq!(response.status(), 404);
+}
+
+#[tokio::test]
+async fn test_webhooks() {
+    use std::io::{BufReader, Write};
+    use http_server_proyecto1_so::utils::parse_request_into_hashmap;
+
+    // Receptor local que guarda el cuerpo de cada entrega
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let receiver_url = format!("http://{}/hook", listener.local_addr().unwrap());
+    let received = std::sync::Arc::new(std::sync::Mutex::new(Vec::<serde_json::Value>::new()));
+    let received_clone = std::sync::Arc::clone(&received);
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let request = parse_request_into_hashmap(BufReader::new(&stream));
+            received_clone.lock().unwrap().push(request.get("body").cloned().unwrap_or_default());
+            let _ = stream.write_all(b"HTTP/1.1 204 No Content\r\nConnection: close\r\n\r\n");
+        }
+    });
+
+    let client = reqwest::Client::new();
+    let response = client.post("http://localhost:7000/api/webhooks").json(&serde_json::json!({ "url": "no es una url" })).send().await.unwrap();
+    assert_eq!(response.status(), 422);
+    let response = client
+        .post("http://localhost:7000/api/webhooks")
+        .json(&serde_json::json!({ "url": receiver_url, "events": ["created"] }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 201);
+    let body: serde_json::Value = response.json().await.unwrap();
+    let webhook_id = body["webhook"]["id"].as_str().unwrap().to_string();
+    assert_eq!(body["webhook"]["secret"].as_str().unwrap().len(), 64);
+
+    let team = serde_json::json!({ "name": "Equipo Webhook", "drivers": [] });
+    let response = client.post("http://localhost:7000/api/escuderias").json(&team).send().await.unwrap();
+    assert_eq!(response.status(), 201);
+
+    // La entrega es asíncrona: esperar a que llegue al receptor
+    let mut delivered = None;
+    for _ in 0..100 {
+        delivered = received.lock().unwrap().iter().find(|body| body["team_id"] == "equipo-webhook").cloned();
+        if delivered.is_some() {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+    let delivered = delivered.expect("El webhook no recibió la escudería");
+    assert_eq!((&delivered["event"], &delivered["team"]["name"]), (&serde_json::json!("created"), &serde_json::json!("Equipo Webhook")));
+
+    let body: serde_json::Value = client.get("http://localhost:7000/api/webhooks").send().await.unwrap().json().await.unwrap();
+    let listed = body["webhooks"].as_array().unwrap().iter().find(|webhook| webhook["id"] == webhook_id.as_str()).unwrap();
+    assert!(listed.get("secret").is_none());
+    let response = client.get("http://localhost:7000/api/webhooks/dead-letters").send().await.unwrap();
+    assert_eq!(response.status(), 200);
+
+    let url = format!("http://localhost:7000/api/webhooks/{}", webhook_id);
+    assert_eq!(client.delete(&url).send().await.unwrap().status(), 200);
+    assert_eq!(client.delete(&url).send().await.unwrap().status(), 404);
+}
+
+#[tokio::test]
+async fn test_seasons_and_standings() {
+    let client = reqwest::Client::new();
+    let team = serde_json::json!({ "name": "Equipo Temporada", "drivers": [
+        { "name": "Piloto Temporada", "age": 24, "nationality": "Finnish" },
+        { "name": "Piloto Temporada Dos", "age": 29, "nationality": "Finnish" }
+    ] });
+    let response = client.post("http://localhost:7000/api/escuderias").json(&team).send().await.unwrap();
+    assert_eq!(response.status(), 201);
+
+    let race = serde_json::json!({ "name": "Gran Premio de Mónaco", "circuit": "Circuit de Monaco", "date": "2021-05-23", "results": [
+        { "position": 2, "driver": "piloto-temporada-dos", "team": "equipo-temporada" },
+        { "position": 1, "driver": "piloto-temporada", "team": "equipo-temporada", "fastest_lap": true }
+    ] });
+    let response = client.put("http://localhost:7000/api/seasons/2021").json(&serde_json::json!({ "races": [race] })).send().await.unwrap();
+    assert_eq!(response.status(), 201);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["season"]["races"][0]["round"], 1);
+    assert_eq!(body["season"]["races"][0]["results"][0]["points"], 26);
+
+    // La segunda carrera se agrega a la temporada existente
+    let mut second = race.clone();
+    second["date"] = serde_json::json!("2021-06-06");
+    second["results"][0]["position"] = serde_json::json!(1);
+    second["results"][1]["position"] = serde_json::json!(4);
+    let response = client.put("http://localhost:7000/api/seasons/2021/races/2").json(&second).send().await.unwrap();
+    assert_eq!(response.status(), 201);
+
+    let body: serde_json::Value = client.get("http://localhost:7000/api/seasons/2021/standings/drivers").send().await.unwrap().json().await.unwrap();
+    let standings: Vec<(String, u64)> = body["standings"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|s| (s["driver"].as_str().unwrap().to_string(), s["points"].as_u64().unwrap()))
+        .collect();
+    assert_eq!(standings, vec![("piloto-temporada-dos".to_string(), 43), ("piloto-temporada".to_string(), 39)]);
+    let body: serde_json::Value = client.get("http://localhost:7000/api/seasons/2021/standings/constructors").send().await.unwrap().json().await.unwrap();
+    assert_eq!(body["standings"][0]["name"], "Equipo Temporada");
+    assert_eq!(body["standings"][0]["points"], 82);
+
+    // Errores: resultado inválido, temporada inexistente, año que no es un número
+    second["results"][0]["driver"] = serde_json::json!("nadie");
+    let response = client.put("http://localhost:7000/api/seasons/2021/races/3").json(&second).send().await.unwrap();
+    assert_eq!(response.status(), 422);
+    let response = client.get("http://localhost:7000/api/seasons/1999/standings/drivers").send().await.unwrap();
+    assert_eq!(response.status(), 404);
+    let response = client.get("http://localhost:7000/api/seasons/dos-mil").send().await.unwrap();
+    assert_eq!(response.status(), 400);
+
+    let response = client.delete("http://localhost:7000/api/seasons/2021").send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    let response = client.get("http://localhost:7000/api/seasons/2021").send().await.unwrap();
+    assert_eq!(response.status(), 404);
+}
+
+#[tokio::test]
+async fn test_import_and_export() {
+    let client = reqwest::Client::new();
+
+    let response = client.get("http://localhost:7000/api/export?format=csv").send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["content-type"], "text/csv; charset=utf-8");
+    let csv = response.text().await.unwrap();
+    assert!(csv.starts_with("team_id,team_name,driver_id,driver_name,driver_age,driver_nationality,driver_transfers"));
+    assert!(csv.contains("red-bull-racing,Red Bull Racing,max-verstappen,Max Verstappen,25,Dutch,"));
+    let response = client.get("http://localhost:7000/api/export?format=yaml").send().await.unwrap();
+    assert_eq!(response.headers()["content-type"], "application/yaml");
+    assert!(response.text().await.unwrap().starts_with("teams:\n"));
+
+    // El dry run informa los cambios sin aplicarlos
+    let sheet = "team_name,driver_name,driver_age,driver_nationality\nEquipo Importado,\"Piloto, Importado\",22,Brazilian\n";
+    let import = |dry_run: bool| {
+        client
+            .post(format!("http://localhost:7000/api/import?dry_run={}", dry_run))
+            .header("Content-Type", "text/csv")
+            .body(sheet)
+            .send()
+    };
+    let report: serde_json::Value = import(true).await.unwrap().json().await.unwrap();
+    assert_eq!(report["dry_run"], true);
+    assert_eq!(report["changes"], serde_json::json!([{ "change": "created", "id": "equipo-importado", "name": "Equipo Importado" }]));
+    let response = client.get("http://localhost:7000/api/escuderias/equipo-importado").send().await.unwrap();
+    assert_eq!(response.status(), 404);
+
+    let response = import(false).await.unwrap();
+    assert_eq!(response.status(), 200);
+    let body: serde_json::Value = client.get("http://localhost:7000/api/escuderias/equipo-importado").send().await.unwrap().json().await.unwrap();
+    assert_eq!(body["team"]["drivers"][0]["name"], "Piloto, Importado");
+    let report: serde_json::Value = import(false).await.unwrap().json().await.unwrap();
+    assert_eq!((report["changes"].as_array().unwrap().len(), &report["unchanged"]), (0, &serde_json::json!(1)));
+
+    // Formatos no soportados
+    let response = client.post("http://localhost:7000/api/import").header("Content-Type", "application/xml").body("<teams/>").send().await.unwrap();
+    assert_eq!(response.status(), 415);
+    let response = client.get("http://localhost:7000/api/export?format=xlsx").send().await.unwrap();
+    assert_eq!(response.status(), 400);
+    let response = client.post("http://localhost:7000/api/import?format=csv").body("team_name,driver_name,driver_age\nEquipo,Uno,viejo\n").send().await.unwrap();
+    assert_eq!(response.status(), 422);
+}
+
+#[tokio::test]
+async fn test_content_negotiation() {
+    let client = reqwest::Client::new();
+    let url = "http://localhost:7000/api/escuderias/red-bull-racing";
+    let get = |accept: &'static str| client.get(url).header("Accept", accept).send();
+
+    let response = get("application/json").await.unwrap();
+    assert_eq!(response.headers()["content-type"], "application/json");
+    assert_eq!(response.headers()["vary"], "Accept");
+    let json_etag = response.headers()["etag"].to_str().unwrap().to_string();
+    let team: serde_json::Value = response.json().await.unwrap();
+
+    // El mismo JSON en los demás formatos, cada uno con su propio ETag
+    let response = get("application/json; pretty=true").await.unwrap();
+    let pretty = response.text().await.unwrap();
+    assert!(pretty.starts_with("{\n  \"team\": {"));
+    assert_eq!(serde_json::from_str::<serde_json::Value>(&pretty).unwrap(), team);
+
+    let response = get("text/html;q=0.9, application/msgpack;q=0.8, text/csv;q=0.5").await.unwrap();
+    assert_eq!(response.headers()["content-type"], "application/msgpack");
+    let msgpack_etag = response.headers()["etag"].to_str().unwrap().to_string();
+    assert_ne!(msgpack_etag, json_etag);
+    let bytes = response.bytes().await.unwrap();
+    assert_eq!(rmp_serde::from_slice::<serde_json::Value>(&bytes).unwrap(), team);
+    let response = client.get(url).header("Accept", "application/msgpack").header("If-None-Match", &msgpack_etag).send().await.unwrap();
+    assert_eq!(response.status(), 304);
+
+    let response = get("text/csv").await.unwrap();
+    assert_eq!(response.headers()["content-type"], "text/csv; charset=utf-8");
+    let csv = response.text().await.unwrap();
+    assert!(csv.lines().next().unwrap().split(',').any(|column| column == "drivers.0.name"));
+    assert!(csv.contains("red-bull-racing"));
+
+    let response = get("application/xml").await.unwrap();
+    assert_eq!(response.headers()["content-type"], "application/xml; charset=utf-8");
+    assert!(response.text().await.unwrap().contains("<team><drivers><item>"));
+
+    // Los errores también se envían en el formato pedido
+    let response = client.get("http://localhost:7000/api/escuderias/no-existe").header("Accept", "application/xml").send().await.unwrap();
+    assert_eq!(response.status(), 404);
+    assert!(response.text().await.unwrap().contains("<error>"));
+
+    // Si ningún formato es aceptable se responde 406 sin ejecutar la operación
+    let response = client
+        .post("http://localhost:7000/api/escuderias")
+        .header("Accept", "text/html, application/json;q=0")
+        .json(&serde_json::json!({ "name": "Equipo 406", "drivers": [] }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 406);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert!(body["available"].as_array().unwrap().contains(&serde_json::json!("text/csv")));
+    let response = client.get("http://localhost:7000/api/escuderias/equipo-406").send().await.unwrap();
+    assert_eq!(response.status(), 404);
+}